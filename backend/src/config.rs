@@ -1,21 +1,45 @@
 use crate::error::RootErrorKind;
+use crate::parsing::ParseOptions;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
+use tracing::{event, Level};
 
-pub fn read_path(configFile: File) -> Result<PathBuf, Box<dyn Error>> {
-    let mut path_str = String::new();
+#[derive(Debug)]
+pub struct Config {
+    pub directory: PathBuf,
+    pub options: ParseOptions,
+}
 
-    BufReader::new(configFile).read_line(&mut path_str)?;
+// The first line of config.txt is the map directory, optionally followed by one option per line:
+//   elevation_scan=true
+//   feature_envelopes=true
+pub fn read_config(config_file: File) -> Result<Config, Box<dyn Error>> {
+    let mut lines = BufReader::new(config_file).lines();
+    let directory = read_path(&lines.next().transpose()?.unwrap_or_default())?;
 
-    if path_str.ends_with('\n') {
-        path_str.pop();
-        if path_str.ends_with('\r') {
-            path_str.pop();
+    let mut options = ParseOptions::default();
+    for line in lines {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || RootErrorKind::InvalidOption(line.to_string());
+        let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+        let value: bool = value.trim().parse().map_err(|_| invalid())?;
+        match key.trim() {
+            "elevation_scan" => options.elevation_scan = value,
+            "feature_envelopes" => options.feature_envelopes = value,
+            key => event!(Level::WARN, "Ignoring unknown option {key} in config.txt"),
         }
     }
-    path_str = path_str.replace("\"", "");
+    Ok(Config { directory, options })
+}
+
+fn read_path(line: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path_str = line.replace("\"", "");
     if path_str == "" {
         return Err(RootErrorKind::InvalidMapDirectory(
             "No directory specified! Define a map file directory in config.txt!".to_string(),
@@ -24,3 +48,37 @@ pub fn read_path(configFile: File) -> Result<PathBuf, Box<dyn Error>> {
     }
     return Ok(PathBuf::from(path_str));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn mock_config(content: &str) -> Result<Config, Box<dyn Error>> {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        read_config(file.reopen().unwrap())
+    }
+
+    #[test]
+    fn test_read_config_path_only() {
+        let config = mock_config("\"/data/maps\"\r\n").unwrap();
+        assert_eq!(config.directory, PathBuf::from("/data/maps"));
+        assert!(!config.options.elevation_scan);
+        assert!(!config.options.feature_envelopes);
+    }
+
+    #[test]
+    fn test_read_config_options() {
+        let config =
+            mock_config("/data/maps\n# Slower, but checks every record\nelevation_scan = true\n")
+                .unwrap();
+        assert!(config.options.elevation_scan);
+        assert!(!config.options.feature_envelopes);
+        let config = mock_config("/data/maps\nfeature_envelopes=true\n").unwrap();
+        assert!(config.options.feature_envelopes);
+        assert!(mock_config("/data/maps\nelevation_scan=yes\n").is_err());
+        assert!(mock_config("").is_err());
+    }
+}
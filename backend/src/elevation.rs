@@ -0,0 +1,159 @@
+use crate::index::Node;
+use crate::parsing::dted::DTEDGrid;
+use crate::spatial::Coordinate;
+use crate::MapType;
+use rstar::{RTree, AABB};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use tracing::{event, Level};
+
+// Mean earth radius in metres, for distances along a profile.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+// DTED tiles covering a point, highest resolution first. Looked up while the index is locked,
+// so the tiles can be read after it's released.
+pub fn candidate_tiles(index: &RTree<Node>, point: Coordinate) -> Vec<PathBuf> {
+    let mut candidates: Vec<(&PathBuf, &str)> = index
+        .locate_in_envelope_intersecting(&AABB::from_point(point))
+        .filter_map(|node| match node.map.as_ref() {
+            MapType::DTED(dted) => Some((
+                &dted.path,
+                node.metadata
+                    .tags
+                    .iter()
+                    .find(|(k, _)| k == "Level")
+                    .map_or("", |(_, v)| v.as_str()),
+            )),
+            _ => None,
+        })
+        .collect();
+    // DTED2 before DTED1, so voids in finer tiles fall back to coarser ones.
+    candidates.sort_by(|a, b| b.1.cmp(a.1));
+    candidates
+        .into_iter()
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+// Reads elevations from DTED tiles, keeping tiles open between points. Reads block, so this is
+// used off the async runtime.
+#[derive(Default)]
+pub struct ElevationSampler {
+    tiles: HashMap<PathBuf, Option<(DTEDGrid, BufReader<File>)>>,
+}
+
+impl ElevationSampler {
+    // Elevation in metres, from the first of the candidate tiles with data at the point.
+    pub fn elevation(&mut self, point: Coordinate, candidates: &[PathBuf]) -> Option<f64> {
+        for path in candidates {
+            let tile = self.tiles.entry(path.clone()).or_insert_with(|| {
+                let mut reader = match File::open(path) {
+                    Ok(f) => BufReader::new(f),
+                    Err(e) => {
+                        event!(Level::ERROR, "Failed to open {path:?}: {e:?}");
+                        return None;
+                    }
+                };
+                match DTEDGrid::from_reader(&mut reader) {
+                    Ok(grid) => Some((grid, reader)),
+                    Err(e) => {
+                        event!(Level::ERROR, "Failed to read DTED grid of {path:?}: {e}");
+                        None
+                    }
+                }
+            });
+            let Some((grid, reader)) = tile else {
+                continue;
+            };
+            match grid.elevation_at(reader, point) {
+                Ok(Some(elevation)) => return Some(elevation),
+                Ok(None) => {}
+                Err(e) => event!(Level::ERROR, "Failed to read elevation from {path:?}: {e}"),
+            }
+        }
+        None
+    }
+}
+
+// Great circle distance in metres.
+pub fn haversine((long_a, lat_a): Coordinate, (long_b, lat_b): Coordinate) -> f64 {
+    let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_long = (long_b - long_a).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_long / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+// Points along a polyline, with the given number of extra samples evenly spaced along each segment.
+pub fn densify_polyline(points: &[Coordinate], samples_per_segment: usize) -> Vec<Coordinate> {
+    let mut densified = Vec::with_capacity(points.len() * (samples_per_segment + 1));
+    for segment in points.windows(2) {
+        let ((x1, y1), (x2, y2)) = (segment[0], segment[1]);
+        for step in 0..=samples_per_segment {
+            let t = step as f64 / (samples_per_segment + 1) as f64;
+            densified.push((x1 + (x2 - x1) * t, y1 + (y2 - y1) * t));
+        }
+    }
+    densified.extend(points.last());
+    densified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::dted::tests::mock_dted;
+    use crate::parsing::dted::{parse_dted, DTEDMap};
+    use std::io::Write;
+    use std::sync::Arc;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_sampler_prefers_finer_tiles() {
+        // Both tiles cover (1, 50), the DTED2 tile has a void where the DTED1 tile has data.
+        let mut tiles = Vec::new();
+        let mut index = RTree::new();
+        for (level, elevations) in [("DTED1", [10, 20, 30]), ("DTED2", [0xFFFF, 200, 300])] {
+            let mut file = NamedTempFile::new().unwrap();
+            file.write_all(&mock_dted(&[elevations.to_vec(), elevations.to_vec()]))
+                .unwrap();
+            let mut metadata =
+                parse_dted(&mut BufReader::new(file.reopen().unwrap()), false).unwrap();
+            metadata.tags.retain(|(k, _)| k != "Level");
+            metadata.tags.push(("Level".to_string(), level.to_string()));
+            index.insert(Node {
+                metadata: metadata.into(),
+                map: Arc::new(MapType::DTED(DTEDMap {
+                    path: file.path().to_path_buf(),
+                })),
+            });
+            tiles.push(file);
+        }
+
+        let mut sampler = ElevationSampler::default();
+        let mut elevation = |point| sampler.elevation(point, &candidate_tiles(&index, point));
+        assert_eq!(elevation((1.0, 50.0)), Some(10.0));
+        assert_eq!(elevation((1.0, 50.05)), Some(200.0));
+        assert_eq!(elevation((1.5, 50.5)), None);
+        assert!(candidate_tiles(&index, (10.0, 10.0)).is_empty());
+    }
+
+    #[test]
+    fn test_haversine() {
+        // One degree of latitude is roughly 111km.
+        let distance = haversine((0.0, 50.0), (0.0, 51.0));
+        assert!((111_000.0..111_400.0).contains(&distance), "{distance}");
+        assert_eq!(haversine((1.0, 1.0), (1.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn test_densify_polyline() {
+        let points = densify_polyline(&[(0.0, 0.0), (2.0, 0.0), (2.0, 4.0)], 1);
+        assert_eq!(
+            points,
+            vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 2.0), (2.0, 4.0)]
+        );
+        assert_eq!(densify_polyline(&[(1.0, 1.0)], 3), vec![(1.0, 1.0)]);
+    }
+}
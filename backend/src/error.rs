@@ -4,6 +4,8 @@ use std::fmt::{Display, Formatter};
 #[derive(Debug, Clone)]
 pub enum RootErrorKind {
     InvalidMapDirectory(String),
+    // A config.txt option line, which isn't `key=true` or `key=false`.
+    InvalidOption(String),
     UnexpectedPathType,
 }
 
@@ -11,6 +13,9 @@ impl Display for RootErrorKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             RootErrorKind::InvalidMapDirectory(s) => write!(f, "Configured Map Dir invalid! {}", s),
+            RootErrorKind::InvalidOption(line) => {
+                write!(f, "Invalid option {line:?}, expected key=true or key=false")
+            }
             RootErrorKind::UnexpectedPathType => {
                 write!(f, "Unexpected Map Type! Could be symlink?")
             }
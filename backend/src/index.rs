@@ -16,6 +16,36 @@ pub struct Node {
 pub struct MetaData {
     pub region: Region,
     pub tags: Vec<(String, String)>,
+    // Simplified outline of the data, within region, where it is known more precisely than the box.
+    pub footprint: Option<Vec<Coordinate>>,
+    // Envelopes of the individual features within the file, where they are kept.
+    pub features: Option<Vec<FeatureEnvelope>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureEnvelope {
+    // Position of the feature within the file.
+    pub index: usize,
+    pub id: Option<String>,
+    pub region: Region,
+}
+
+impl MetaData {
+    // Exact intersection test, the index only tests envelopes.
+    pub fn intersects(&self, region: &Region) -> bool {
+        let footprint = match &self.footprint {
+            Some(footprint) if !footprint.is_empty() => region.intersects_polygon(footprint),
+            _ => true,
+        };
+        // Files whose features are kept only match where one of them does.
+        let features = match &self.features {
+            Some(features) if !features.is_empty() => {
+                features.iter().any(|f| f.region.intersects(region))
+            }
+            _ => true,
+        };
+        footprint && features
+    }
 }
 
 // Implement RTreeObject on Node.
@@ -1,4 +1,5 @@
 use crate::index::Node;
+use crate::spatial::Coordinate;
 use crate::worker::QueryState;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -41,3 +42,48 @@ pub struct QueryRegion {
 pub struct ResultQuery {
     pub uuid: Uuid,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CRSUsage {
+    pub crs: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CRSUsageResponse {
+    pub crs: Vec<CRSUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ElevationQuery {
+    pub lon: f64,
+    pub lat: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ElevationResponse {
+    pub lon: f64,
+    pub lat: f64,
+    pub elevation: Option<f64>,
+}
+
+// Points to sample, or the vertices of a polyline when samples_per_segment is given.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ElevationProfileQuery {
+    pub points: Vec<Coordinate>,
+    pub samples_per_segment: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProfileSample {
+    pub lon: f64,
+    pub lat: f64,
+    // Metres along the profile from the first point.
+    pub distance: f64,
+    pub elevation: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ElevationProfileResponse {
+    pub profile: Vec<ProfileSample>,
+}
@@ -1,13 +1,19 @@
-use crate::config::read_path;
+use crate::config::read_config;
 use crate::error::RootErrorKind;
 use crate::index::Node;
+use crate::parsing::asc::ASCMap;
+use crate::parsing::crs::{self, CRSRegistry};
 use crate::parsing::dted::DTEDMap;
 use crate::parsing::geojson::GEOJSONMap;
 use crate::parsing::gpkg::GPKGMap;
+use crate::parsing::gpx::GPXMap;
+use crate::parsing::hgt::HGTMap;
 use crate::parsing::kml::KMLMap;
+use crate::parsing::kmz::KMZMap;
 use crate::parsing::mbtiles::MBTilesMap;
+use crate::parsing::nitf::NITFMap;
 use crate::parsing::shapefile::ShapeFileMap;
-use crate::routes::{index, results, search};
+use crate::routes::{crs, elevation, elevation_profile, index, results, search};
 use crate::worker::{worker, QueryTask};
 use axum;
 use geotiff::GeoTiffMap;
@@ -21,7 +27,7 @@ use std::ffi::OsStr;
 use std::fs::{DirEntry, File};
 use std::future::IntoFuture;
 use std::io::{stdin, stdout, BufReader, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
 use tokio;
@@ -32,6 +38,7 @@ use tracing_subscriber;
 use uuid::Uuid;
 
 mod config;
+mod elevation;
 mod error;
 mod index;
 mod io;
@@ -54,10 +61,15 @@ struct State {
 pub enum MapType {
     GEOTIFF(GeoTiffMap),
     DTED(DTEDMap),
+    HGT(HGTMap),
+    ASC(ASCMap),
     KML(KMLMap),
+    KMZ(KMZMap),
     GEOJSON(GEOJSONMap),
     MBTILES(MBTilesMap),
     GPKG(GPKGMap),
+    GPX(GPXMap),
+    NITF(NITFMap),
     SHAPEFILE(ShapeFileMap),
 }
 
@@ -66,6 +78,17 @@ pub struct FileMeta {
     pub path: PathBuf,
 }
 
+// Sidecar file sharing the stem of path, such as the .dbf of a .shp. Extensions are often upper case.
+fn companion(files: &[DirEntry], path: &Path, extension: &str) -> Option<PathBuf> {
+    files.iter().map(DirEntry::path).find(|candidate| {
+        candidate
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|s| s.eq_ignore_ascii_case(extension))
+            && candidate.file_stem() == path.file_stem()
+    })
+}
+
 // File traversal logic.
 fn traverse(p: PathBuf) -> Result<Vec<MapType>, Box<dyn Error>> {
     let mut build = Vec::new();
@@ -84,75 +107,32 @@ fn traverse(p: PathBuf) -> Result<Vec<MapType>, Box<dyn Error>> {
             let ext = path.extension().and_then(OsStr::to_str);
             if let Some(ext) = ext {
                 match ext {
-                    "tif" => {
-                        build.push(MapType::GEOTIFF(GeoTiffMap {
-                            tiff: path.clone(),
-                            tfw: files
-                                .iter()
-                                .find(|candidate| {
-                                    candidate
-                                        .path()
-                                        .extension()
-                                        .and_then(OsStr::to_str)
-                                        .is_some_and(|s| s == "tfw")
-                                        && candidate
-                                            .path()
-                                            .file_stem()
-                                            .is_some_and(|s| s == path.file_stem().unwrap())
-                                })
-                                .map(DirEntry::path),
-                            prj: files
-                                .iter()
-                                .find(|candidate| {
-                                    candidate
-                                        .path()
-                                        .extension()
-                                        .and_then(OsStr::to_str)
-                                        .is_some_and(|s| s == "prj")
-                                        && candidate
-                                            .path()
-                                            .file_stem()
-                                            .is_some_and(|s| s == path.file_stem().unwrap())
-                                })
-                                .map(DirEntry::path),
-                        }));
-                    }
+                    "tif" => build.push(MapType::GEOTIFF(GeoTiffMap {
+                        tfw: companion(&files, &path, "tfw"),
+                        prj: companion(&files, &path, "prj"),
+                        tiff: path,
+                    })),
                     "kml" => build.push(MapType::KML(KMLMap { path })),
-                    "dt1" | "dt2" => build.push(MapType::DTED(DTEDMap { path })),
+                    "kmz" => build.push(MapType::KMZ(KMZMap { path })),
+                    "dt0" | "dt1" | "dt2" => build.push(MapType::DTED(DTEDMap { path })),
+                    "hgt" => build.push(MapType::HGT(HGTMap { path })),
+                    "asc" => build.push(MapType::ASC(ASCMap {
+                        prj: companion(&files, &path, "prj"),
+                        path,
+                    })),
                     "geojson" => build.push(MapType::GEOJSON(GEOJSONMap { path })),
                     "mbtiles" => build.push(MapType::MBTILES(MBTilesMap { path })),
                     "gpkg" => build.push(MapType::GPKG(GPKGMap { path })),
+                    "gpx" => build.push(MapType::GPX(GPXMap { path })),
+                    "ntf" | "nitf" => build.push(MapType::NITF(NITFMap { path })),
                     "shp" => {
                         build.push(MapType::SHAPEFILE(ShapeFileMap {
                             shp: path.clone(),
-                            tfw: files
-                                .iter()
-                                .find(|candidate| {
-                                    candidate
-                                        .path()
-                                        .extension()
-                                        .and_then(OsStr::to_str)
-                                        .is_some_and(|s| s == "tfw")
-                                        && candidate
-                                            .path()
-                                            .file_stem()
-                                            .is_some_and(|s| s == path.file_stem().unwrap())
-                                })
-                                .map(DirEntry::path),
-                            prj: files
-                                .iter()
-                                .find(|candidate| {
-                                    candidate
-                                        .path()
-                                        .extension()
-                                        .and_then(OsStr::to_str)
-                                        .is_some_and(|s| s == "prj")
-                                        && candidate
-                                            .path()
-                                            .file_stem()
-                                            .is_some_and(|s| s == path.file_stem().unwrap())
-                                })
-                                .map(DirEntry::path),
+                            tfw: companion(&files, &path, "tfw"),
+                            prj: companion(&files, &path, "prj"),
+                            shx: companion(&files, &path, "shx"),
+                            dbf: companion(&files, &path, "dbf"),
+                            cpg: companion(&files, &path, "cpg"),
                         }));
                     }
                     _ => {}
@@ -175,10 +155,10 @@ async fn main() {
     let mut stdout = stdout();
     tracing_subscriber::fmt::init();
     // Load Config (Expect in WD)
-    let directory = match std::env::current_dir() {
+    let config = match std::env::current_dir() {
         Ok(d) => match File::open(d.join("config.txt")) {
-            Ok(f) => match read_path(f) {
-                Ok(directory) => directory,
+            Ok(f) => match read_config(f) {
+                Ok(config) => config,
                 Err(e) => {
                     event!(Level::ERROR, "Failed to parse config.txt, reason: {e:?}");
                     // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
@@ -220,6 +200,31 @@ async fn main() {
         "config.txt Loaded from current working directory!"
     );
 
+    // Optional user CRS definitions, extending the built in ones.
+    if let Ok(f) =
+        std::env::current_dir().and_then(|d| File::open(d.join(crs::USER_DEFINITIONS_FILE)))
+    {
+        match CRSRegistry::from_reader(BufReader::new(f)) {
+            Ok(registry) => {
+                event!(
+                    Level::INFO,
+                    "Loaded {} user CRS definitions from {}",
+                    registry.user_definitions(),
+                    crs::USER_DEFINITIONS_FILE
+                );
+                let _ = crs::install(registry);
+            }
+            Err(e) => {
+                event!(
+                    Level::ERROR,
+                    "Failed to parse {}, using built in CRS definitions only. Reason: {e}",
+                    crs::USER_DEFINITIONS_FILE
+                );
+            }
+        }
+    }
+
+    let directory = config.directory;
     if !directory.exists() {
         event!(Level::ERROR, "Map Directory: {:?}", directory);
         event!(Level::ERROR, "Does not exist! Please edit in config.txt!");
@@ -255,16 +260,13 @@ async fn main() {
     event!(Level::DEBUG, "Empty Index Initialised!");
 
     for (_, map) in files.iter().enumerate() {
-        match parse(map.clone()) {
-            Ok(v) => match v {
-                None => {
-                    // Ignore if none!
-                }
-                Some(node) => {
+        match parse(map.clone(), config.options) {
+            Ok(nodes) => {
+                for node in nodes {
                     event!(Level::DEBUG, "Found & Inserted: {:?}", node);
                     idx.insert(node);
                 }
-            },
+            }
             Err(e) => {
                 event!(Level::ERROR, "{:?}", e);
             }
@@ -306,6 +308,9 @@ async fn main() {
         .route("/", axum::routing::get(index))
         .route("/search", axum::routing::get(search))
         .route("/results", axum::routing::get(results))
+        .route("/crs", axum::routing::get(crs))
+        .route("/elevation", axum::routing::get(elevation))
+        .route("/elevation/profile", axum::routing::post(elevation_profile))
         .layer(cors)
         .layer(axum::Extension(state)); // Pass state through to methods (le middleware)
 
@@ -0,0 +1,387 @@
+use crate::parsing::crs;
+use crate::parsing::reproject::{densify, reproject_extent, to_wgs84, FOOTPRINT_SAMPLES};
+use crate::spatial::{Coordinate, Region};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use tracing::{event, Level};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ASCMap {
+    pub(crate) path: PathBuf,
+    pub(crate) prj: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum ASCErrorState {
+    UnexpectedFormat(String),
+    MissingHeader(&'static str),
+    ProjectionError(String),
+}
+
+impl Display for ASCErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ASCErrorState::UnexpectedFormat(s) => write!(f, "UnexpectedFormatError: {s}"),
+            ASCErrorState::MissingHeader(key) => write!(f, "Missing the {key} header!"),
+            ASCErrorState::ProjectionError(s) => write!(f, "ProjectionError: {s}"),
+        }
+    }
+}
+
+impl Error for ASCErrorState {}
+
+#[derive(Debug)]
+pub struct ASCMetaData {
+    pub region: Region,
+    pub tags: Vec<(String, String)>,
+    pub footprint: Option<Vec<Coordinate>>,
+}
+
+// The keyword value lines before the cell values.
+#[derive(Debug, Default)]
+struct ASCHeader {
+    columns: Option<usize>,
+    rows: Option<usize>,
+    // Lower left of the grid, and whether it's given as the corner or the centre of that cell.
+    x: Option<f64>,
+    y: Option<f64>,
+    centre: bool,
+    // Cell width and height, GDAL writes dx and dy for non square cells.
+    cell_size: Option<(f64, f64)>,
+    no_data: Option<f64>,
+}
+
+impl ASCHeader {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), ASCErrorState> {
+        let invalid = || ASCErrorState::UnexpectedFormat(format!("Invalid {key} value: {value:?}"));
+        let number = || value.parse::<f64>().map_err(|_| invalid());
+        // Positions and sizes, where NaN or infinity would pass into the region.
+        let finite = || number().ok().filter(|v| v.is_finite()).ok_or_else(invalid);
+        let count = || value.parse::<usize>().map_err(|_| invalid());
+        match key.to_ascii_lowercase().as_str() {
+            "ncols" => self.columns = Some(count()?),
+            "nrows" => self.rows = Some(count()?),
+            "xllcorner" => self.x = Some(finite()?),
+            "yllcorner" => self.y = Some(finite()?),
+            "xllcenter" => {
+                self.x = Some(finite()?);
+                self.centre = true;
+            }
+            "yllcenter" => {
+                self.y = Some(finite()?);
+                self.centre = true;
+            }
+            "cellsize" => self.cell_size = Some((finite()?, finite()?)),
+            "dx" => self.cell_size = Some((finite()?, self.cell_size.map_or(0.0, |c| c.1))),
+            "dy" => self.cell_size = Some((self.cell_size.map_or(0.0, |c| c.0), finite()?)),
+            "nodata_value" => self.no_data = Some(number()?),
+            _ => event!(Level::WARN, "Ignoring unknown ASC header {key}."),
+        }
+        Ok(())
+    }
+}
+
+// Reads every cell, checking their count and tagging the elevation statistics.
+// The line which ended the header already holds the first values.
+fn scan_cells(
+    reader: &mut BufReader<File>,
+    mut line: String,
+    no_data: Option<f64>,
+    expected: usize,
+    tags: &mut Vec<(String, String)>,
+) -> Result<(), ASCErrorState> {
+    let io_error = |e: std::io::Error| ASCErrorState::UnexpectedFormat(format!("{e:?}"));
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    let (mut sum, mut count, mut cells) = (0f64, 0usize, 0usize);
+    loop {
+        for value in line.split_whitespace() {
+            let value: f64 = value.parse().map_err(|_| {
+                ASCErrorState::UnexpectedFormat(format!("Invalid cell value {value:?}"))
+            })?;
+            cells += 1;
+            if Some(value) == no_data {
+                continue;
+            }
+            min = min.min(value);
+            max = max.max(value);
+            sum += value;
+            count += 1;
+        }
+        line.clear();
+        if reader.read_line(&mut line).map_err(io_error)? == 0 {
+            break;
+        }
+    }
+    if cells != expected {
+        return Err(ASCErrorState::UnexpectedFormat(format!(
+            "Expected {expected} cells, found {cells}"
+        )));
+    }
+    if count > 0 {
+        tags.push(("MinElevation".to_string(), min.to_string()));
+        tags.push(("MaxElevation".to_string(), max.to_string()));
+        tags.push((
+            "MeanElevation".to_string(),
+            format!("{:.1}", sum / count as f64),
+        ));
+    }
+    Ok(())
+}
+
+// Reads the header, and with scan every cell for the elevation statistics. Without a .prj the grid
+// is taken to be in EPSG:4326.
+pub fn parse_asc(
+    reader: &mut BufReader<File>,
+    prj_reader: Option<&mut BufReader<File>>,
+    scan: bool,
+) -> Result<ASCMetaData, ASCErrorState> {
+    let io_error = |e: std::io::Error| ASCErrorState::UnexpectedFormat(format!("{e:?}"));
+    let mut header = ASCHeader::default();
+    let mut line = String::new();
+    // Header lines start with a keyword, the first starting with a number is the first row.
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(io_error)? == 0 {
+            break;
+        }
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            None => continue,
+            Some(key) if key.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                let value = tokens.next().ok_or_else(|| {
+                    ASCErrorState::UnexpectedFormat(format!("Header {key} has no value"))
+                })?;
+                header.set(key, value)?;
+            }
+            Some(_) => break,
+        }
+    }
+
+    let columns = header
+        .columns
+        .ok_or(ASCErrorState::MissingHeader("ncols"))?;
+    let rows = header.rows.ok_or(ASCErrorState::MissingHeader("nrows"))?;
+    let expected = columns.checked_mul(rows).ok_or_else(|| {
+        ASCErrorState::UnexpectedFormat(format!("{columns} by {rows} cells is too many"))
+    })?;
+    let x = header.x.ok_or(ASCErrorState::MissingHeader("xllcorner"))?;
+    let y = header.y.ok_or(ASCErrorState::MissingHeader("yllcorner"))?;
+    let (width, height) = header
+        .cell_size
+        .ok_or(ASCErrorState::MissingHeader("cellsize"))?;
+    if width <= 0.0 || height <= 0.0 {
+        return Err(ASCErrorState::UnexpectedFormat(format!(
+            "Cell size must be positive, got {width} by {height}"
+        )));
+    }
+
+    let mut tags = vec![
+        ("Filetype".to_string(), "ASC".to_string()),
+        ("Columns".to_string(), columns.to_string()),
+        ("Rows".to_string(), rows.to_string()),
+        ("CellSize".to_string(), width.to_string()),
+    ];
+    if height != width {
+        tags.push(("CellHeight".to_string(), height.to_string()));
+    }
+    if let Some(no_data) = header.no_data {
+        tags.push(("NoData".to_string(), no_data.to_string()));
+    }
+
+    if scan {
+        scan_cells(reader, line, header.no_data, expected, &mut tags)?;
+    }
+
+    let (west, south) = match header.centre {
+        true => (x - width / 2.0, y - height / 2.0),
+        false => (x, y),
+    };
+    let (east, north) = (west + columns as f64 * width, south + rows as f64 * height);
+    let corners = [(west, north), (east, north), (east, south), (west, south)];
+
+    let Some(prj_reader) = prj_reader else {
+        if west < -180.0 || east > 180.0 || south < -90.0 || north > 90.0 {
+            event!(
+                Level::WARN,
+                "ASC grid ({west}, {south}) to ({east}, {north}) is beyond longitude and latitude, but has no .prj."
+            );
+            tags.push(("CoordinatesOutOfRange".to_string(), "true".to_string()));
+        }
+        return Ok(ASCMetaData {
+            region: Region::envelope(&corners),
+            tags,
+            footprint: Some(corners.to_vec()),
+        });
+    };
+
+    let mut wkt = String::new();
+    prj_reader.read_to_string(&mut wkt).map_err(io_error)?;
+    let projection_error = |e: &dyn Display| ASCErrorState::ProjectionError(e.to_string());
+    let proj = crs::registry()
+        .projection_from_wkt(&wkt)
+        .map_err(|e| projection_error(&e))?;
+    tags.push(("CRS".to_string(), crs::wkt_label(&wkt)));
+    let extent = reproject_extent(&corners, &proj).map_err(|e| projection_error(&e))?;
+    if extent.crosses_antimeridian {
+        tags.push(("CrossesAntimeridian".to_string(), "true".to_string()));
+    }
+    let footprint = match extent.wraps() {
+        true => None,
+        false => Some(
+            to_wgs84(&densify(&corners, FOOTPRINT_SAMPLES), &proj)
+                .map_err(|e| projection_error(&e))?,
+        ),
+    };
+    Ok(ASCMetaData {
+        region: Region {
+            top_left: extent.top_left(),
+            bottom_right: extent.bottom_right(),
+        },
+        tags,
+        footprint,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::tests::tag;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn reader(content: &str) -> BufReader<File> {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        BufReader::new(file.reopen().unwrap())
+    }
+
+    const GRID: &str = "ncols 4
+nrows 3
+xllcorner -2.0
+yllcorner 51.0
+cellsize 0.25
+NODATA_value -9999
+10 20 30 40
+-9999 50 60 70
+80 90 100 -9999
+";
+
+    #[test]
+    fn test_parse_asc() {
+        let metadata = parse_asc(&mut reader(GRID), None, true).unwrap();
+        assert_eq!(metadata.region.top_left, (-2.0, 51.75));
+        assert_eq!(metadata.region.bottom_right, (-1.0, 51.0));
+        assert_eq!(tag(&metadata.tags, "Filetype"), Some("ASC"));
+        assert_eq!(tag(&metadata.tags, "Columns"), Some("4"));
+        assert_eq!(tag(&metadata.tags, "Rows"), Some("3"));
+        assert_eq!(tag(&metadata.tags, "CellSize"), Some("0.25"));
+        assert_eq!(tag(&metadata.tags, "NoData"), Some("-9999"));
+        assert_eq!(tag(&metadata.tags, "MinElevation"), Some("10"));
+        assert_eq!(tag(&metadata.tags, "MaxElevation"), Some("100"));
+        assert_eq!(tag(&metadata.tags, "MeanElevation"), Some("55.0"));
+        assert!(tag(&metadata.tags, "CoordinatesOutOfRange").is_none());
+    }
+
+    #[test]
+    fn test_parse_asc_without_scan() {
+        // The header is enough for the extent, so cells aren't read or counted.
+        let grid = GRID.replace("80 90 100 -9999\n", "");
+        let metadata = parse_asc(&mut reader(&grid), None, false).unwrap();
+        assert_eq!(metadata.region.top_left, (-2.0, 51.75));
+        assert_eq!(tag(&metadata.tags, "Rows"), Some("3"));
+        assert!(tag(&metadata.tags, "MinElevation").is_none());
+    }
+
+    #[test]
+    fn test_parse_asc_cell_centres() {
+        // Values may wrap across lines, and headers be in any case.
+        let grid = "NCOLS 2\nNROWS 2\nXLLCENTER 0.5\nYLLCENTER 0.5\nDX 1\nDY 2\n1 2\n3\n4\n";
+        let metadata = parse_asc(&mut reader(grid), None, true).unwrap();
+        assert_eq!(metadata.region.top_left, (0.0, 3.5));
+        assert_eq!(metadata.region.bottom_right, (2.0, -0.5));
+        assert_eq!(tag(&metadata.tags, "CellHeight"), Some("2"));
+        assert_eq!(tag(&metadata.tags, "MeanElevation"), Some("2.5"));
+    }
+
+    #[test]
+    fn test_parse_asc_with_prj() {
+        let grid =
+            "ncols 2\nnrows 2\nxllcorner 529000\nyllcorner 179000\ncellsize 1000\n1 2\n3 4\n";
+        let mut prj = reader(crs_definitions::from_code(27700).unwrap().wkt);
+        let metadata = parse_asc(&mut reader(grid), Some(&mut prj), true).unwrap();
+        assert_eq!(tag(&metadata.tags, "CRS"), Some("EPSG:27700"));
+        let (west, north) = metadata.region.top_left;
+        let (east, south) = metadata.region.bottom_right;
+        assert!((-0.2..-0.1).contains(&west) && (-0.15..0.0).contains(&east));
+        assert!((51.48..51.5).contains(&south) && (51.5..51.52).contains(&north));
+
+        let metadata = parse_asc(&mut reader(grid), None, true).unwrap();
+        assert_eq!(tag(&metadata.tags, "CoordinatesOutOfRange"), Some("true"));
+    }
+
+    #[test]
+    fn test_parse_asc_invalid() {
+        assert!(matches!(
+            parse_asc(
+                &mut reader("nrows 1\nxllcorner 0\nyllcorner 0\ncellsize 1\n1\n"),
+                None,
+                true
+            ),
+            Err(ASCErrorState::MissingHeader("ncols"))
+        ));
+        assert!(matches!(
+            parse_asc(
+                &mut reader(&GRID.replace("80 90 100 -9999\n", "")),
+                None,
+                true
+            ),
+            Err(ASCErrorState::UnexpectedFormat(_))
+        ));
+        assert!(matches!(
+            parse_asc(&mut reader(&GRID.replace("50", "fifty")), None, true),
+            Err(ASCErrorState::UnexpectedFormat(_))
+        ));
+        assert!(matches!(
+            parse_asc(
+                &mut reader(&GRID.replace("cellsize 0.25", "cellsize -1")),
+                None,
+                true
+            ),
+            Err(ASCErrorState::UnexpectedFormat(_))
+        ));
+        // NaN and infinity parse as numbers, but can't place the grid.
+        for header in ["xllcorner NaN", "yllcorner inf", "cellsize NaN"] {
+            let key = header.split(' ').next().unwrap();
+            let grid = GRID
+                .lines()
+                .map(|line| match line.starts_with(key) {
+                    true => header,
+                    false => line,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(matches!(
+                parse_asc(&mut reader(&grid), None, true),
+                Err(ASCErrorState::UnexpectedFormat(_))
+            ));
+        }
+        // Dimensions whose cell count overflows.
+        let grid = format!(
+            "ncols {}\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 1\n1\n",
+            usize::MAX
+        );
+        assert!(matches!(
+            parse_asc(&mut reader(&grid), None, true),
+            Err(ASCErrorState::UnexpectedFormat(_))
+        ));
+        let mut prj = reader("NOT WKT");
+        assert!(matches!(
+            parse_asc(&mut reader(GRID), Some(&mut prj), true),
+            Err(ASCErrorState::ProjectionError(_))
+        ));
+    }
+}
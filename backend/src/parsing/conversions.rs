@@ -1,10 +1,18 @@
-use crate::index::MetaData;
+use crate::index::{FeatureEnvelope, MetaData};
+use crate::parsing::asc::ASCMetaData;
 use crate::parsing::dted::DT2MetaData;
 use crate::parsing::geojson::GeoJSONMetaData;
 use crate::parsing::gpkg::GPKGMetaData;
+use crate::parsing::gpx::GPXMetaData;
+use crate::parsing::hgt::HGTMetaData;
 use crate::parsing::kml::KMLMetadata;
 use crate::parsing::mbtiles::MBTilesMetaData;
+use crate::parsing::nitf::NITFMetaData;
+use crate::parsing::reproject::{
+    densify, reproject_extent, to_wgs84, ReprojectErrorState, FOOTPRINT_SAMPLES,
+};
 use crate::parsing::shapefile::ShapeFileMetaData;
+use crate::spatial::{convex_hull, Region};
 use geotiff::GeoTiffMetaData;
 
 impl From<KMLMetadata> for MetaData {
@@ -12,24 +20,77 @@ impl From<KMLMetadata> for MetaData {
         MetaData {
             region: value.region.into(),
             tags: value.tags,
+            footprint: value.footprint.map(|f| convex_hull(&f)),
+            features: None,
         }
     }
 }
 
-impl From<GeoTiffMetaData> for MetaData {
-    fn from(value: GeoTiffMetaData) -> Self {
-        MetaData {
-            region: value.region.into(),
-            tags: value.tags,
+// GeoTIFF corners are in the source CRS, so are reprojected here rather than by the geotiff crate.
+impl TryFrom<GeoTiffMetaData> for MetaData {
+    type Error = ReprojectErrorState;
+
+    fn try_from(value: GeoTiffMetaData) -> Result<Self, Self::Error> {
+        let mut tags = value.tags;
+        let extent = reproject_extent(&value.corners, &value.projection)?;
+        if extent.crosses_antimeridian {
+            tags.push(("CrossesAntimeridian".to_string(), "true".to_string()));
         }
+        // Outline of rotated or reprojected images, which only partly fill their region.
+        let footprint = match extent.wraps() {
+            true => None,
+            false => Some(to_wgs84(
+                &densify(&value.corners, FOOTPRINT_SAMPLES),
+                &value.projection,
+            )?),
+        };
+        Ok(MetaData {
+            region: Region {
+                top_left: extent.top_left(),
+                bottom_right: extent.bottom_right(),
+            },
+            tags,
+            footprint: footprint.map(|f| convex_hull(&f)),
+            features: None,
+        })
     }
 }
 
 impl From<DT2MetaData> for MetaData {
     fn from(value: DT2MetaData) -> Self {
+        let corners = [
+            value.region.top_left,
+            value.region.top_right,
+            value.region.bottom_right,
+            value.region.bottom_left,
+        ];
         MetaData {
             region: value.region.into(),
             tags: value.tags,
+            footprint: Some(convex_hull(&corners)),
+            features: None,
+        }
+    }
+}
+
+impl From<HGTMetaData> for MetaData {
+    fn from(value: HGTMetaData) -> Self {
+        MetaData {
+            region: value.region,
+            tags: value.tags,
+            footprint: value.footprint.map(|f| convex_hull(&f)),
+            features: None,
+        }
+    }
+}
+
+impl From<ASCMetaData> for MetaData {
+    fn from(value: ASCMetaData) -> Self {
+        MetaData {
+            region: value.region,
+            tags: value.tags,
+            footprint: value.footprint.map(|f| convex_hull(&f)),
+            features: None,
         }
     }
 }
@@ -39,6 +100,17 @@ impl From<GeoJSONMetaData> for MetaData {
         let x = MetaData {
             region: value.region.into(),
             tags: value.tags,
+            footprint: value.footprint.map(|f| convex_hull(&f)),
+            features: value.features.map(|features| {
+                features
+                    .into_iter()
+                    .map(|f| FeatureEnvelope {
+                        index: f.index,
+                        id: f.id,
+                        region: f.region.into(),
+                    })
+                    .collect()
+            }),
         };
         return x;
     }
@@ -49,6 +121,8 @@ impl From<MBTilesMetaData> for MetaData {
         MetaData {
             region: value.region.into(),
             tags: value.tags,
+            footprint: None,
+            features: None,
         }
     }
 }
@@ -58,6 +132,30 @@ impl From<GPKGMetaData> for MetaData {
         MetaData {
             region: value.region.into(),
             tags: value.tags,
+            footprint: None,
+            features: None,
+        }
+    }
+}
+
+impl From<GPXMetaData> for MetaData {
+    fn from(value: GPXMetaData) -> Self {
+        MetaData {
+            region: value.region,
+            tags: value.tags,
+            footprint: value.footprint.map(|f| convex_hull(&f)),
+            features: None,
+        }
+    }
+}
+
+impl From<NITFMetaData> for MetaData {
+    fn from(value: NITFMetaData) -> Self {
+        MetaData {
+            region: value.region,
+            tags: value.tags,
+            footprint: value.footprint.map(|f| convex_hull(&f)),
+            features: None,
         }
     }
 }
@@ -67,6 +165,8 @@ impl From<ShapeFileMetaData> for MetaData {
         MetaData {
             region: value.region,
             tags: value.tags,
+            footprint: value.footprint.map(|f| convex_hull(&f)),
+            features: None,
         }
     }
 }
@@ -81,7 +181,7 @@ mod tests {
     use crate::parsing::mbtiles::MBTilesRegion;
     use crate::spatial::Coordinate;
     use crate::spatial::Region;
-    use geotiff::GeoTiffRegion;
+    use proj4rs::Proj;
     use std::collections::HashMap;
 
     #[test]
@@ -93,6 +193,7 @@ mod tests {
         let kml_metadata = KMLMetadata {
             region: kml_region,
             tags: vec![("creator".to_string(), "test".to_string())],
+            footprint: None,
         };
         let meta_data: MetaData = From::from(kml_metadata);
         // Ensure that your assertions are compatible with how you interpret the region for MetaData
@@ -119,24 +220,55 @@ mod tests {
     // Test for another metadata conversion as an example
     #[test]
     fn geotiff_to_metadata_conversion() {
-        let geotiff_region = GeoTiffRegion {
-            top_left: (30.0, -120.0),
-            bottom_right: (35.0, -115.0),
-        };
         let geotiff_metadata = GeoTiffMetaData {
-            region: geotiff_region,
+            corners: [
+                (-120.0, 35.0),
+                (-115.0, 35.0),
+                (-115.0, 30.0),
+                (-120.0, 30.0),
+            ],
+            projection: Proj::from_proj_string(crs_definitions::EPSG_4326.proj4).unwrap(),
             tags: vec![("resolution".to_string(), "high".to_string())],
         };
-        let meta_data: MetaData = geotiff_metadata.into();
-        assert_eq!(meta_data.region.top_left, (30.0, -120.0));
-        assert_eq!(meta_data.region.bottom_right, (35.0, -115.0));
+        let meta_data: MetaData = geotiff_metadata.try_into().unwrap();
+        assert!((meta_data.region.top_left.0 - -120.0).abs() < 1e-9);
+        assert!((meta_data.region.top_left.1 - 35.0).abs() < 1e-9);
+        assert!((meta_data.region.bottom_right.0 - -115.0).abs() < 1e-9);
+        assert!((meta_data.region.bottom_right.1 - 30.0).abs() < 1e-9);
+        assert_eq!(meta_data.footprint.unwrap().len(), 4);
         assert_eq!(
             meta_data.tags,
             vec![("resolution".to_string(), "high".to_string())]
         );
     }
 
-    // Test the conversion from DT2MetaData to MetaData
+    #[test]
+    fn projected_geotiff_to_metadata_conversion() {
+        // 10km of British National Grid, slightly rotated.
+        let geotiff_metadata = GeoTiffMetaData {
+            corners: [
+                (449997.5, 209997.5),
+                (459997.5, 210497.5),
+                (460497.5, 200497.5),
+                (450497.5, 199997.5),
+            ],
+            projection: Proj::from_proj_string(crs_definitions::EPSG_27700.proj4).unwrap(),
+            tags: vec![],
+        };
+        let meta_data: MetaData = geotiff_metadata.try_into().unwrap();
+        let (west, north) = meta_data.region.top_left;
+        let (east, south) = meta_data.region.bottom_right;
+        assert!((-1.3..-1.2).contains(&west), "west: {west}");
+        assert!((-1.2..-1.1).contains(&east), "east: {east}");
+        assert!((51.6..51.8).contains(&south), "south: {south}");
+        assert!((51.7..51.9).contains(&north), "north: {north}");
+        // The footprint follows the rotation, so is within the region.
+        let footprint = meta_data.footprint.unwrap();
+        assert!(footprint
+            .iter()
+            .all(|(x, y)| (west..=east).contains(x) && (south..=north).contains(y)));
+    }
+
     #[test]
     fn dt2_to_metadata_conversion() {
         let dt2_region = DT2Region {
@@ -150,9 +282,10 @@ mod tests {
             tags: vec![("source".to_string(), "satellite".to_string())],
         };
         let meta_data: MetaData = dt2_metadata.into();
-        // Assuming the conversion uses top_left and bottom_right for the MetaData region
-        assert_eq!(meta_data.region.top_left, (50.0, -100.0));
-        assert_eq!(meta_data.region.bottom_right, (45.0, -95.0));
+        // The region bounds all four corners, which also form the footprint.
+        assert_eq!(meta_data.region.top_left, (45.0, -95.0));
+        assert_eq!(meta_data.region.bottom_right, (50.0, -100.0));
+        assert_eq!(meta_data.footprint.unwrap().len(), 4);
         assert_eq!(
             meta_data.tags,
             vec![("source".to_string(), "satellite".to_string())]
@@ -169,6 +302,8 @@ mod tests {
         let geojson_metadata = GeoJSONMetaData {
             region: geojson_region,
             tags: vec![("type".to_string(), "feature".to_string())],
+            footprint: None,
+            features: None,
         };
         let meta_data: MetaData = geojson_metadata.into();
 
@@ -230,6 +365,7 @@ mod tests {
         let shapefile_metadata = ShapeFileMetaData {
             region: shapefile_region,
             tags: vec![("attribute".to_string(), "value".to_string())],
+            footprint: None,
         };
         let meta_data: MetaData = shapefile_metadata.into();
         assert_eq!(meta_data.region.top_left, (40.0, -110.0));
@@ -248,6 +384,8 @@ mod tests {
         let geojson_metadata = GeoJSONMetaData {
             region: geojson_region,
             tags: Vec::new(), // Empty tag list
+            footprint: None,
+            features: None,
         };
         let meta_data: MetaData = geojson_metadata.into();
         assert!(meta_data.tags.is_empty());
@@ -282,6 +420,7 @@ mod tests {
         let kml_metadata = KMLMetadata {
             region: kml_region,
             tags: Vec::new(), // Empty tag vector
+            footprint: None,
         };
         let meta_data: MetaData = From::from(kml_metadata);
         assert!(meta_data.tags.is_empty()); // Confirm that the converted tags vector is empty
@@ -300,6 +439,8 @@ mod tests {
                 ("type".to_string(), "feature".to_string()),
                 ("source".to_string(), "user".to_string()),
             ],
+            footprint: None,
+            features: None,
         };
         let meta_data: MetaData = From::from(geojson_metadata);
 
@@ -0,0 +1,332 @@
+use geotiff::crs::CRSDefinitions;
+use proj4rs::Proj;
+use proj4wkt::wkt_to_projstring;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::BufRead;
+use std::sync::{Mutex, OnceLock};
+use tracing::{event, Level};
+
+static REGISTRY: OnceLock<CRSRegistry> = OnceLock::new();
+
+// Name of the optional user definitions file, looked for alongside config.txt.
+pub const USER_DEFINITIONS_FILE: &str = "crs.txt";
+
+#[derive(Debug)]
+pub enum CRSErrorState {
+    UnexpectedFormat(String),
+    // Line number, and why its definition was rejected.
+    InvalidDefinition(usize, String),
+    UnknownCRS(u32),
+    ProjectionError(String),
+}
+
+impl Display for CRSErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CRSErrorState::UnexpectedFormat(s) => write!(f, "UnexpectedFormatError: {s}"),
+            CRSErrorState::InvalidDefinition(line, reason) => {
+                write!(f, "Invalid CRS definition on line {line}: {reason}")
+            }
+            CRSErrorState::UnknownCRS(code) => write!(f, "Unsupported CRS: {code}"),
+            CRSErrorState::ProjectionError(s) => write!(f, "ProjectionError: {s}"),
+        }
+    }
+}
+
+impl Error for CRSErrorState {}
+
+// EPSG code to proj string lookup, combining the built in crs_definitions with user supplied ones.
+// User definitions take precedence, so can also correct built in ones.
+#[derive(Debug, Default)]
+pub struct CRSRegistry {
+    definitions: HashMap<u32, String>,
+    // Unknown codes already reported, so each is only logged once.
+    reported: Mutex<HashSet<u32>>,
+}
+
+impl CRSRegistry {
+    pub fn new() -> CRSRegistry {
+        CRSRegistry::default()
+    }
+
+    // Reads user definitions, one per line, as an EPSG code followed by a proj string or WKT:
+    //   EPSG:3413 +proj=stere +lat_0=90 +lat_ts=70 +lon_0=-45 +datum=WGS84 +units=m
+    //   102100 PROJCS["WGS_1984_Web_Mercator_Auxiliary_Sphere",...]
+    // Blank lines and lines starting with # are ignored.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<CRSRegistry, CRSErrorState> {
+        let mut definitions = HashMap::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| {
+                CRSErrorState::UnexpectedFormat(format!("Failed to read CRS definitions: {e:?}"))
+            })?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |reason: String| CRSErrorState::InvalidDefinition(number + 1, reason);
+            let Some((code, definition)) = line.split_once(char::is_whitespace) else {
+                return Err(invalid(
+                    "expected a code followed by a definition".to_string(),
+                ));
+            };
+            let code = parse_code(code).ok_or_else(|| invalid(format!("bad code {code:?}")))?;
+            let definition = definition.trim();
+            let proj_string = if definition.starts_with('+') {
+                definition.to_string()
+            } else {
+                wkt_to_projstring(definition).map_err(|e| invalid(format!("{e:?}")))?
+            };
+            Proj::from_proj_string(&proj_string).map_err(|e| invalid(format!("{e:?}")))?;
+            definitions.insert(code, proj_string);
+        }
+        Ok(CRSRegistry {
+            definitions,
+            reported: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn user_definitions(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn proj_string(&self, code: u32) -> Option<String> {
+        if let Some(definition) = self.definitions.get(&code) {
+            return Some(definition.clone());
+        }
+        u16::try_from(code)
+            .ok()
+            .and_then(crs_definitions::from_code)
+            .map(|def| def.proj4.to_string())
+    }
+
+    // As proj_string, but an error for unknown codes, logging guidance the first time each is seen.
+    pub fn lookup(&self, code: u32) -> Result<String, CRSErrorState> {
+        self.proj_string(code).ok_or_else(|| {
+            if self.reported.lock().unwrap().insert(code) {
+                event!(
+                    Level::WARN,
+                    "Unknown CRS EPSG:{code}. Files using it can't be indexed until it is defined; \
+                    add a line such as `EPSG:{code} <proj string or WKT>` to {USER_DEFINITIONS_FILE}."
+                );
+            }
+            CRSErrorState::UnknownCRS(code)
+        })
+    }
+
+    pub fn projection(&self, code: u32) -> Result<Proj, CRSErrorState> {
+        Proj::from_proj_string(&self.lookup(code)?)
+            .map_err(|e| CRSErrorState::ProjectionError(format!("{e:?}")))
+    }
+
+    // Builds a projection from WKT, preferring the registry's definition for its EPSG code.
+    pub fn projection_from_wkt(&self, wkt: &str) -> Result<Proj, CRSErrorState> {
+        let wkt = wkt.trim();
+        if let Some(proj_string) = wkt_authority(wkt).and_then(|code| self.proj_string(code)) {
+            return Proj::from_proj_string(&proj_string)
+                .map_err(|e| CRSErrorState::ProjectionError(format!("{e:?}")));
+        }
+        let proj_string = wkt_to_projstring(wkt)
+            .map_err(|e| CRSErrorState::ProjectionError(format!("Failed to convert WKT: {e:?}")))?;
+        Proj::from_proj_string(&proj_string)
+            .map_err(|e| CRSErrorState::ProjectionError(format!("{e:?}")))
+    }
+}
+
+// Lets GeoTIFFs resolve their GeoKeys and .prj sidecars through the shared registry.
+impl CRSDefinitions for CRSRegistry {
+    fn proj_string(&self, code: u32) -> Option<String> {
+        CRSRegistry::proj_string(self, code)
+    }
+
+    fn lookup(&self, code: u32) -> Option<String> {
+        CRSRegistry::lookup(self, code).ok()
+    }
+
+    fn projection_from_wkt(&self, wkt: &str) -> Result<(Proj, String), String> {
+        CRSRegistry::projection_from_wkt(self, wkt)
+            .map(|projection| (projection, wkt_label(wkt)))
+            .map_err(|e| e.to_string())
+    }
+}
+
+// The shared registry, holding only the built in definitions unless one was installed at startup.
+pub fn registry() -> &'static CRSRegistry {
+    REGISTRY.get_or_init(CRSRegistry::new)
+}
+
+// Installs the registry used by all parsers. Fails if one is already in use.
+pub fn install(registry: CRSRegistry) -> Result<(), CRSRegistry> {
+    REGISTRY.set(registry)
+}
+
+// Accepts 27700, EPSG:27700, or urn:ogc:def:crs:EPSG::27700 style codes.
+pub fn parse_code(code: &str) -> Option<u32> {
+    let code = code.trim();
+    let digits = code.rsplit([':', '/']).next().unwrap_or(code);
+    if digits.len() != code.len() && !code.to_ascii_uppercase().contains("EPSG") {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+// Name of the CRS a WKT definition describes, its first quoted string.
+pub fn wkt_name(wkt: &str) -> Option<String> {
+    let start = wkt.find('"')? + 1;
+    let end = start + wkt[start..].find('"')?;
+    Some(wkt[start..end].to_string())
+}
+
+// Identifies a CRS for the CRS metadata tag, by EPSG code where it has one, otherwise by name.
+pub fn wkt_label(wkt: &str) -> String {
+    match wkt_authority(wkt) {
+        Some(code) => format!("EPSG:{code}"),
+        None => wkt_name(wkt).unwrap_or_else(|| "Unknown".to_string()),
+    }
+}
+
+// EPSG code of the root of a WKT definition, from its AUTHORITY (WKT1) or ID (WKT2).
+pub fn wkt_authority(wkt: &str) -> Option<u32> {
+    let upper = wkt.to_ascii_uppercase();
+    let mut depth = 0i32;
+    let mut in_quotes = false;
+    let mut code = None;
+    for (index, c) in upper.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '[' | '(' if !in_quotes => depth += 1,
+            ']' | ')' if !in_quotes => depth -= 1,
+            // Only keys directly within the root element describe the CRS as a whole.
+            'A' | 'I' if !in_quotes && depth == 1 => {
+                let rest = &upper[index..];
+                let Some(rest) = rest
+                    .strip_prefix("AUTHORITY")
+                    .or_else(|| rest.strip_prefix("ID"))
+                else {
+                    continue;
+                };
+                let rest = rest.trim_start_matches(['[', '(']).trim_start();
+                if let Some(rest) = rest.strip_prefix("\"EPSG\"") {
+                    let digits: String = rest
+                        .trim_start_matches([',', ' ', '"'])
+                        .chars()
+                        .take_while(|c| c.is_ascii_digit())
+                        .collect();
+                    code = digits.parse().ok().or(code);
+                }
+            }
+            _ => {}
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_lookup() {
+        let registry = CRSRegistry::new();
+        assert!(registry.proj_string(27700).unwrap().contains("+proj=tmerc"));
+        assert!(registry.projection(4326).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_code() {
+        let registry = CRSRegistry::new();
+        assert!(registry.proj_string(999_999).is_none());
+        assert!(matches!(
+            registry.projection(999_999),
+            Err(CRSErrorState::UnknownCRS(999_999))
+        ));
+        // Reported once, however many files use it.
+        assert!(registry.projection(999_999).is_err());
+        assert_eq!(registry.reported.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_user_definitions() {
+        let file = format!(
+            "# Custom definitions\n\nEPSG:999999 +proj=stere +lat_0=90 +lat_ts=70 +lon_0=-45 +datum=WGS84 +units=m\n102100 {}\n",
+            crs_definitions::EPSG_3857.wkt
+        );
+        let registry = CRSRegistry::from_reader(file.as_bytes()).unwrap();
+        assert_eq!(registry.user_definitions(), 2);
+        assert!(registry.projection(999_999).is_ok());
+        assert!(registry.proj_string(102100).unwrap().contains("+proj=merc"));
+        // Built in definitions are still available.
+        assert!(registry.projection(27700).is_ok());
+    }
+
+    #[test]
+    fn test_user_definitions_override_builtin() {
+        let registry =
+            CRSRegistry::from_reader("4326 +proj=longlat +ellps=GRS80 +no_defs".as_bytes())
+                .unwrap();
+        assert_eq!(
+            registry.proj_string(4326).unwrap(),
+            "+proj=longlat +ellps=GRS80 +no_defs"
+        );
+    }
+
+    #[test]
+    fn test_invalid_user_definition() {
+        let result = CRSRegistry::from_reader("EPSG:1234 +proj=nonsense\n".as_bytes());
+        assert!(matches!(
+            result,
+            Err(CRSErrorState::InvalidDefinition(1, _))
+        ));
+        let result = CRSRegistry::from_reader("\nnot_a_code +proj=longlat\n".as_bytes());
+        assert!(matches!(
+            result,
+            Err(CRSErrorState::InvalidDefinition(2, _))
+        ));
+    }
+
+    #[test]
+    fn test_parse_code() {
+        assert_eq!(parse_code("27700"), Some(27700));
+        assert_eq!(parse_code("EPSG:27700"), Some(27700));
+        assert_eq!(parse_code("urn:ogc:def:crs:EPSG::27700"), Some(27700));
+        assert_eq!(parse_code("ESRI:102100"), None);
+        assert_eq!(parse_code("abc"), None);
+    }
+
+    #[test]
+    fn test_wkt_authority() {
+        assert_eq!(wkt_authority(crs_definitions::EPSG_27700.wkt), Some(27700));
+        assert_eq!(wkt_authority(crs_definitions::EPSG_4326.wkt), Some(4326));
+        // Only nested authorities, the root has none.
+        assert_eq!(
+            wkt_authority(
+                r#"GEOGCS["Custom",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137,298.257223563,AUTHORITY["EPSG","7030"]]],UNIT["Degree",0.0174532925199433]]"#
+            ),
+            None
+        );
+        assert_eq!(
+            wkt_authority(
+                r#"GEOGCRS["WGS 84",DATUM["World Geodetic System 1984",ELLIPSOID["WGS 84",6378137,298.257223563]],ID["EPSG",4326]]"#
+            ),
+            Some(4326)
+        );
+    }
+
+    #[test]
+    fn test_wkt_label() {
+        assert_eq!(wkt_label(crs_definitions::EPSG_27700.wkt), "EPSG:27700");
+        assert_eq!(
+            wkt_label(r#"PROJCS["Custom_Grid",GEOGCS["GCS_WGS_1984"]]"#),
+            "Custom_Grid"
+        );
+    }
+
+    #[test]
+    fn test_projection_from_wkt_without_authority() {
+        let registry = CRSRegistry::new();
+        let wkt = r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#;
+        assert!(registry.projection_from_wkt(wkt).is_ok());
+    }
+}
@@ -1,26 +1,35 @@
+use crate::parsing::fields::{numeric_field, text_field};
 use crate::spatial::Coordinate;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
 use std::path::PathBuf;
+use tracing::{event, Level};
+
 #[derive(Debug)]
 pub enum DT2ErrorState {
     UnexpectedFormat(String),
     UHLError(UHLErrorState),
     DSIError(DSIErrorState),
+    ACCError(ACCErrorState),
+    DataRecordError(DataRecordErrorState),
 }
 
 impl Display for DT2ErrorState {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            DT2ErrorState::UnexpectedFormat(s) => write!(f, "UnexpectedFormatError: {s}"),
+            DT2ErrorState::UHLError(e) => write!(f, "UHLError: {e}"),
+            DT2ErrorState::DSIError(e) => write!(f, "DSIError: {e}"),
+            DT2ErrorState::ACCError(e) => write!(f, "ACCError: {e}"),
+            DT2ErrorState::DataRecordError(e) => write!(f, "DataRecordError: {e}"),
+        }
     }
 }
 
-impl Error for DT2ErrorState {
-    // TODO: Implement error descriptions!
-}
+impl Error for DT2ErrorState {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DTEDMap {
@@ -34,6 +43,26 @@ pub enum UHLErrorState {
     InvalidDDMMSSH([u8; 8]),
 }
 
+impl Display for UHLErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UHLErrorState::InvalidLength(length) => {
+                write!(f, "Expected {UHL_LENGTH} bytes, got {length}")
+            }
+            UHLErrorState::InvalidSentinel(sentinel) => write!(
+                f,
+                "Invalid sentinel {:?}",
+                String::from_utf8_lossy(sentinel)
+            ),
+            UHLErrorState::InvalidDDMMSSH(data) => write!(
+                f,
+                "Invalid DDDMMSSH coordinate {:?}",
+                String::from_utf8_lossy(data)
+            ),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum DSIErrorState {
     InvalidLength(usize),
@@ -42,6 +71,87 @@ pub enum DSIErrorState {
     InvalidDDDMMSSH([u8; 8]),
 }
 
+impl Display for DSIErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DSIErrorState::InvalidLength(length) => {
+                write!(f, "Expected {DSI_LENGTH} bytes, got {length}")
+            }
+            DSIErrorState::InvalidSentinel(sentinel) => write!(
+                f,
+                "Invalid sentinel {:?}",
+                String::from_utf8_lossy(sentinel)
+            ),
+            DSIErrorState::InvalidDDMMSSH(data) => write!(
+                f,
+                "Invalid DDMMSSH coordinate {:?}",
+                String::from_utf8_lossy(data)
+            ),
+            DSIErrorState::InvalidDDDMMSSH(data) => write!(
+                f,
+                "Invalid DDDMMSSH coordinate {:?}",
+                String::from_utf8_lossy(data)
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ACCErrorState {
+    InvalidLength(usize),
+    InvalidSentinel([u8; 3]),
+}
+
+impl Display for ACCErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ACCErrorState::InvalidLength(length) => {
+                write!(f, "Expected {ACC_LENGTH} bytes, got {length}")
+            }
+            ACCErrorState::InvalidSentinel(sentinel) => write!(
+                f,
+                "Invalid sentinel {:?}",
+                String::from_utf8_lossy(sentinel)
+            ),
+        }
+    }
+}
+
+// Problems with an individual data record, by its index.
+#[derive(Debug)]
+pub enum DataRecordErrorState {
+    Truncated(usize),
+    InvalidSentinel(usize, u8),
+    InvalidChecksum(usize),
+}
+
+impl Display for DataRecordErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataRecordErrorState::Truncated(record) => {
+                write!(f, "Data record {record} is truncated")
+            }
+            DataRecordErrorState::InvalidSentinel(record, found) => write!(
+                f,
+                "Data record {record} has an invalid sentinel: {found:#04x}"
+            ),
+            DataRecordErrorState::InvalidChecksum(record) => {
+                write!(f, "Data record {record} fails its checksum")
+            }
+        }
+    }
+}
+
+// Record lengths, the three header records precede the elevation data.
+const UHL_LENGTH: usize = 80;
+const DSI_LENGTH: usize = 648;
+const ACC_LENGTH: usize = 2700;
+
+// Sentinel starting every data record.
+const DATA_SENTINEL: u8 = 0xAA;
+// Elevation for points without data.
+const VOID_ELEVATION: i16 = -32767;
+
 fn parse_dddmmssh(data: &[u8]) -> Result<f64, DT2ErrorState> {
     if data.len() != 8 {
         return Err(DT2ErrorState::DSIError(DSIErrorState::InvalidLength(
@@ -162,6 +272,12 @@ pub struct DT2MetaData {
 #[derive(Debug)]
 pub struct UserHeaderLabel {
     origin: (f64, f64),
+    // Number of longitude lines (data records), and of elevations along each.
+    longitude_lines: Option<usize>,
+    latitude_points: Option<usize>,
+    // Post spacing, in tenths of arc seconds.
+    longitude_interval: Option<u32>,
+    latitude_interval: Option<u32>,
 }
 
 impl UserHeaderLabel {
@@ -204,7 +320,11 @@ impl UserHeaderLabel {
             }
         };
         return Ok(UserHeaderLabel {
-            origin: (latitude, longitude), // For now we will only do origin, as is all that is useful.
+            origin: (latitude, longitude),
+            longitude_lines: numeric_field(&buffer[47..51]),
+            latitude_points: numeric_field(&buffer[51..55]),
+            longitude_interval: numeric_field(&buffer[20..24]),
+            latitude_interval: numeric_field(&buffer[24..28]),
         });
     }
 }
@@ -215,6 +335,16 @@ pub struct DataSetIdentification {
     ne_corner: Coordinate,
     nw_corner: Coordinate,
     se_corner: Coordinate,
+    security_classification: Option<String>,
+    // Series designator, such as DTED1.
+    product_level: Option<String>,
+    producer: Option<String>,
+    edition: Option<u32>,
+    // Post spacing, in tenths of arc seconds.
+    latitude_interval: Option<u32>,
+    longitude_interval: Option<u32>,
+    // Number of elevations along each longitude line.
+    latitude_lines: Option<usize>,
 }
 
 impl DataSetIdentification {
@@ -323,19 +453,263 @@ impl DataSetIdentification {
             }
         };
 
+        let security_classification = match buffer[3] {
+            b'S' => Some("Secret".to_string()),
+            b'C' => Some("Confidential".to_string()),
+            b'R' => Some("Restricted".to_string()),
+            b'U' => Some("Unclassified".to_string()),
+            _ => None,
+        };
+
         return Ok(DataSetIdentification {
             sw_corner: (sw_long, sw_lat),
             ne_corner: (ne_long, ne_lat),
             nw_corner: (nw_long, nw_lat),
             se_corner: (se_long, se_lat),
+            security_classification,
+            product_level: text_field(&buffer[59..64]),
+            producer: text_field(&buffer[102..110]),
+            edition: numeric_field(&buffer[87..89]),
+            latitude_interval: numeric_field(&buffer[273..277]),
+            longitude_interval: numeric_field(&buffer[277..281]),
+            latitude_lines: numeric_field(&buffer[281..285]),
         });
     }
 }
 
-pub fn parse_dted(reader: &mut BufReader<File>) -> Result<DT2MetaData, DT2ErrorState> {
-    let tags = vec![("Filetype".to_string(), "DTED".to_string())];
-    let mut uhl_buf = [0u8; 80];
-    let _uhl = match reader.read_exact(&mut uhl_buf) {
+// DTED level from its latitude spacing (tenths of arc seconds), which unlike longitude spacing
+// doesn't vary with latitude. Falls back to the number of points along each longitude line.
+pub fn detect_level(latitude_interval: Option<u32>, latitude_points: Option<usize>) -> Option<u8> {
+    match (latitude_interval, latitude_points) {
+        (Some(300), _) | (None, Some(121)) => Some(0),
+        (Some(30), _) | (None, Some(1201)) => Some(1),
+        (Some(10), _) | (None, Some(3601)) => Some(2),
+        _ => None,
+    }
+}
+
+// Accuracies in metres, at 90% confidence. None where not available.
+#[derive(Debug)]
+pub struct AccuracyDescription {
+    absolute_horizontal: Option<u32>,
+    absolute_vertical: Option<u32>,
+    relative_horizontal: Option<u32>,
+    relative_vertical: Option<u32>,
+}
+
+impl AccuracyDescription {
+    pub fn from_bytes(buffer: &[u8]) -> Result<AccuracyDescription, DT2ErrorState> {
+        if buffer.len() != ACC_LENGTH {
+            return Err(DT2ErrorState::ACCError(ACCErrorState::InvalidLength(
+                buffer.len(),
+            )));
+        }
+        let sentinel = &buffer[0..3];
+        if sentinel != b"ACC" {
+            return Err(DT2ErrorState::ACCError(ACCErrorState::InvalidSentinel(
+                sentinel.try_into().unwrap(),
+            )));
+        }
+        Ok(AccuracyDescription {
+            absolute_horizontal: numeric_field(&buffer[3..7]),
+            absolute_vertical: numeric_field(&buffer[7..11]),
+            relative_horizontal: numeric_field(&buffer[11..15]),
+            relative_vertical: numeric_field(&buffer[15..19]),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ElevationStatistics {
+    pub min: i16,
+    pub max: i16,
+    pub mean: f64,
+}
+
+// Elevations are stored as big endian signed magnitude, rather than two's complement.
+fn decode_elevation(bytes: [u8; 2]) -> i16 {
+    let raw = u16::from_be_bytes(bytes);
+    let magnitude = (raw & 0x7FFF) as i16;
+    match raw & 0x8000 {
+        0 => magnitude,
+        _ => -magnitude,
+    }
+}
+
+// Reads every data record following the ACC, verifying each record's sentinel and checksum.
+// Voids are skipped, so None if every point is void.
+pub fn scan_elevations(
+    reader: &mut BufReader<File>,
+    longitude_lines: usize,
+    latitude_points: usize,
+) -> Result<Option<ElevationStatistics>, DT2ErrorState> {
+    // Sentinel, block count, longitude and latitude counts, elevations, then checksum.
+    let mut record = vec![0u8; 8 + latitude_points * 2 + 4];
+    let checksum_start = record.len() - 4;
+    let (mut min, mut max) = (i16::MAX, i16::MIN);
+    let (mut sum, mut count) = (0f64, 0usize);
+    for line in 0..longitude_lines {
+        reader
+            .read_exact(&mut record)
+            .map_err(|_| DT2ErrorState::DataRecordError(DataRecordErrorState::Truncated(line)))?;
+        if record[0] != DATA_SENTINEL {
+            return Err(DT2ErrorState::DataRecordError(
+                DataRecordErrorState::InvalidSentinel(line, record[0]),
+            ));
+        }
+        // Checksum is the sum of every preceding byte in the record.
+        let checksum = record[..checksum_start]
+            .iter()
+            .fold(0u32, |sum, b| sum.wrapping_add(*b as u32));
+        if checksum != u32::from_be_bytes(record[checksum_start..].try_into().unwrap()) {
+            return Err(DT2ErrorState::DataRecordError(
+                DataRecordErrorState::InvalidChecksum(line),
+            ));
+        }
+        for elevation in record[8..checksum_start].chunks_exact(2) {
+            let elevation = decode_elevation([elevation[0], elevation[1]]);
+            if elevation == VOID_ELEVATION {
+                continue;
+            }
+            min = min.min(elevation);
+            max = max.max(elevation);
+            sum += elevation as f64;
+            count += 1;
+        }
+    }
+    Ok(match count {
+        0 => None,
+        _ => Some(ElevationStatistics {
+            min,
+            max,
+            mean: sum / count as f64,
+        }),
+    })
+}
+
+// Post layout of a DTED file, for reading elevations at a point without scanning the whole file.
+#[derive(Debug, Clone)]
+pub struct DTEDGrid {
+    // South west post, as (long, lat).
+    origin: Coordinate,
+    // Post spacing in degrees.
+    longitude_interval: f64,
+    latitude_interval: f64,
+    longitude_lines: usize,
+    latitude_points: usize,
+}
+
+impl DTEDGrid {
+    pub fn from_reader(reader: &mut BufReader<File>) -> Result<DTEDGrid, DT2ErrorState> {
+        let mut uhl_buf = [0u8; UHL_LENGTH];
+        reader
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_exact(&mut uhl_buf))
+            .map_err(|e| DT2ErrorState::UnexpectedFormat(format!("Failed to read bytes: {e:?}")))?;
+        let uhl = UserHeaderLabel::from_bytes(&uhl_buf)?;
+        let (
+            Some(longitude_interval),
+            Some(latitude_interval),
+            Some(longitude_lines @ 2..),
+            Some(latitude_points @ 2..),
+        ) = (
+            uhl.longitude_interval,
+            uhl.latitude_interval,
+            uhl.longitude_lines,
+            uhl.latitude_points,
+        )
+        else {
+            return Err(DT2ErrorState::UnexpectedFormat(
+                "UHL is missing the post spacing or counts needed to read elevations".to_string(),
+            ));
+        };
+        Ok(DTEDGrid {
+            origin: (uhl.origin.1, uhl.origin.0),
+            longitude_interval: longitude_interval as f64 / 36000.0,
+            latitude_interval: latitude_interval as f64 / 36000.0,
+            longitude_lines,
+            latitude_points,
+        })
+    }
+
+    fn record_length(&self) -> usize {
+        8 + self.latitude_points * 2 + 4
+    }
+
+    fn post(
+        &self,
+        reader: &mut BufReader<File>,
+        line: usize,
+        point: usize,
+    ) -> Result<Option<f64>, DT2ErrorState> {
+        let offset =
+            UHL_LENGTH + DSI_LENGTH + ACC_LENGTH + line * self.record_length() + 8 + point * 2;
+        let mut bytes = [0u8; 2];
+        reader
+            .seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| reader.read_exact(&mut bytes))
+            .map_err(|_| DT2ErrorState::DataRecordError(DataRecordErrorState::Truncated(line)))?;
+        Ok(match decode_elevation(bytes) {
+            VOID_ELEVATION => None,
+            elevation => Some(elevation as f64),
+        })
+    }
+
+    // Bilinear interpolation between the four surrounding posts, ignoring any which are void.
+    // None if the point is outside the grid, or every surrounding post is void.
+    pub fn elevation_at(
+        &self,
+        reader: &mut BufReader<File>,
+        (long, lat): Coordinate,
+    ) -> Result<Option<f64>, DT2ErrorState> {
+        let x = (long - self.origin.0) / self.longitude_interval;
+        let y = (lat - self.origin.1) / self.latitude_interval;
+        // Allow for rounding on the far edges.
+        let (max_x, max_y) = (
+            (self.longitude_lines - 1) as f64,
+            (self.latitude_points - 1) as f64,
+        );
+        if !(-1e-9..=max_x + 1e-9).contains(&x) || !(-1e-9..=max_y + 1e-9).contains(&y) {
+            return Ok(None);
+        }
+        let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+        let line = (x.floor() as usize).min(self.longitude_lines - 2);
+        let point = (y.floor() as usize).min(self.latitude_points - 2);
+        let (tx, ty) = (x - line as f64, y - point as f64);
+
+        let mut weighted = 0.0;
+        let mut total_weight = 0.0;
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - tx) * (1.0 - ty)),
+            (1, 0, tx * (1.0 - ty)),
+            (0, 1, (1.0 - tx) * ty),
+            (1, 1, tx * ty),
+        ] {
+            // Also skips posts only reached through rounding error.
+            if weight < 1e-9 {
+                continue;
+            }
+            if let Some(elevation) = self.post(reader, line + dx, point + dy)? {
+                weighted += elevation * weight;
+                total_weight += weight;
+            }
+        }
+        Ok(match total_weight > 0.0 {
+            true => Some(weighted / total_weight),
+            false => None,
+        })
+    }
+}
+
+// Parses the DTED headers. Elevation statistics and integrity checks need every data record read,
+// so are optional.
+pub fn parse_dted(
+    reader: &mut BufReader<File>,
+    scan_records: bool,
+) -> Result<DT2MetaData, DT2ErrorState> {
+    let mut tags = vec![("Filetype".to_string(), "DTED".to_string())];
+    let mut uhl_buf = [0u8; UHL_LENGTH];
+    let uhl = match reader.read_exact(&mut uhl_buf) {
         Ok(_) => UserHeaderLabel::from_bytes(&uhl_buf)?,
         Err(e) => {
             return Err(DT2ErrorState::UnexpectedFormat(
@@ -343,7 +717,7 @@ pub fn parse_dted(reader: &mut BufReader<File>) -> Result<DT2MetaData, DT2ErrorS
             ));
         }
     };
-    let mut dsi_buf = [0u8; 648];
+    let mut dsi_buf = [0u8; DSI_LENGTH];
     let dsi = match reader.read_exact(&mut dsi_buf) {
         Ok(_) => DataSetIdentification::from_bytes(&dsi_buf)?,
         Err(e) => {
@@ -352,6 +726,88 @@ pub fn parse_dted(reader: &mut BufReader<File>) -> Result<DT2MetaData, DT2ErrorS
             ));
         }
     };
+    // Some files are truncated to their identifying records, so the ACC is optional.
+    let mut acc_buf = vec![0u8; ACC_LENGTH];
+    let acc = match reader.read_exact(&mut acc_buf) {
+        Ok(_) => Some(AccuracyDescription::from_bytes(&acc_buf)?),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+            event!(
+                Level::INFO,
+                "DTED file ends before its ACC record, accuracy is unknown."
+            );
+            None
+        }
+        Err(e) => {
+            return Err(DT2ErrorState::UnexpectedFormat(format!(
+                "Failed to read bytes: {e:?}"
+            )));
+        }
+    };
+
+    if let Some(level) = detect_level(
+        dsi.latitude_interval,
+        dsi.latitude_lines.or(uhl.latitude_points),
+    ) {
+        tags.push(("Level".to_string(), format!("DTED{level}")));
+    }
+    tags.push(("OriginLatitude".to_string(), uhl.origin.0.to_string()));
+    tags.push(("OriginLongitude".to_string(), uhl.origin.1.to_string()));
+    let dsi_tags = [
+        ("SecurityClassification", dsi.security_classification),
+        ("ProductLevel", dsi.product_level),
+        ("Producer", dsi.producer),
+        ("Edition", dsi.edition.map(|e| e.to_string())),
+        // Tenths of arc seconds to arc seconds.
+        (
+            "LatitudeInterval",
+            dsi.latitude_interval.map(|i| (i as f64 / 10.0).to_string()),
+        ),
+        (
+            "LongitudeInterval",
+            dsi.longitude_interval
+                .map(|i| (i as f64 / 10.0).to_string()),
+        ),
+    ];
+    for (key, value) in dsi_tags {
+        if let Some(value) = value {
+            tags.push((key.to_string(), value));
+        }
+    }
+    if let Some(acc) = &acc {
+        let acc_tags = [
+            ("AbsoluteHorizontalAccuracy", acc.absolute_horizontal),
+            ("AbsoluteVerticalAccuracy", acc.absolute_vertical),
+            ("RelativeHorizontalAccuracy", acc.relative_horizontal),
+            ("RelativeVerticalAccuracy", acc.relative_vertical),
+        ];
+        for (key, value) in acc_tags {
+            if let Some(value) = value {
+                tags.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+
+    if let (true, Some(_), Some(lines), Some(points)) =
+        (scan_records, &acc, uhl.longitude_lines, uhl.latitude_points)
+    {
+        match scan_elevations(reader, lines, points) {
+            Ok(stats) => {
+                tags.push(("Integrity".to_string(), "passed".to_string()));
+                if let Some(stats) = stats {
+                    tags.push(("MinElevation".to_string(), stats.min.to_string()));
+                    tags.push(("MaxElevation".to_string(), stats.max.to_string()));
+                    tags.push(("MeanElevation".to_string(), format!("{:.1}", stats.mean)));
+                }
+            }
+            // Still worth indexing, the headers were valid.
+            Err(DT2ErrorState::DataRecordError(e)) => {
+                event!(Level::WARN, "DTED integrity check failed: {e}");
+                tags.push(("Integrity".to_string(), "failed".to_string()));
+                tags.push(("IntegrityError".to_string(), e.to_string()));
+            }
+            Err(e) => return Err(e),
+        }
+    }
 
     return Ok(DT2MetaData {
         region: DT2Region {
@@ -365,8 +821,9 @@ pub fn parse_dted(reader: &mut BufReader<File>) -> Result<DT2MetaData, DT2ErrorS
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::parsing::tests::tag;
     use std::io::{Seek, SeekFrom, Write};
     use tempfile::tempfile;
 
@@ -496,7 +953,7 @@ mod tests {
         let mut reader = BufReader::new(temp_file);
 
         // Call parse_dt2 function
-        let result = parse_dted(&mut reader);
+        let result = parse_dted(&mut reader, true);
         assert!(result.is_ok());
 
         // Validate the returned DT2Region (adjust assertions based on actual data and expected results)
@@ -540,4 +997,183 @@ mod tests {
             Err(DT2ErrorState::UHLError(UHLErrorState::InvalidLength(_)))
         ));
     }
+
+    // A complete DTED file, of longitude lines each holding the given elevations.
+    pub(crate) fn mock_dted(lines: &[Vec<u16>]) -> Vec<u8> {
+        let points = lines.first().map_or(0, |l| l.len());
+        let mut uhl = vec![b' '; UHL_LENGTH];
+        uhl[0..4].copy_from_slice(b"UHL1");
+        uhl[4..12].copy_from_slice(b"0010000E");
+        uhl[12..20].copy_from_slice(b"0500000N");
+        // 3 minute spacing, 0.05 degrees.
+        uhl[20..28].copy_from_slice(b"18001800");
+        uhl[47..51].copy_from_slice(format!("{:04}", lines.len()).as_bytes());
+        uhl[51..55].copy_from_slice(format!("{points:04}").as_bytes());
+
+        let mut dsi = vec![b' '; DSI_LENGTH];
+        dsi[0..4].copy_from_slice(b"DSIU");
+        dsi[59..64].copy_from_slice(b"DTED2");
+        dsi[87..89].copy_from_slice(b"03");
+        dsi[102..110].copy_from_slice(b"USNIMA  ");
+        for (start, corner) in [
+            (204, "500000N0010000E"),
+            (219, "510000N0010000E"),
+            (234, "510000N0020000E"),
+            (249, "500000N0020000E"),
+        ] {
+            dsi[start..start + 15].copy_from_slice(corner.as_bytes());
+        }
+        dsi[273..277].copy_from_slice(b"0010");
+        dsi[277..281].copy_from_slice(b"0020");
+
+        let mut acc = vec![b' '; ACC_LENGTH];
+        acc[0..3].copy_from_slice(b"ACC");
+        acc[3..7].copy_from_slice(b"0050");
+        acc[7..11].copy_from_slice(b"0030");
+        acc[11..15].copy_from_slice(b"NA  ");
+        acc[15..19].copy_from_slice(b"0012");
+
+        let mut data = [uhl, dsi, acc].concat();
+        for (index, line) in lines.iter().enumerate() {
+            let start = data.len();
+            data.push(DATA_SENTINEL);
+            data.extend_from_slice(&(index as u32).to_be_bytes()[1..]);
+            data.extend_from_slice(&(index as u16).to_be_bytes());
+            data.extend_from_slice(&[0, 0]);
+            for elevation in line {
+                data.extend_from_slice(&elevation.to_be_bytes());
+            }
+            let checksum: u32 = data[start..].iter().map(|b| *b as u32).sum();
+            data.extend_from_slice(&checksum.to_be_bytes());
+        }
+        data
+    }
+
+    fn mock_reader(data: &[u8]) -> BufReader<File> {
+        let mut temp_file = tempfile().unwrap();
+        temp_file.write_all(data).unwrap();
+        temp_file.seek(SeekFrom::Start(0)).unwrap();
+        BufReader::new(temp_file)
+    }
+
+    #[test]
+    fn test_decode_elevation() {
+        assert_eq!(decode_elevation([0x01, 0x2C]), 300);
+        assert_eq!(decode_elevation([0x80, 0x0A]), -10);
+        assert_eq!(decode_elevation([0xFF, 0xFF]), VOID_ELEVATION);
+    }
+
+    #[test]
+    fn test_parse_dted_tags() {
+        // -10 is stored as signed magnitude, 0xFFFF is a void.
+        let data = mock_dted(&[vec![100, 200, 0x800A], vec![300, 0xFFFF, 50]]);
+        let metadata = parse_dted(&mut mock_reader(&data), true).unwrap();
+        let tags = &metadata.tags;
+
+        assert_eq!(tag(tags, "OriginLatitude"), Some("50"));
+        assert_eq!(tag(tags, "OriginLongitude"), Some("1"));
+        assert_eq!(tag(tags, "SecurityClassification"), Some("Unclassified"));
+        assert_eq!(tag(tags, "ProductLevel"), Some("DTED2"));
+        assert_eq!(tag(tags, "Producer"), Some("USNIMA"));
+        assert_eq!(tag(tags, "Edition"), Some("3"));
+        assert_eq!(tag(tags, "LatitudeInterval"), Some("1"));
+        assert_eq!(tag(tags, "LongitudeInterval"), Some("2"));
+        assert_eq!(tag(tags, "AbsoluteHorizontalAccuracy"), Some("50"));
+        assert_eq!(tag(tags, "AbsoluteVerticalAccuracy"), Some("30"));
+        assert_eq!(tag(tags, "RelativeHorizontalAccuracy"), None);
+        assert_eq!(tag(tags, "RelativeVerticalAccuracy"), Some("12"));
+        assert_eq!(tag(tags, "MinElevation"), Some("-10"));
+        assert_eq!(tag(tags, "MaxElevation"), Some("300"));
+        assert_eq!(tag(tags, "MeanElevation"), Some("128.0"));
+        assert_eq!(tag(tags, "Level"), Some("DTED2"));
+        assert_eq!(tag(tags, "Integrity"), Some("passed"));
+        assert_eq!(metadata.region.bottom_left, (1.0, 50.0));
+        assert_eq!(metadata.region.top_right, (2.0, 51.0));
+    }
+
+    #[test]
+    fn test_parse_dted_without_elevation_scan() {
+        let data = mock_dted(&[vec![100, 200], vec![300, 400]]);
+        let metadata = parse_dted(&mut mock_reader(&data), false).unwrap();
+        assert_eq!(tag(&metadata.tags, "AbsoluteVerticalAccuracy"), Some("30"));
+        assert_eq!(tag(&metadata.tags, "MinElevation"), None);
+    }
+
+    #[test]
+    fn test_parse_dted_truncated_data_records() {
+        // The headers are still indexed, without elevation statistics.
+        let data = mock_dted(&[vec![100, 200], vec![300, 400]]);
+        let metadata = parse_dted(&mut mock_reader(&data[..data.len() - 6]), true).unwrap();
+        assert_eq!(tag(&metadata.tags, "ProductLevel"), Some("DTED2"));
+        assert_eq!(tag(&metadata.tags, "MinElevation"), None);
+        assert_eq!(tag(&metadata.tags, "Integrity"), Some("failed"));
+        assert_eq!(
+            tag(&metadata.tags, "IntegrityError"),
+            Some("Data record 1 is truncated")
+        );
+    }
+
+    #[test]
+    fn test_parse_dted_corrupt_elevation() {
+        let mut data = mock_dted(&[vec![100, 200], vec![300, 400]]);
+        // Second elevation of the first record.
+        data[UHL_LENGTH + DSI_LENGTH + ACC_LENGTH + 11] ^= 0x01;
+        let metadata = parse_dted(&mut mock_reader(&data), true).unwrap();
+        assert_eq!(tag(&metadata.tags, "Integrity"), Some("failed"));
+        assert_eq!(
+            tag(&metadata.tags, "IntegrityError"),
+            Some("Data record 0 fails its checksum")
+        );
+    }
+
+    #[test]
+    fn test_parse_dted_invalid_record_sentinel() {
+        let mut data = mock_dted(&[vec![100, 200], vec![300, 400]]);
+        // Start of the second record, after the first's 8 byte header, elevations and checksum.
+        data[UHL_LENGTH + DSI_LENGTH + ACC_LENGTH + 16] = 0x00;
+        let metadata = parse_dted(&mut mock_reader(&data), true).unwrap();
+        assert_eq!(
+            tag(&metadata.tags, "IntegrityError"),
+            Some("Data record 1 has an invalid sentinel: 0x00")
+        );
+    }
+
+    #[test]
+    fn test_detect_level() {
+        assert_eq!(detect_level(Some(300), None), Some(0));
+        assert_eq!(detect_level(Some(30), Some(1201)), Some(1));
+        assert_eq!(detect_level(None, Some(3601)), Some(2));
+        // Intervals take precedence over point counts.
+        assert_eq!(detect_level(Some(10), Some(1201)), Some(2));
+        assert_eq!(detect_level(Some(15), None), None);
+    }
+
+    #[test]
+    fn test_accuracy_description_invalid_sentinel() {
+        let buffer = vec![b' '; ACC_LENGTH];
+        assert!(matches!(
+            AccuracyDescription::from_bytes(&buffer),
+            Err(DT2ErrorState::ACCError(ACCErrorState::InvalidSentinel(_)))
+        ));
+    }
+
+    #[test]
+    fn test_elevation_at() {
+        // Two longitude lines of three points, 0.05 degrees apart from (1, 50).
+        let data = mock_dted(&[vec![100, 200, 300], vec![200, 0xFFFF, 500]]);
+        let mut reader = mock_reader(&data);
+        let grid = DTEDGrid::from_reader(&mut reader).unwrap();
+        let at = |reader: &mut BufReader<File>, point| grid.elevation_at(reader, point).unwrap();
+
+        assert_eq!(at(&mut reader, (1.0, 50.0)), Some(100.0));
+        assert_eq!(at(&mut reader, (1.05, 50.1)), Some(500.0));
+        // Half way along each axis, between 100, 200, 200 and the void.
+        let centre = at(&mut reader, (1.025, 50.025)).unwrap();
+        assert!((centre - 500.0 / 3.0).abs() < 1e-9, "{centre}");
+        assert!((at(&mut reader, (1.0, 50.075)).unwrap() - 250.0).abs() < 1e-9);
+        // The void itself.
+        assert_eq!(at(&mut reader, (1.05, 50.05)), None);
+        assert_eq!(at(&mut reader, (0.99, 50.05)), None);
+        assert_eq!(at(&mut reader, (1.02, 50.11)), None);
+    }
 }
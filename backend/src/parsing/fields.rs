@@ -0,0 +1,36 @@
+// Fixed width ASCII fields, as in DTED and NITF headers.
+
+// Trimmed ASCII field, None if blank.
+pub(crate) fn text_field(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    match text.is_empty() {
+        true => None,
+        false => Some(text.to_string()),
+    }
+}
+
+// Numeric ASCII field, None if blank or not a number, such as "NA" (not available).
+pub(crate) fn numeric_field<T: std::str::FromStr>(data: &[u8]) -> Option<T> {
+    text_field(data)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_field() {
+        assert_eq!(text_field(b"  Survey \0\0").as_deref(), Some("Survey"));
+        assert!(text_field(b"    ").is_none());
+        assert!(text_field(b"\0\0").is_none());
+    }
+
+    #[test]
+    fn test_numeric_field() {
+        assert_eq!(numeric_field::<u32>(b"0042"), Some(42));
+        assert_eq!(numeric_field::<i32>(b"-12 "), Some(-12));
+        assert!(numeric_field::<u32>(b"NA  ").is_none());
+        assert!(numeric_field::<u32>(b"    ").is_none());
+    }
+}
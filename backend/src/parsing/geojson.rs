@@ -1,38 +1,42 @@
-use crate::spatial::Coordinate;
+use crate::parsing::crs;
+use crate::parsing::reproject::{
+    densify, reproject_bounds, to_wgs84, GeographicExtent, FOOTPRINT_SAMPLES,
+};
+use crate::spatial::{convex_hull, Coordinate, FootprintBuffer};
 use json_event_parser::{JsonEvent, JsonReader};
+use proj4rs::Proj;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
+use tracing::{event, Level};
 
-pub fn get_boundaries(coordinates: Vec<[f64; 2]>) -> (Coordinate, Coordinate) {
-    let mut min_x: f64 = coordinates[0][0];
-    let mut min_y: f64 = coordinates[0][1];
-    let mut max_x: f64 = coordinates[0][0];
-    let mut max_y: f64 = coordinates[0][1];
-    for coordinate in coordinates {
-        if coordinate[0] > max_x {
-            max_x = coordinate[0];
-        }
-        if coordinate[0] < min_x {
-            min_x = coordinate[0];
-        }
-        if coordinate[1] > max_y {
-            max_y = coordinate[1];
-        }
-        if coordinate[1] < min_y {
-            min_y = coordinate[1];
-        }
-    }
-    return ((min_x, min_y), (max_x, max_y));
-}
+// Deepest nesting accepted; a MultiPolygon in a FeatureCollection needs 7.
+const MAX_DEPTH: usize = 64;
+// Beyond this many features, per feature envelopes are dropped rather than held in the index.
+pub const MAX_FEATURE_ENVELOPES: usize = 100_000;
+
+const GEOMETRY_TYPES: [&str; 7] = [
+    "Point",
+    "MultiPoint",
+    "LineString",
+    "MultiLineString",
+    "Polygon",
+    "MultiPolygon",
+    "GeometryCollection",
+];
 
 #[derive(Debug)]
 pub enum GeoJSONErrorState {
     InvalidJSON(Box<dyn Error>),
     UnparsableCoordinate(String),
+    InvalidStructure(String),
+    ProjectionError(String),
+    Empty,
+    NoCoordinates,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,13 +46,18 @@ pub struct GEOJSONMap {
 
 impl Display for GeoJSONErrorState {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            GeoJSONErrorState::InvalidJSON(e) => write!(f, "Invalid JSON: {e}"),
+            GeoJSONErrorState::UnparsableCoordinate(c) => write!(f, "Unparsable coordinate: {c}"),
+            GeoJSONErrorState::InvalidStructure(e) => write!(f, "Invalid GeoJSON: {e}"),
+            GeoJSONErrorState::ProjectionError(e) => write!(f, "Projection error: {e}"),
+            GeoJSONErrorState::Empty => write!(f, "Empty GeoJSON file"),
+            GeoJSONErrorState::NoCoordinates => write!(f, "GeoJSON file has no coordinates"),
+        }
     }
 }
 
-impl Error for GeoJSONErrorState {
-    // TODO: Add error descriptions!
-}
+impl Error for GeoJSONErrorState {}
 
 #[derive(Debug, Clone)]
 pub struct GeoJSONRegion {
@@ -56,104 +65,535 @@ pub struct GeoJSONRegion {
     pub bottom_left: Coordinate,
 }
 
+// Envelope of a single feature, numbered by its position in the file, for searching within files.
+#[derive(Debug, Clone)]
+pub struct GeoJSONFeature {
+    pub index: usize,
+    pub id: Option<String>,
+    pub region: GeoJSONRegion,
+}
+
 #[derive(Debug, Clone)]
 pub struct GeoJSONMetaData {
     pub region: GeoJSONRegion,
     pub tags: Vec<(String, String)>,
+    pub footprint: Option<Vec<Coordinate>>,
+    pub features: Option<Vec<GeoJSONFeature>>,
 }
 
-pub fn parse_geojson(reader: &mut BufReader<File>) -> Result<GeoJSONMetaData, GeoJSONErrorState> {
-    let mut json_reader = JsonReader::from_reader(reader);
-    let mut buffer = Vec::new();
-    let mut coordinate_pairs: Vec<[f64; 2]> = Vec::new();
-    let tags = vec![("Filetype".to_string(), "GEOJSON".to_string())];
-
-    while let event = match json_reader.read_event(&mut buffer) {
-        Ok(event) => event,
-        Err(e) => {
-            return Err(GeoJSONErrorState::InvalidJSON(Box::new(e)));
-        }
-    } {
-        match event {
-            JsonEvent::ObjectKey(k) if k == "coordinates" => {
-                // Coordinate capture.
-                let mut depth = 0;
-                let mut dimensions = 0;
-                let mut coord_pair_buf = [0f64, 0f64];
-                while let Ok(event) = json_reader.read_event(&mut buffer) {
-                    // coord capture logic...
-                    match event {
-                        JsonEvent::StartArray => {
-                            depth += 1; // Iterate depth.
-                        }
-                        JsonEvent::EndArray => {
-                            depth -= 1; // Decrement
-                            if dimensions != 0 {
-                                coordinate_pairs.push(coord_pair_buf.clone());
-                            }
-                            dimensions = 0;
-                        }
-                        JsonEvent::Number(num_str) => {
-                            if dimensions < 2 {
-                                coord_pair_buf[dimensions] = match num_str.parse::<f64>() {
-                                    Ok(v) => v,
-                                    Err(e) => {
-                                        eprintln!(
-                                            "Unparsable number string in GEOJSON file! {e:?}"
-                                        );
-                                        return Err(GeoJSONErrorState::UnparsableCoordinate(
-                                            num_str.to_string(),
-                                        ));
-                                    }
+// Running minimum and maximum of positions, only x and y are kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bounds(Option<(Coordinate, Coordinate)>);
+
+impl Bounds {
+    pub fn extend(&mut self, (x, y): Coordinate) {
+        self.0 = Some(match self.0 {
+            None => ((x, y), (x, y)),
+            Some((min, max)) => ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y))),
+        });
+    }
+
+    pub fn merge(&mut self, other: Bounds) {
+        if let Some((min, max)) = other.0 {
+            self.extend(min);
+            self.extend(max);
+        }
+    }
+
+    pub fn region(&self) -> Option<GeoJSONRegion> {
+        self.0.map(|(min, max)| GeoJSONRegion {
+            top_right: max,
+            bottom_left: min,
+        })
+    }
+}
+
+// What is known of an object once all its members are read, as members may come in any order.
+#[derive(Debug, Default)]
+struct Object {
+    kind: Option<String>,
+    id: Option<String>,
+    bounds: Bounds,
+    // Type of a Feature's geometry, None when it is null.
+    geometry: Option<String>,
+    // Name of the CRS from a crs member, as in GeoJSON before RFC 7946.
+    crs: Option<String>,
+}
+
+// Reads GeoJSON as a stream of events, so files are never held in memory whole.
+struct GeoJSONParser<'a> {
+    reader: JsonReader<&'a mut BufReader<File>>,
+    buffer: Vec<u8>,
+    hull: FootprintBuffer,
+    // An antimeridian crossing bbox means the positions can't be outlined by a hull.
+    wraps: bool,
+    features: usize,
+    geometry_counts: BTreeMap<String, usize>,
+    properties: BTreeSet<String>,
+    envelopes: Option<Vec<GeoJSONFeature>>,
+}
+
+impl<'a> GeoJSONParser<'a> {
+    fn new(reader: &'a mut BufReader<File>, feature_envelopes: bool) -> GeoJSONParser<'a> {
+        let mut reader = JsonReader::from_reader(reader);
+        reader.max_stack_size(MAX_DEPTH);
+        GeoJSONParser {
+            reader,
+            buffer: Vec::new(),
+            hull: FootprintBuffer::default(),
+            wraps: false,
+            features: 0,
+            geometry_counts: BTreeMap::new(),
+            properties: BTreeSet::new(),
+            envelopes: feature_envelopes.then(Vec::new),
+        }
+    }
+
+    fn next(&mut self) -> Result<JsonEvent<'_>, GeoJSONErrorState> {
+        self.reader
+            .read_event(&mut self.buffer)
+            .map_err(|e| GeoJSONErrorState::InvalidJSON(Box::new(e)))
+    }
+
+    // Skips the next value, however deeply nested.
+    fn skip_value(&mut self) -> Result<(), GeoJSONErrorState> {
+        let mut depth = 0usize;
+        loop {
+            match self.next()? {
+                JsonEvent::StartArray | JsonEvent::StartObject => depth += 1,
+                JsonEvent::EndArray | JsonEvent::EndObject => depth -= 1,
+                JsonEvent::ObjectKey(_) => continue,
+                JsonEvent::Eof => return Err(invalid("unexpected end of file")),
+                _ => {}
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Reads the members of an object, once its StartObject has been read.
+    fn read_object(&mut self) -> Result<Object, GeoJSONErrorState> {
+        let mut object = Object::default();
+        loop {
+            let key = match self.next()? {
+                JsonEvent::ObjectKey(key) => key.to_string(),
+                JsonEvent::EndObject => return Ok(object),
+                _ => return Err(invalid("expected an object member")),
+            };
+            match key.as_str() {
+                "type" => match self.next()? {
+                    JsonEvent::String(kind) => object.kind = Some(kind.to_string()),
+                    _ => return Err(invalid("type must be a string")),
+                },
+                "id" => match self.next()? {
+                    JsonEvent::String(id) | JsonEvent::Number(id) => {
+                        object.id = Some(id.to_string())
+                    }
+                    JsonEvent::Null => {}
+                    _ => return Err(invalid("id must be a string or number")),
+                },
+                "coordinates" => match self.next()? {
+                    JsonEvent::StartArray => self.read_positions(&mut object.bounds)?,
+                    JsonEvent::Null => {}
+                    _ => return Err(invalid("coordinates must be an array")),
+                },
+                "bbox" => {
+                    let bbox = self.read_bbox()?;
+                    object.bounds.merge(bbox);
+                }
+                "geometry" => match self.next()? {
+                    JsonEvent::StartObject => {
+                        let geometry = self.read_object()?;
+                        object.bounds.merge(geometry.bounds);
+                        object.geometry = geometry.kind;
+                    }
+                    JsonEvent::Null => {}
+                    _ => return Err(invalid("geometry must be an object or null")),
+                },
+                "geometries" | "features" => {
+                    if !matches!(self.next()?, JsonEvent::StartArray) {
+                        return Err(invalid(&format!("{key} must be an array")));
+                    }
+                    loop {
+                        match self.next()? {
+                            JsonEvent::StartObject => {
+                                let child = self.read_object()?;
+                                object.bounds.merge(child.bounds);
+                                if key == "features" {
+                                    self.add_feature(child);
                                 }
                             }
-                            dimensions += 1;
+                            JsonEvent::EndArray => break,
+                            _ => return Err(invalid(&format!("{key} must only hold objects"))),
                         }
-                        _ => {}
                     }
-                    if depth == 0 {
-                        break; // Terminate loop.
+                }
+                "properties" => self.read_property_keys()?,
+                "crs" => object.crs = self.read_crs()?,
+                _ => self.skip_value()?,
+            }
+        }
+    }
+
+    // Reads a position, or arrays of them nested to any depth, once the opening StartArray has been read.
+    fn read_positions(&mut self, bounds: &mut Bounds) -> Result<(), GeoJSONErrorState> {
+        let mut position = [0f64; 2];
+        let mut dimensions = 0;
+        let mut nested = false;
+        loop {
+            match self.next()? {
+                JsonEvent::Number(n) if !nested => {
+                    let value = parse_number(n)?;
+                    // Only x and y are indexed, altitude and beyond are ignored.
+                    if dimensions < 2 {
+                        position[dimensions] = value;
                     }
+                    dimensions += 1;
+                }
+                JsonEvent::StartArray if dimensions == 0 => {
+                    nested = true;
+                    self.read_positions(bounds)?;
                 }
+                JsonEvent::EndArray => break,
+                _ => return Err(invalid("positions must be arrays of numbers")),
             }
-            JsonEvent::Eof => {
-                break;
+        }
+        match dimensions {
+            // An empty array, as in an empty geometry.
+            0 => Ok(()),
+            1 => Err(invalid("positions need at least two numbers")),
+            _ => {
+                let position = (position[0], position[1]);
+                bounds.extend(position);
+                self.hull.push(position);
+                Ok(())
             }
-            _ => {}
         }
     }
 
-    let boundaries = get_boundaries(coordinate_pairs);
-    return Ok(GeoJSONMetaData {
-        region: GeoJSONRegion {
-            top_right: boundaries.1,
-            bottom_left: boundaries.0,
+    // A bbox holds all the minimums then all the maximums, in 2 or 3 dimensions.
+    fn read_bbox(&mut self) -> Result<Bounds, GeoJSONErrorState> {
+        if !matches!(self.next()?, JsonEvent::StartArray) {
+            return Err(invalid("bbox must be an array"));
+        }
+        let mut values = Vec::new();
+        loop {
+            match self.next()? {
+                JsonEvent::Number(n) => values.push(parse_number(n)?),
+                JsonEvent::EndArray => break,
+                _ => return Err(invalid("bbox must only hold numbers")),
+            }
+        }
+        if values.len() < 4 || values.len() % 2 != 0 {
+            return Err(invalid(&format!("bbox has {} values", values.len())));
+        }
+        let half = values.len() / 2;
+        let (west, south, east, north) = (values[0], values[1], values[half], values[half + 1]);
+        let mut bounds = Bounds::default();
+        if west > east {
+            // Crosses the antimeridian, which the index can't wrap, so covers every longitude.
+            self.wraps = true;
+            bounds.extend((-180.0, south));
+            bounds.extend((180.0, north));
+        } else {
+            bounds.extend((west, south));
+            bounds.extend((east, north));
+        }
+        Ok(bounds)
+    }
+
+    // Reads a named crs member, {"type": "name", "properties": {"name": "EPSG:27700"}},
+    // or an older {"type": "EPSG", "properties": {"code": 27700}}. Linked CRSes can't be followed.
+    fn read_crs(&mut self) -> Result<Option<String>, GeoJSONErrorState> {
+        match self.next()? {
+            JsonEvent::StartObject => {}
+            // A null crs means none can be assumed, which is treated as the default.
+            JsonEvent::Null => return Ok(None),
+            _ => return Err(invalid("crs must be an object or null")),
+        }
+        let mut name = None;
+        loop {
+            match self.next()? {
+                JsonEvent::ObjectKey("properties") => {}
+                JsonEvent::ObjectKey(_) => {
+                    self.skip_value()?;
+                    continue;
+                }
+                JsonEvent::EndObject => {
+                    return name
+                        .map(Some)
+                        .ok_or_else(|| invalid("crs has no name, linked CRSes aren't supported"));
+                }
+                _ => return Err(invalid("expected a crs member")),
+            }
+            if !matches!(self.next()?, JsonEvent::StartObject) {
+                return Err(invalid("crs properties must be an object"));
+            }
+            loop {
+                let key = match self.next()? {
+                    JsonEvent::ObjectKey(key) => key.to_string(),
+                    JsonEvent::EndObject => break,
+                    _ => return Err(invalid("expected a crs property")),
+                };
+                match key.as_str() {
+                    "name" | "code" => match self.next()? {
+                        JsonEvent::String(value) | JsonEvent::Number(value) => {
+                            name = Some(match key.as_str() {
+                                "code" => format!("EPSG:{value}"),
+                                _ => value.to_string(),
+                            });
+                        }
+                        _ => return Err(invalid("crs name must be a string")),
+                    },
+                    _ => self.skip_value()?,
+                }
+            }
+        }
+    }
+
+    // Collects the keys of a properties object, skipping their values.
+    fn read_property_keys(&mut self) -> Result<(), GeoJSONErrorState> {
+        match self.next()? {
+            JsonEvent::StartObject => {}
+            JsonEvent::Null => return Ok(()),
+            _ => return Err(invalid("properties must be an object or null")),
+        }
+        loop {
+            let key = match self.next()? {
+                JsonEvent::ObjectKey(key) => key.to_string(),
+                JsonEvent::EndObject => return Ok(()),
+                _ => return Err(invalid("expected a property")),
+            };
+            self.properties.insert(key);
+            self.skip_value()?;
+        }
+    }
+
+    fn add_feature(&mut self, feature: Object) {
+        let geometry = feature
+            .geometry
+            .unwrap_or_else(|| "NullGeometry".to_string());
+        *self.geometry_counts.entry(geometry).or_default() += 1;
+        if let (Some(envelopes), Some(region)) = (&mut self.envelopes, feature.bounds.region()) {
+            envelopes.push(GeoJSONFeature {
+                index: self.features,
+                id: feature.id,
+                region,
+            });
+            if envelopes.len() > MAX_FEATURE_ENVELOPES {
+                event!(
+                    Level::WARN,
+                    "Over {MAX_FEATURE_ENVELOPES} features in GEOJSON file, not storing their envelopes."
+                );
+                self.envelopes = None;
+            }
+        }
+        self.features += 1;
+    }
+}
+
+fn invalid(reason: &str) -> GeoJSONErrorState {
+    GeoJSONErrorState::InvalidStructure(reason.to_string())
+}
+
+fn parse_number(number: &str) -> Result<f64, GeoJSONErrorState> {
+    match number.parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        _ => {
+            eprintln!("Unparsable number string in GEOJSON file! {number:?}");
+            Err(GeoJSONErrorState::UnparsableCoordinate(number.to_string()))
+        }
+    }
+}
+
+fn projection_error(e: impl Display) -> GeoJSONErrorState {
+    GeoJSONErrorState::ProjectionError(e.to_string())
+}
+
+// Projection for a crs member's name, None where coordinates are already longitude and latitude.
+fn resolve_crs(name: &str) -> Result<Option<Proj>, GeoJSONErrorState> {
+    // CRS84 is WGS84 with longitude first, as RFC 7946 GeoJSON is.
+    if name.to_ascii_uppercase().ends_with("CRS84") {
+        return Ok(None);
+    }
+    match crs::parse_code(name) {
+        Some(4326) => Ok(None),
+        Some(code) => crs::registry()
+            .projection(code)
+            .map(Some)
+            .map_err(projection_error),
+        None => Err(GeoJSONErrorState::ProjectionError(format!(
+            "Unsupported crs {name}"
+        ))),
+    }
+}
+
+// Region in EPSG:4326 of a region in the given projection, with the extent it was taken from.
+fn reproject_region(
+    region: &GeoJSONRegion,
+    proj: &Proj,
+) -> Result<(GeoJSONRegion, GeographicExtent), GeoJSONErrorState> {
+    let (min, max) = (region.bottom_left, region.top_right);
+    let extent = reproject_bounds((min.0, min.1, max.0, max.1), proj).map_err(projection_error)?;
+    let ((west, north), (east, south)) = (extent.top_left(), extent.bottom_right());
+    Ok((
+        GeoJSONRegion {
+            top_right: (east, north),
+            bottom_left: (west, south),
         },
+        extent,
+    ))
+}
+
+// Whether nothing but whitespace remains.
+fn is_blank(reader: &mut BufReader<File>) -> Result<bool, GeoJSONErrorState> {
+    loop {
+        let buffer = reader
+            .fill_buf()
+            .map_err(|e| GeoJSONErrorState::InvalidJSON(Box::new(e)))?;
+        if buffer.is_empty() {
+            return Ok(true);
+        }
+        let whitespace = buffer
+            .iter()
+            .take_while(|b| b.is_ascii_whitespace())
+            .count();
+        if whitespace < buffer.len() {
+            return Ok(false);
+        }
+        reader.consume(whitespace);
+    }
+}
+
+// Indexes a GeoJSON file by the extent of all its positions and bbox members.
+// With feature_envelopes, the envelope of each feature is also kept for searching within the file.
+// Files declaring a CRS in a crs member are reprojected into EPSG:4326.
+pub fn parse_geojson(
+    reader: &mut BufReader<File>,
+    feature_envelopes: bool,
+) -> Result<GeoJSONMetaData, GeoJSONErrorState> {
+    if is_blank(reader)? {
+        return Err(GeoJSONErrorState::Empty);
+    }
+    let mut parser = GeoJSONParser::new(reader, feature_envelopes);
+    let root = match parser.next()? {
+        JsonEvent::StartObject => parser.read_object()?,
+        _ => return Err(invalid("the root must be an object")),
+    };
+    let bounds = root.bounds;
+    let crs = root.crs.clone();
+    match root.kind.as_deref() {
+        Some("FeatureCollection") => {}
+        Some("Feature") => parser.add_feature(root),
+        Some(kind) if GEOMETRY_TYPES.contains(&kind) => {
+            *parser.geometry_counts.entry(kind.to_string()).or_default() += 1;
+        }
+        kind => return Err(invalid(&format!("unknown root type {kind:?}"))),
+    }
+    let region = bounds.region().ok_or(GeoJSONErrorState::NoCoordinates)?;
+
+    let mut tags = vec![
+        ("Filetype".to_string(), "GEOJSON".to_string()),
+        ("FeatureCount".to_string(), parser.features.to_string()),
+    ];
+    for (geometry, count) in parser.geometry_counts.iter() {
+        tags.push((format!("{geometry}Count"), count.to_string()));
+    }
+    if !parser.properties.is_empty() {
+        let keys: Vec<&str> = parser.properties.iter().map(String::as_str).collect();
+        tags.push(("Properties".to_string(), keys.join(", ")));
+    }
+
+    let hull = match parser.wraps || parser.hull.points.is_empty() {
+        true => None,
+        false => Some(convex_hull(&parser.hull.points)),
+    };
+    let projection = crs.as_deref().map(resolve_crs).transpose()?.flatten();
+    if let Some(crs) = &crs {
+        let label = match crs::parse_code(crs) {
+            Some(code) => format!("EPSG:{code}"),
+            None => "EPSG:4326".to_string(),
+        };
+        tags.push(("CRS".to_string(), label));
+    }
+
+    let Some(proj) = projection else {
+        let (min, max) = (region.bottom_left, region.top_right);
+        if crs.is_none() && (min.0 < -180.0 || max.0 > 180.0 || min.1 < -90.0 || max.1 > 90.0) {
+            event!(
+                Level::WARN,
+                "GEOJSON coordinates {min:?} to {max:?} are beyond longitude and latitude, but no crs is declared."
+            );
+            tags.push(("CoordinatesOutOfRange".to_string(), "true".to_string()));
+        }
+        return Ok(GeoJSONMetaData {
+            region,
+            tags,
+            footprint: hull,
+            features: parser.envelopes,
+        });
+    };
+
+    let (region, extent) = reproject_region(&region, &proj)?;
+    if extent.crosses_antimeridian {
+        tags.push(("CrossesAntimeridian".to_string(), "true".to_string()));
+    }
+    let footprint = match (hull, extent.wraps()) {
+        (Some(hull), false) => {
+            Some(to_wgs84(&densify(&hull, FOOTPRINT_SAMPLES), &proj).map_err(projection_error)?)
+        }
+        _ => None,
+    };
+    let features = match parser.envelopes {
+        Some(envelopes) => Some(
+            envelopes
+                .into_iter()
+                .map(|feature| {
+                    Ok(GeoJSONFeature {
+                        region: reproject_region(&feature.region, &proj)?.0,
+                        ..feature
+                    })
+                })
+                .collect::<Result<Vec<_>, GeoJSONErrorState>>()?,
+        ),
+        None => None,
+    };
+    Ok(GeoJSONMetaData {
+        region,
         tags,
-    });
+        footprint,
+        features,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parsing::tests::tag;
     use std::io::BufReader;
     use std::io::Seek;
     use std::io::SeekFrom;
     use std::io::Write;
     use tempfile::tempfile;
 
+    fn parse(data: &[u8]) -> Result<GeoJSONMetaData, GeoJSONErrorState> {
+        let mut temp_file = tempfile().unwrap();
+        temp_file.write_all(data).unwrap();
+        temp_file.seek(SeekFrom::Start(0)).unwrap();
+        parse_geojson(&mut BufReader::new(temp_file), true)
+    }
+
     #[test]
-    fn test_get_boundaries() {
-        // Test coordinates for boundary calculation
-        let coordinates = vec![
-            [0.0, 0.0], // Bottom left corner
-            [2.0, 2.0], // Top right corner
-            [0.0, 2.0], // Top left corner
-            [2.0, 0.0], // Bottom right corner
-        ];
-        let (bottom_left, top_right) = get_boundaries(coordinates);
-        assert_eq!(bottom_left, (0.0, 0.0)); // Assert bottom left corner
-        assert_eq!(top_right, (2.0, 2.0)); // Assert top right corner
+    fn test_bounds() {
+        let mut bounds = Bounds::default();
+        assert!(bounds.region().is_none());
+        for coordinate in [(0.0, 0.0), (2.0, 2.0), (0.0, 2.0), (2.0, 0.0)] {
+            bounds.extend(coordinate);
+        }
+        let region = bounds.region().unwrap();
+        assert_eq!(region.bottom_left, (0.0, 0.0));
+        assert_eq!(region.top_right, (2.0, 2.0));
     }
 
     #[test]
@@ -167,25 +607,18 @@ mod tests {
                 "coordinates": [[[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0], [0.0, 0.0]]]
             }
         }"#;
-        let mut temp_file = tempfile().unwrap();
-        temp_file.write_all(geojson_data).unwrap();
-        temp_file.seek(SeekFrom::Start(0)).unwrap(); // Reset file pointer to the start
-
-        let mut reader = BufReader::new(temp_file);
-        let result = parse_geojson(&mut reader);
+        let result = parse(geojson_data);
         assert!(result.is_ok()); // Assert that parsing was successful
+        let metadata = result.unwrap();
+        assert_eq!(tag(&metadata.tags, "FeatureCount"), Some("1"));
+        assert_eq!(tag(&metadata.tags, "PolygonCount"), Some("1"));
+        assert_eq!(metadata.footprint.unwrap().len(), 4);
     }
 
     #[test]
     fn test_parse_geojson_empty_file() {
-        let empty_data = br#""#;
-        let mut temp_file = tempfile().unwrap();
-        temp_file.write_all(empty_data).unwrap();
-        temp_file.seek(SeekFrom::Start(0)).unwrap();
-
-        let mut reader = BufReader::new(temp_file);
-        let result = parse_geojson(&mut reader);
-        assert!(matches!(result, Err(GeoJSONErrorState::InvalidJSON(_))));
+        assert!(matches!(parse(b""), Err(GeoJSONErrorState::Empty)));
+        assert!(matches!(parse(b" \n\t"), Err(GeoJSONErrorState::Empty)));
     }
 
     #[test]
@@ -197,14 +630,220 @@ mod tests {
             // Missing "coordinates" field
         }
     }"#;
+        let result = parse(missing_coordinates_geojson_data);
+        assert!(matches!(result, Err(GeoJSONErrorState::InvalidJSON(_))));
+    }
+
+    #[test]
+    fn test_parse_geojson_no_coordinates() {
+        let result = parse(br#"{"type": "FeatureCollection", "features": []}"#);
+        assert!(matches!(result, Err(GeoJSONErrorState::NoCoordinates)));
+        let result = parse(br#"{"type": "Feature", "geometry": null, "properties": {}}"#);
+        assert!(matches!(result, Err(GeoJSONErrorState::NoCoordinates)));
+    }
+
+    #[test]
+    fn test_parse_geojson_feature_collection() {
+        let geojson_data = br#"{
+            "type": "FeatureCollection",
+            "name": {"nested": [1, 2, [3]]},
+            "features": [
+                {"type": "Feature", "id": 7, "properties": {"name": "a", "tags": {"x": [1]}},
+                 "geometry": {"type": "Point", "coordinates": [1.0, 2.0, 100.0]}},
+                {"type": "Feature", "properties": {"height": 3},
+                 "geometry": {"coordinates": [[[[5.0, 5.0], [6.0, 5.0], [6.0, 6.0], [5.0, 5.0]]],
+                                              [[[-3.0, -1.0], [-2.0, -1.0], [-2.0, 0.0], [-3.0, -1.0]]]],
+                              "type": "MultiPolygon"}},
+                {"type": "Feature", "properties": null, "geometry": {"type": "GeometryCollection",
+                 "geometries": [{"type": "Point", "coordinates": [10, 10]},
+                                {"type": "LineString", "coordinates": [[10, 10], [11, 12]]}]}},
+                {"type": "Feature", "properties": {"name": "b"}, "geometry": null},
+                {"type": "Feature", "id": "line", "geometry": {"type": "LineString", "coordinates": []}}
+            ]
+        }"#;
+        let metadata = parse(geojson_data).unwrap();
+        assert_eq!(metadata.region.bottom_left, (-3.0, -1.0));
+        assert_eq!(metadata.region.top_right, (11.0, 12.0));
+        assert_eq!(tag(&metadata.tags, "FeatureCount"), Some("5"));
+        assert_eq!(tag(&metadata.tags, "PointCount"), Some("1"));
+        assert_eq!(tag(&metadata.tags, "MultiPolygonCount"), Some("1"));
+        assert_eq!(tag(&metadata.tags, "GeometryCollectionCount"), Some("1"));
+        assert_eq!(tag(&metadata.tags, "LineStringCount"), Some("1"));
+        assert_eq!(tag(&metadata.tags, "NullGeometryCount"), Some("1"));
+        assert_eq!(
+            tag(&metadata.tags, "Properties"),
+            Some("height, name, tags")
+        );
+
+        // Features without positions have no envelope.
+        let features = metadata.features.unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!(features[0].index, 0);
+        assert_eq!(features[0].id.as_deref(), Some("7"));
+        assert_eq!(features[0].region.bottom_left, (1.0, 2.0));
+        assert_eq!(features[1].region.bottom_left, (-3.0, -1.0));
+        assert_eq!(features[1].region.top_right, (6.0, 6.0));
+        assert_eq!(features[2].index, 2);
+        assert_eq!(features[2].region.top_right, (11.0, 12.0));
+    }
+
+    #[test]
+    fn test_parse_geojson_bare_geometry() {
+        let metadata =
+            parse(br#"{"type": "MultiPoint", "coordinates": [[1, 1], [3, 4]]}"#).unwrap();
+        assert_eq!(metadata.region.bottom_left, (1.0, 1.0));
+        assert_eq!(metadata.region.top_right, (3.0, 4.0));
+        assert_eq!(tag(&metadata.tags, "FeatureCount"), Some("0"));
+        assert_eq!(tag(&metadata.tags, "MultiPointCount"), Some("1"));
+        assert!(metadata.features.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_geojson_bbox() {
+        let metadata = parse(
+            br#"{"type": "FeatureCollection", "bbox": [-10, -10, 0, 10, 10, 50], "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1, 1]}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.region.bottom_left, (-10.0, -10.0));
+        assert_eq!(metadata.region.top_right, (10.0, 10.0));
+
+        // Across the antimeridian, west is greater than east.
+        let metadata = parse(
+            br#"{"type": "Feature", "bbox": [170, -5, -170, 5],
+                "geometry": {"type": "LineString", "coordinates": [[170, -5], [190, 5]]}}"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.region.bottom_left, (-180.0, -5.0));
+        assert_eq!(metadata.region.top_right, (190.0, 5.0));
+        assert!(metadata.footprint.is_none());
+
+        let result = parse(br#"{"type": "Point", "bbox": [1, 2, 3], "coordinates": [1, 2]}"#);
+        assert!(matches!(
+            result,
+            Err(GeoJSONErrorState::InvalidStructure(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_geojson_invalid_structure() {
+        for data in [
+            &br#"[1, 2]"#[..],
+            br#"{"type": "Unknown", "coordinates": [1, 2]}"#,
+            br#"{"coordinates": [1, 2]}"#,
+            br#"{"type": "Point", "coordinates": [1]}"#,
+            br#"{"type": "LineString", "coordinates": [[1, 2], 3]}"#,
+            br#"{"type": "Point", "coordinates": "1, 2"}"#,
+            br#"{"type": "FeatureCollection", "features": [1]}"#,
+        ] {
+            let result = parse(data);
+            assert!(
+                matches!(result, Err(GeoJSONErrorState::InvalidStructure(_))),
+                "{}: {result:?}",
+                String::from_utf8_lossy(data)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_geojson_too_deep() {
+        let data = format!("{}{}", "[".repeat(100), "]".repeat(100));
+        let data = format!(r#"{{"type": "Point", "coordinates": {data}}}"#);
+        assert!(matches!(
+            parse(data.as_bytes()),
+            Err(GeoJSONErrorState::InvalidJSON(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_geojson_without_envelopes() {
         let mut temp_file = tempfile().unwrap();
         temp_file
-            .write_all(missing_coordinates_geojson_data)
+            .write_all(
+                br#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [1, 2]}}"#,
+            )
             .unwrap();
         temp_file.seek(SeekFrom::Start(0)).unwrap();
+        let metadata = parse_geojson(&mut BufReader::new(temp_file), false).unwrap();
+        assert!(metadata.features.is_none());
+    }
 
-        let mut reader = BufReader::new(temp_file);
-        let result = parse_geojson(&mut reader);
-        assert!(matches!(result, Err(GeoJSONErrorState::InvalidJSON(_))));
+    #[test]
+    fn test_parse_geojson_named_crs() {
+        // Around London, in British National Grid.
+        let metadata = parse(
+            br#"{"type": "FeatureCollection",
+                "features": [{"type": "Feature", "id": "a", "properties": {},
+                    "geometry": {"type": "Polygon", "coordinates": [[[529000, 179000], [531000, 179000],
+                        [531000, 181000], [529000, 181000], [529000, 179000]]]}}],
+                "crs": {"type": "name", "properties": {"name": "urn:ogc:def:crs:EPSG::27700"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(tag(&metadata.tags, "CRS"), Some("EPSG:27700"));
+        assert!(tag(&metadata.tags, "CoordinatesOutOfRange").is_none());
+        let (west, south) = metadata.region.bottom_left;
+        let (east, north) = metadata.region.top_right;
+        assert!((-0.2..-0.1).contains(&west) && (-0.15..0.0).contains(&east));
+        assert!((51.48..51.5).contains(&south) && (51.5..51.52).contains(&north));
+        assert!(metadata
+            .footprint
+            .unwrap()
+            .iter()
+            .all(|(x, y)| (-0.2..0.0).contains(x) && (51.4..51.6).contains(y)));
+        let feature = &metadata.features.unwrap()[0];
+        assert_eq!(feature.region.bottom_left, metadata.region.bottom_left);
+        assert_eq!(feature.region.top_right, metadata.region.top_right);
+    }
+
+    #[test]
+    fn test_parse_geojson_legacy_epsg_crs() {
+        let metadata = parse(
+            br#"{"type": "Point", "coordinates": [1113194.9, 1118890.0],
+                "crs": {"type": "EPSG", "properties": {"code": 3857}}}"#,
+        )
+        .unwrap();
+        assert_eq!(tag(&metadata.tags, "CRS"), Some("EPSG:3857"));
+        let (x, y) = metadata.region.bottom_left;
+        assert!(
+            (x - 10.0).abs() < 1e-3 && (y - 10.0).abs() < 1e-3,
+            "{x}, {y}"
+        );
+    }
+
+    #[test]
+    fn test_parse_geojson_crs84() {
+        let metadata = parse(
+            br#"{"type": "Point", "coordinates": [10, 20],
+                "crs": {"type": "name", "properties": {"name": "urn:ogc:def:crs:OGC:1.3:CRS84"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(tag(&metadata.tags, "CRS"), Some("EPSG:4326"));
+        assert_eq!(metadata.region.bottom_left, (10.0, 20.0));
+    }
+
+    #[test]
+    fn test_parse_geojson_unsupported_crs() {
+        let result = parse(
+            br#"{"type": "Point", "coordinates": [10, 20],
+                "crs": {"type": "name", "properties": {"name": "urn:ogc:def:crs:ESRI::102100"}}}"#,
+        );
+        assert!(matches!(result, Err(GeoJSONErrorState::ProjectionError(_))));
+        let result = parse(
+            br#"{"type": "Point", "coordinates": [10, 20],
+                "crs": {"type": "link", "properties": {"href": "http://example.com/crs/42"}}}"#,
+        );
+        assert!(matches!(
+            result,
+            Err(GeoJSONErrorState::InvalidStructure(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_geojson_out_of_range_without_crs() {
+        let metadata = parse(br#"{"type": "Point", "coordinates": [530000, 180000]}"#).unwrap();
+        assert_eq!(tag(&metadata.tags, "CoordinatesOutOfRange"), Some("true"));
+        assert!(tag(&metadata.tags, "CRS").is_none());
+        let metadata = parse(br#"{"type": "Point", "coordinates": [-0.1, 51.5]}"#).unwrap();
+        assert!(tag(&metadata.tags, "CoordinatesOutOfRange").is_none());
     }
 }
@@ -0,0 +1,213 @@
+use crate::ifd::{resolve_integers, ImageFileDirectory, SubfileKind};
+use crate::util::{ByteOrder, TIFFVariant};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+const GHOST_AREA_PREFIX: &str = "GDAL_STRUCTURAL_METADATA_SIZE=";
+// Prefix, then a 6 digit size, then " bytes\n".
+const GHOST_AREA_HEADER_LENGTH: usize = 43;
+
+// Images no larger than a single 512 pixel tile don't need overviews.
+const OVERVIEW_THRESHOLD: u64 = 512;
+
+// Checks the layout of a TIFF against the Cloud Optimized GeoTIFF requirements.
+// Returns the reasons it is not a COG, so an empty list means it is one.
+pub fn validate_cog(
+    directories: &mut [ImageFileDirectory],
+    primary: usize,
+    byte_order: &ByteOrder,
+    variant: &TIFFVariant,
+    reader: &mut BufReader<File>,
+) -> Vec<String> {
+    let mut reasons = Vec::new();
+
+    // Every image, including overviews and masks, should be tiled.
+    for (index, directory) in directories.iter().enumerate() {
+        if ![322, 323, 324]
+            .iter()
+            .all(|tag| directory.entries.contains_key(tag))
+        {
+            reasons.push(format!(
+                "IFD {index} ({}) is not tiled",
+                directory.dimensions()
+            ));
+        }
+    }
+
+    let main = &directories[primary];
+    let has_overviews = directories
+        .iter()
+        .any(|d| d.kind == SubfileKind::ReducedResolution);
+    if !has_overviews && (main.width > OVERVIEW_THRESHOLD || main.height > OVERVIEW_THRESHOLD) {
+        reasons.push(format!("No overviews for a {} image", main.dimensions()));
+    }
+
+    // All IFDs should be read with the first request, so must precede any image data.
+    let last_ifd = directories.iter().map(|d| d.offset).max().unwrap_or(0);
+    match first_data_offset(directories, byte_order, reader) {
+        Some(first_data) if first_data < last_ifd => {
+            reasons.push(format!(
+                "IFD at offset {last_ifd} follows image data at offset {first_data}"
+            ));
+        }
+        Some(_) => {}
+        None => reasons.push("Could not read image data offsets".to_string()),
+    }
+
+    match read_ghost_area(variant, reader) {
+        None => reasons.push("Missing ghost area header".to_string()),
+        Some(ghost) if ghost.contains("KNOWN_INCOMPATIBLE_EDITION=YES") => {
+            reasons.push("Modified since being written as a COG".to_string())
+        }
+        Some(_) => {}
+    }
+
+    reasons
+}
+
+// Smallest offset of any tile or strip, ignoring the zero offsets of sparse blocks.
+fn first_data_offset(
+    directories: &mut [ImageFileDirectory],
+    byte_order: &ByteOrder,
+    reader: &mut BufReader<File>,
+) -> Option<u64> {
+    let mut first: Option<u64> = None;
+    for directory in directories.iter_mut() {
+        // TileOffsets, falling back to StripOffsets.
+        let offsets = match resolve_integers(&mut directory.entries, 324, byte_order, reader) {
+            Ok(Some(offsets)) => offsets,
+            Ok(None) => resolve_integers(&mut directory.entries, 273, byte_order, reader)
+                .ok()?
+                .unwrap_or_default(),
+            Err(_) => return None,
+        };
+        if let Some(min) = offsets.into_iter().filter(|o| *o != 0).min() {
+            first = Some(first.map_or(min, |f| f.min(min)));
+        }
+    }
+    // No image data at all can't be out of order.
+    Some(first.unwrap_or(u64::MAX))
+}
+
+// GDAL writes structural metadata directly after the header, describing the COG layout.
+fn read_ghost_area(variant: &TIFFVariant, reader: &mut BufReader<File>) -> Option<String> {
+    let start = match variant {
+        TIFFVariant::Classic => 8,
+        TIFFVariant::BigTIFF => 16,
+    };
+    reader.seek(SeekFrom::Start(start)).ok()?;
+    let mut header = [0u8; GHOST_AREA_HEADER_LENGTH];
+    reader.read_exact(&mut header).ok()?;
+    let header = std::str::from_utf8(&header).ok()?;
+    let size: u64 = header
+        .strip_prefix(GHOST_AREA_PREFIX)?
+        .strip_suffix(" bytes\n")?
+        .parse()
+        .ok()?;
+
+    let mut content = String::new();
+    reader.take(size).read_to_string(&mut content).ok()?;
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ifd::read_directories;
+    use crate::tests::{ifd_offset, longs, mock_tiff_ifds_with_ghost, shorts, MockEntry};
+
+    const GHOST: &str = "GDAL_STRUCTURAL_METADATA_SIZE=000140 bytes\nLAYOUT=IFDS_BEFORE_DATA\nBLOCK_ORDER=ROW_MAJOR\nBLOCK_LEADER=SIZE_AS_UINT4\nBLOCK_TRAILER=LAST_4_BYTES_REPEATED\nKNOWN_INCOMPATIBLE_EDITION=NO\n";
+
+    fn tiled(width: u16, height: u16, new_subfile_type: u32, data_offset: u32) -> Vec<MockEntry> {
+        vec![
+            (254, 4, 1, longs(&[new_subfile_type])),
+            (256, 3, 1, shorts(&[width])),
+            (257, 3, 1, shorts(&[height])),
+            (322, 3, 1, shorts(&[256])),
+            (323, 3, 1, shorts(&[256])),
+            (324, 4, 1, longs(&[data_offset])),
+        ]
+    }
+
+    fn validate(ghost: &str, ifds: &[Vec<MockEntry>]) -> Vec<String> {
+        let mut reader = mock_tiff_ifds_with_ghost(ghost, ifds, ifds.len());
+        let mut directories = read_directories(
+            &mut reader,
+            ifd_offset(0) as u64,
+            &ByteOrder::LittleEndian,
+            &TIFFVariant::Classic,
+        )
+        .unwrap();
+        validate_cog(
+            &mut directories,
+            0,
+            &ByteOrder::LittleEndian,
+            &TIFFVariant::Classic,
+            &mut reader,
+        )
+    }
+
+    #[test]
+    fn test_valid_cog() {
+        let reasons = validate(
+            GHOST,
+            &[tiled(1024, 1024, 0, 20000), tiled(512, 512, 1, 10000)],
+        );
+        assert!(reasons.is_empty(), "{reasons:?}");
+    }
+
+    #[test]
+    fn test_small_image_without_overviews() {
+        let reasons = validate(GHOST, &[tiled(256, 256, 0, 20000)]);
+        assert!(reasons.is_empty(), "{reasons:?}");
+    }
+
+    #[test]
+    fn test_stripped_without_overviews_or_ghost() {
+        let reasons = validate(
+            "",
+            &[vec![
+                (256, 3, 1, shorts(&[1024])),
+                (257, 3, 1, shorts(&[1024])),
+                (273, 4, 1, longs(&[20000])),
+            ]],
+        );
+        assert_eq!(
+            reasons,
+            vec![
+                "IFD 0 (1024x1024) is not tiled",
+                "No overviews for a 1024x1024 image",
+                "Missing ghost area header",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ifd_after_image_data() {
+        // The overview's IFD comes after the main image's tiles.
+        let reasons = validate(
+            GHOST,
+            &[tiled(1024, 1024, 0, 1000), tiled(512, 512, 1, 20000)],
+        );
+        assert_eq!(
+            reasons,
+            vec![format!(
+                "IFD at offset {} follows image data at offset 1000",
+                ifd_offset(1)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_modified_cog() {
+        let ghost = GHOST.replace(
+            "KNOWN_INCOMPATIBLE_EDITION=NO",
+            "KNOWN_INCOMPATIBLE_EDITION=YES",
+        );
+        let reasons = validate(
+            &ghost,
+            &[tiled(1024, 1024, 0, 20000), tiled(512, 512, 1, 10000)],
+        );
+        assert_eq!(reasons, vec!["Modified since being written as a COG"]);
+    }
+}
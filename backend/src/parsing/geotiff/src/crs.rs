@@ -0,0 +1,16 @@
+use proj4rs::Proj;
+
+// EPSG code and WKT resolution, supplied by the caller so GeoTIFFs share CRS definitions with
+// every other format rather than this crate holding its own.
+pub trait CRSDefinitions {
+    // Proj string for an EPSG code, if known.
+    fn proj_string(&self, code: u32) -> Option<String>;
+
+    // As proj_string, for a code the file depends on, so an unknown one may be reported.
+    fn lookup(&self, code: u32) -> Option<String> {
+        self.proj_string(code)
+    }
+
+    // Projection for the WKT of a .prj sidecar, along with a label for the CRS tag.
+    fn projection_from_wkt(&self, wkt: &str) -> Result<(Proj, String), String>;
+}
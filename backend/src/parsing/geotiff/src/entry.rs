@@ -14,6 +14,21 @@ pub enum EntryType {
     RATIONAL,
     UNDEFINED,
     DOUBLE,
+    IFD,
+    LONG8,
+    IFD8,
+}
+
+impl EntryType {
+    // Size in bytes of a single value of this type.
+    fn size(&self) -> u64 {
+        match self {
+            EntryType::BYTES | EntryType::ASCII | EntryType::UNDEFINED => 1,
+            EntryType::SHORT => 2,
+            EntryType::LONG | EntryType::IFD => 4,
+            EntryType::RATIONAL | EntryType::DOUBLE | EntryType::LONG8 | EntryType::IFD8 => 8,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,25 +40,33 @@ pub enum EntryValue {
     RATIONAL(Vec<(u32, u32)>),
     UNDEFINED(Vec<u8>),
     DOUBLE(Vec<f64>),
+    IFD(Vec<u32>),
+    LONG8(Vec<u64>),
+    IFD8(Vec<u64>),
 }
 
 #[derive(Debug, Clone)]
 pub struct IFDEntry {
     pub(crate) tag: u16,
-    count: u32,
+    count: u64,
     field_type: EntryType,
-    associated_bytes: [u8; 4],
+    // The value, or offset to it; 4 bytes in classic TIFF, 8 in BigTIFF.
+    associated_bytes: Vec<u8>,
     value: Option<EntryValue>,
 }
 
 impl IFDEntry {
     pub fn new(entry_buf: &[u8], byte_order: &ByteOrder) -> Result<IFDEntry, TIFFErrorState> {
-        if entry_buf.len() != 12 {
-            // Ensure expected entry buffer length (Entries are 12 bytes)
-            return Err(TIFFErrorState::IFDEntryError(
-                IFDEntryErrorState::InvalidLength(entry_buf.len()),
-            ));
-        }
+        // Classic entries are 12 bytes, BigTIFF entries are 20 bytes.
+        let (count_range, value_range) = match entry_buf.len() {
+            12 => (4..8, 8..12),
+            20 => (4..12, 12..20),
+            _ => {
+                return Err(TIFFErrorState::IFDEntryError(
+                    IFDEntryErrorState::InvalidLength(entry_buf.len()),
+                ))
+            }
+        };
 
         let tag = u16::from_bytes(&entry_buf[0..2], &byte_order); // Get tag ID
 
@@ -57,6 +80,9 @@ impl IFDEntry {
             5 => EntryType::RATIONAL,
             7 => EntryType::UNDEFINED,
             12 => EntryType::DOUBLE,
+            13 => EntryType::IFD,
+            16 => EntryType::LONG8,
+            18 => EntryType::IFD8,
             _ => {
                 return Err(TIFFErrorState::IFDEntryError(
                     IFDEntryErrorState::UnexpectedEntryType(field_type),
@@ -65,15 +91,66 @@ impl IFDEntry {
         };
 
         // Get count of values
-        let count = u32::from_bytes(&entry_buf[4..8], &byte_order);
-        let associated_bytes: [u8; 4] = [entry_buf[8], entry_buf[9], entry_buf[10], entry_buf[11]];
-        return Ok(IFDEntry {
+        let count = if count_range.len() == 4 {
+            u32::from_bytes(&entry_buf[count_range], &byte_order) as u64
+        } else {
+            u64::from_bytes(&entry_buf[count_range], &byte_order)
+        };
+        let associated_bytes = entry_buf[value_range].to_vec();
+        Ok(IFDEntry {
             tag,
             count,
             field_type,
             associated_bytes,
             value: None,
-        });
+        })
+    }
+
+    // Reads the raw bytes of the value, either held inline or at the associated offset.
+    fn read_bytes(
+        &self,
+        byte_order: &ByteOrder,
+        reader: &mut BufReader<File>,
+    ) -> Result<Vec<u8>, TIFFErrorState> {
+        let missing =
+            || TIFFErrorState::IFDEntryError(IFDEntryErrorState::MissingAssociatedValue(self.tag));
+        let Some(length) = self.count.checked_mul(self.field_type.size()) else {
+            eprintln!("Value length overflows for tag: {}", self.tag);
+            return Err(missing());
+        };
+        if length <= self.associated_bytes.len() as u64 {
+            return Ok(self.associated_bytes[..length as usize].to_vec());
+        }
+
+        let offset = if self.associated_bytes.len() == 4 {
+            u32::from_bytes(&self.associated_bytes, byte_order) as u64
+        } else {
+            u64::from_bytes(&self.associated_bytes, byte_order)
+        };
+        if let Err(e) = reader.seek(SeekFrom::Start(offset)) {
+            eprintln!(
+                "Failed to seek to associated bytes for tag: {}, Error: {:?}",
+                self.tag, e
+            );
+            return Err(missing());
+        }
+
+        // Read through take, so a corrupt count can't allocate beyond the end of the file.
+        let mut bytes = Vec::new();
+        match reader.by_ref().take(length).read_to_end(&mut bytes) {
+            Ok(read) if read as u64 == length => Ok(bytes),
+            Ok(read) => {
+                eprintln!(
+                    "Failed to read bytes for tag: {}, expected {} bytes, got {}",
+                    self.tag, length, read
+                );
+                Err(missing())
+            }
+            Err(e) => {
+                eprintln!("Failed to read bytes for tag: {}, Error: {:?}", self.tag, e);
+                Err(missing())
+            }
+        }
     }
 
     pub fn resolve(
@@ -82,317 +159,73 @@ impl IFDEntry {
         reader: &mut BufReader<File>,
     ) -> Result<&EntryValue, TIFFErrorState> {
         if self.value.is_none() {
+            let bytes = self.read_bytes(byte_order, reader)?;
             let value: EntryValue = match &self.field_type {
-                EntryType::BYTES => EntryValue::BYTES(if self.count < 5 {
-                    Vec::from(self.associated_bytes)
-                } else {
-                    let offset = SeekFrom::Start(u32::from_bytes(
-                        &self.associated_bytes,
-                        &byte_order,
-                    ) as u64);
-                    match reader.seek(offset) {
-                        Ok(_) => {
-                            let mut values = vec![0u8; self.count as usize];
-                            match reader.read_exact(&mut values) {
-                                Ok(..) => values,
-                                Err(e) => {
-                                    eprintln!(
-                                        "Failed to read bytes for tag: {}, Error: {:?}",
-                                        self.tag, e
-                                    );
-                                    return Err(TIFFErrorState::IFDEntryError(
-                                        IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                                    ));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!(
-                                "Failed to seek to associated bytes for tag: {}, Error: {:?}",
-                                self.tag, e
-                            );
-                            return Err(TIFFErrorState::IFDEntryError(
-                                IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                            ));
-                        }
-                    }
-                }),
-                EntryType::ASCII => EntryValue::ASCII(if self.count < 5 {
-                    match String::from_utf8(self.associated_bytes.to_vec()) {
-                        Ok(s) => vec![s.trim_end_matches("\0").to_string()],
-                        Err(e) => {
-                            eprintln!(
-                                "Failed to parse string from bytes for tag: {}, Error: {:?}",
-                                self.tag, e
-                            );
-                            return Err(TIFFErrorState::IFDEntryError(
-                                IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                            ));
-                        }
-                    }
-                } else {
-                    let offset = SeekFrom::Start(u32::from_bytes(
-                        &self.associated_bytes,
-                        &byte_order,
-                    ) as u64);
-
-                    match reader.seek(offset) {
-                        Ok(_) => {
-                            let mut bytes = vec![0u8; self.count as usize];
-
-                            match reader.read_exact(&mut bytes) {
-                                Ok(..) => match String::from_utf8(bytes) {
-                                    Ok(s) => vec![s.trim_end_matches("\0").to_string()],
-                                    Err(e) => {
-                                        eprintln!("Failed to parse string from bytes for tag: {}, Error: {:?}", self.tag, e);
-                                        return Err(TIFFErrorState::IFDEntryError(
-                                            IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                                        ));
-                                    }
-                                },
-                                Err(e) => {
-                                    eprintln!(
-                                        "Failed to read bytes for tag: {}, Error: {:?}",
-                                        self.tag, e
-                                    );
-                                    return Err(TIFFErrorState::IFDEntryError(
-                                        IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                                    ));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!(
-                                "Failed to seek to associated bytes for tag: {}, Error: {:?}",
-                                self.tag, e
-                            );
-                            return Err(TIFFErrorState::IFDEntryError(
-                                IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                            ));
-                        }
-                    }
-                }),
-                EntryType::SHORT => {
-                    let mut values: Vec<u16> = Vec::with_capacity(self.count as usize);
-
-                    if self.count < 3 {
-                        for cursor in (0..self.count * 2).step_by(2) {
-                            values.push(u16::from_bytes(
-                                &self.associated_bytes[(cursor as usize)..(cursor as usize) + 2],
-                                &byte_order,
-                            ));
-                        }
-                        EntryValue::SHORT(values)
-                    } else {
-                        let offset = SeekFrom::Start(u32::from_bytes(
-                            &self.associated_bytes,
-                            &byte_order,
-                        ) as u64);
-
-                        match reader.seek(offset) {
-                            Ok(_) => {
-                                for _ in 0..self.count {
-                                    let mut value_buf = [0u8, 0u8];
-                                    match reader.read_exact(&mut value_buf) {
-                                        Ok(..) => {
-                                            values.push(u16::from_bytes(&value_buf, &byte_order))
-                                        }
-                                        Err(e) => {
-                                            eprintln!(
-                                                "Failed to read bytes for tag: {}, Error: {:?}",
-                                                self.tag, e
-                                            );
-                                            return Err(TIFFErrorState::IFDEntryError(
-                                                IFDEntryErrorState::MissingAssociatedValue(
-                                                    self.tag,
-                                                ),
-                                            ));
-                                        }
-                                    }
-                                }
-                                EntryValue::SHORT(values)
-                            }
-                            Err(e) => {
-                                eprintln!(
-                                    "Failed to seek to associated bytes for tag: {}, Error: {:?}",
-                                    self.tag, e
-                                );
-                                return Err(TIFFErrorState::IFDEntryError(
-                                    IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                                ));
-                            }
-                        }
-                    }
-                }
-                EntryType::LONG => {
-                    let mut values: Vec<u32> = Vec::with_capacity((self.count) as usize);
-
-                    if self.count == 1 {
-                        values.push(u32::from_bytes(&self.associated_bytes, &byte_order));
-                        EntryValue::LONG(values)
-                    } else {
-                        let offset = SeekFrom::Start(u32::from_bytes(
-                            &self.associated_bytes,
-                            &byte_order,
-                        ) as u64);
-
-                        match reader.seek(offset) {
-                            Ok(_) => {
-                                for _ in 0..self.count {
-                                    let mut value_buf = [0u8; 4];
-                                    match reader.read_exact(&mut value_buf) {
-                                        Ok(..) => {
-                                            values.push(u32::from_bytes(&value_buf, byte_order));
-                                        }
-                                        Err(e) => {
-                                            eprintln!(
-                                                "Failed to read bytes for tag: {}, Error: {:?}",
-                                                self.tag, e
-                                            );
-                                            return Err(TIFFErrorState::IFDEntryError(
-                                                IFDEntryErrorState::MissingAssociatedValue(
-                                                    self.tag,
-                                                ),
-                                            ));
-                                        }
-                                    }
-                                }
-                                EntryValue::LONG(values)
-                            }
-                            Err(e) => {
-                                eprintln!(
-                                    "Failed to seek to associated bytes for tag: {}, Error: {:?}",
-                                    self.tag, e
-                                );
-                                return Err(TIFFErrorState::IFDEntryError(
-                                    IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                                ));
-                            }
-                        }
+                EntryType::BYTES => EntryValue::BYTES(bytes),
+                EntryType::UNDEFINED => EntryValue::UNDEFINED(bytes),
+                EntryType::ASCII => match String::from_utf8(bytes) {
+                    Ok(s) => EntryValue::ASCII(vec![s.trim_end_matches('\0').to_string()]),
+                    Err(e) => {
+                        eprintln!(
+                            "Failed to parse string from bytes for tag: {}, Error: {:?}",
+                            self.tag, e
+                        );
+                        return Err(TIFFErrorState::IFDEntryError(
+                            IFDEntryErrorState::MissingAssociatedValue(self.tag),
+                        ));
                     }
-                }
-                EntryType::RATIONAL => {
-                    let offset = SeekFrom::Start(u32::from_bytes(
-                        &self.associated_bytes,
-                        &byte_order,
-                    ) as u64);
-                    match reader.seek(offset) {
-                        Ok(_) => {
-                            let mut values: Vec<(u32, u32)> =
-                                Vec::with_capacity(self.count as usize);
-                            for _ in 0..self.count {
-                                let mut value_buf = [0u8; 8];
-                                match reader.read_exact(&mut value_buf) {
-                                    Ok(..) => {
-                                        values.push((
-                                            u32::from_bytes(&value_buf[0..4], &byte_order),
-                                            u32::from_bytes(&value_buf[4..8], &byte_order),
-                                        ));
-                                    }
-                                    Err(e) => {
-                                        eprintln!(
-                                            "Failed to read bytes for tag: {}, Error: {:?}",
-                                            self.tag, e
-                                        );
-                                        return Err(TIFFErrorState::IFDEntryError(
-                                            IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                                        ));
-                                    }
-                                }
-                            }
-                            EntryValue::RATIONAL(values)
-                        }
-                        Err(e) => {
-                            eprintln!(
-                                "Failed to seek to associated bytes for tag: {}, Error: {:?}",
-                                self.tag, e
-                            );
-                            return Err(TIFFErrorState::IFDEntryError(
-                                IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                            ));
-                        }
-                    }
-                }
-                EntryType::DOUBLE => {
-                    let mut values: Vec<f64> = Vec::with_capacity(self.count as usize);
-                    let offset = SeekFrom::Start(u32::from_bytes(
-                        &self.associated_bytes,
-                        &byte_order,
-                    ) as u64);
-
-                    match reader.seek(offset) {
-                        Ok(_) => {
-                            for _ in 0..self.count {
-                                let mut value_buf = [0u8; 8];
-                                match reader.read_exact(&mut value_buf) {
-                                    Ok(..) => {
-                                        values.push(f64::from_bytes(&value_buf, &byte_order));
-                                    }
-                                    Err(e) => {
-                                        eprintln!(
-                                            "Failed to read bytes for tag: {}, Error: {:?}",
-                                            self.tag, e
-                                        );
-                                        return Err(TIFFErrorState::IFDEntryError(
-                                            IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                                        ));
-                                    }
-                                }
-                            }
-                            EntryValue::DOUBLE(values)
-                        }
-                        Err(e) => {
-                            eprintln!(
-                                "Failed to seek to associated bytes for tag: {}, Error: {:?}",
-                                self.tag, e
-                            );
-                            return Err(TIFFErrorState::IFDEntryError(
-                                IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                            ));
-                        }
-                    }
-                }
-                EntryType::UNDEFINED => EntryValue::UNDEFINED(if self.count < 5 {
-                    Vec::from(self.associated_bytes)
-                } else {
-                    let offset = SeekFrom::Start(u32::from_bytes(
-                        &self.associated_bytes,
-                        &byte_order,
-                    ) as u64);
-                    match reader.seek(offset) {
-                        Ok(_) => {
-                            let mut bytes = vec![0u8; self.count as usize];
-                            match reader.read_exact(&mut bytes) {
-                                Ok(..) => bytes,
-                                Err(e) => {
-                                    eprintln!(
-                                        "Failed to read bytes for tag: {}, Error: {:?}",
-                                        self.tag, e
-                                    );
-                                    return Err(TIFFErrorState::IFDEntryError(
-                                        IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                                    ));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!(
-                                "Failed to seek to associated bytes for tag: {}, Error: {:?}",
-                                self.tag, e
-                            );
-                            return Err(TIFFErrorState::IFDEntryError(
-                                IFDEntryErrorState::MissingAssociatedValue(self.tag),
-                            ));
-                        }
-                    }
-                }),
+                },
+                EntryType::SHORT => EntryValue::SHORT(
+                    bytes
+                        .chunks_exact(2)
+                        .map(|b| u16::from_bytes(b, byte_order))
+                        .collect(),
+                ),
+                EntryType::LONG => EntryValue::LONG(
+                    bytes
+                        .chunks_exact(4)
+                        .map(|b| u32::from_bytes(b, byte_order))
+                        .collect(),
+                ),
+                EntryType::RATIONAL => EntryValue::RATIONAL(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|b| {
+                            (
+                                u32::from_bytes(&b[0..4], byte_order),
+                                u32::from_bytes(&b[4..8], byte_order),
+                            )
+                        })
+                        .collect(),
+                ),
+                EntryType::DOUBLE => EntryValue::DOUBLE(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|b| f64::from_bytes(b, byte_order))
+                        .collect(),
+                ),
+                EntryType::IFD => EntryValue::IFD(
+                    bytes
+                        .chunks_exact(4)
+                        .map(|b| u32::from_bytes(b, byte_order))
+                        .collect(),
+                ),
+                EntryType::LONG8 => EntryValue::LONG8(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|b| u64::from_bytes(b, byte_order))
+                        .collect(),
+                ),
+                EntryType::IFD8 => EntryValue::IFD8(
+                    bytes
+                        .chunks_exact(8)
+                        .map(|b| u64::from_bytes(b, byte_order))
+                        .collect(),
+                ),
             };
-
             self.value = Some(value);
-            return Ok(self.value.as_ref().unwrap());
-        } else {
-            return Ok(self.value.as_ref().unwrap());
         }
+        Ok(self.value.as_ref().unwrap())
     }
 }
 
@@ -432,7 +265,7 @@ mod tests {
             tag: 0,
             count: 4,
             field_type: EntryType::BYTES,
-            associated_bytes: [0, 1, 2, 3],
+            associated_bytes: vec![0, 1, 2, 3],
             value: None,
         };
 
@@ -452,7 +285,7 @@ mod tests {
             tag: 0,
             count: 6,
             field_type: EntryType::ASCII,
-            associated_bytes: [0; 4], // Assume offset is at the start of the file
+            associated_bytes: vec![0; 4], // Assume offset is at the start of the file
             value: None,
         };
 
@@ -471,7 +304,7 @@ mod tests {
             tag: 0,
             count: 2,
             field_type: EntryType::SHORT,
-            associated_bytes: [1, 0, 2, 0], // Two SHORT values: 1, 2
+            associated_bytes: vec![1, 0, 2, 0], // Two SHORT values: 1, 2
             value: None,
         };
 
@@ -495,9 +328,9 @@ mod tests {
 
         let mut entry = IFDEntry {
             tag: 0,
-            count: text.len() as u32,
+            count: text.len() as u64,
             field_type: EntryType::ASCII,
-            associated_bytes: [0, 0, 0, 0], // Offset to the ASCII value
+            associated_bytes: vec![0, 0, 0, 0], // Offset to the ASCII value
             value: None,
         };
 
@@ -520,7 +353,7 @@ mod tests {
             tag: 0,
             count: 2,
             field_type: EntryType::LONG,
-            associated_bytes: [0, 0, 0, 0],
+            associated_bytes: vec![0, 0, 0, 0],
             value: None,
         };
 
@@ -541,7 +374,7 @@ mod tests {
             tag: 0,
             count: 2,
             field_type: EntryType::RATIONAL,
-            associated_bytes: [0, 0, 0, 0],
+            associated_bytes: vec![0, 0, 0, 0],
             value: None,
         };
 
@@ -570,7 +403,7 @@ mod tests {
             tag: 0,
             count: 0, // ASCII string length is 0
             field_type: EntryType::ASCII,
-            associated_bytes: [0; 4],
+            associated_bytes: vec![0; 4],
             value: None,
         };
 
@@ -581,4 +414,87 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), &EntryValue::ASCII(vec!["".to_string()]));
     }
+
+    #[test]
+    fn test_bigtiff_entry_new() {
+        // LONG8 ImageWidth, with the value held inline in the 8 byte value field.
+        let mut entry_buf = vec![0, 1, 16, 0];
+        entry_buf.extend_from_slice(&1u64.to_le_bytes());
+        entry_buf.extend_from_slice(&5_000_000_000u64.to_le_bytes());
+
+        let mut entry = IFDEntry::new(&entry_buf, &ByteOrder::LittleEndian).unwrap();
+        assert_eq!(entry.tag, 256);
+        assert_eq!(entry.field_type, EntryType::LONG8);
+        assert_eq!(entry.count, 1);
+
+        let file = tempfile().unwrap();
+        let mut reader = BufReader::new(file);
+        assert_eq!(
+            entry
+                .resolve(&ByteOrder::LittleEndian, &mut reader)
+                .unwrap(),
+            &EntryValue::LONG8(vec![5_000_000_000])
+        );
+    }
+
+    #[test]
+    fn test_resolve_double_inline_bigtiff() {
+        // A single DOUBLE fits within a BigTIFF value field.
+        let mut entry = IFDEntry {
+            tag: 0,
+            count: 1,
+            field_type: EntryType::DOUBLE,
+            associated_bytes: 2.5f64.to_le_bytes().to_vec(),
+            value: None,
+        };
+
+        let file = tempfile().unwrap();
+        let mut reader = BufReader::new(file);
+        let result = entry.resolve(&ByteOrder::LittleEndian, &mut reader);
+        assert_eq!(result.unwrap(), &EntryValue::DOUBLE(vec![2.5]));
+    }
+
+    #[test]
+    fn test_resolve_ifd8_offset() {
+        let mut file = tempfile().unwrap();
+        file.write_all(&[0; 4]).unwrap();
+        file.write_all(&16u64.to_le_bytes()).unwrap();
+        file.write_all(&32u64.to_le_bytes()).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut reader = BufReader::new(file);
+
+        let mut entry = IFDEntry {
+            tag: 330,
+            count: 2,
+            field_type: EntryType::IFD8,
+            associated_bytes: 4u64.to_le_bytes().to_vec(),
+            value: None,
+        };
+
+        let result = entry.resolve(&ByteOrder::LittleEndian, &mut reader);
+        assert_eq!(result.unwrap(), &EntryValue::IFD8(vec![16, 32]));
+    }
+
+    #[test]
+    fn test_resolve_count_past_end_of_file() {
+        let mut file = tempfile().unwrap();
+        file.write_all(&[1, 0, 2, 0]).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut reader = BufReader::new(file);
+
+        let mut entry = IFDEntry {
+            tag: 7,
+            count: u32::MAX as u64,
+            field_type: EntryType::SHORT,
+            associated_bytes: vec![0; 4],
+            value: None,
+        };
+
+        assert!(matches!(
+            entry.resolve(&ByteOrder::LittleEndian, &mut reader),
+            Err(TIFFErrorState::IFDEntryError(
+                IFDEntryErrorState::MissingAssociatedValue(7)
+            ))
+        ));
+    }
 }
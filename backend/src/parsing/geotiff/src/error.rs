@@ -53,6 +53,7 @@ pub enum HeaderErrorState {
     UnexpectedByteOrder([u8; 2]),
     UnexpectedMagicNumber([u8; 2]),
     InvalidLength(usize),
+    UnexpectedOffsetSize(u16),
 }
 
 impl Display for HeaderErrorState {
@@ -65,6 +66,8 @@ impl Display for HeaderErrorState {
                 HeaderErrorState::UnexpectedMagicNumber(e) =>
                     format!("UnexpectedMagicNumber: {e:?}"),
                 HeaderErrorState::InvalidLength(e) => format!("Invalid Header Buffer Length: {e}"),
+                HeaderErrorState::UnexpectedOffsetSize(e) =>
+                    format!("Unexpected BigTIFF Offset Size: {e}"),
             }
         )
     }
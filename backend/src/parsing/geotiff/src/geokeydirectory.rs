@@ -1,3 +1,4 @@
+use crate::crs::CRSDefinitions;
 use crate::error::GeoKeyDirectoryErrorState::UnexpectedFormat;
 use crate::error::TIFFErrorState;
 use proj4rs::Proj;
@@ -31,12 +32,22 @@ impl GeoKeyDirectoryHeader {
     }
 }
 
+// GeoDoubleParamsTag and GeoAsciiParamsTag, key locations for non-short values.
+const GEO_DOUBLE_PARAMS: u16 = 34736;
+const GEO_ASCII_PARAMS: u16 = 34737;
+
+// Code marking a key as user-defined, with the definition given by other keys.
+const USER_DEFINED: u16 = 32767;
+
 #[derive(Debug)]
 pub struct GeoKey {
     pub id: u16,
     pub location: u16,
     pub count: u16,
+    // The value when held inline (location 0).
     pub value: Option<u16>,
+    // Index into the params tag named by location, when not inline.
+    pub value_offset: u16,
 }
 
 impl GeoKey {
@@ -56,6 +67,7 @@ impl GeoKey {
             location,
             count,
             value,
+            value_offset: shorts[3],
         });
     }
 }
@@ -64,6 +76,9 @@ impl GeoKey {
 pub struct GeoKeyDirectory {
     pub header: GeoKeyDirectoryHeader,
     pub keys: HashMap<u16, GeoKey>,
+    // Contents of GeoDoubleParamsTag and GeoAsciiParamsTag, which non-short keys index into.
+    pub doubles: Vec<f64>,
+    pub ascii: String,
 }
 
 impl GeoKeyDirectory {
@@ -97,62 +112,311 @@ impl GeoKeyDirectory {
             map.insert(key.id, key);
         }
 
-        return Ok(GeoKeyDirectory { header, keys: map });
-    }
-
-    pub fn get_projection(&self) -> Result<Proj, TIFFErrorState> {
-        let crs_code = if let Some(v) = self.keys.get(&2048) {
-            if v.location == 0 {
-                match v.value {
-                    Some(v) => {
-                        if v == 4277 {
-                            27700
-                        } else {
-                            v
-                        }
-                    }
-                    None => {
-                        return Err(TIFFErrorState::GeoKeyDirectoryError(UnexpectedFormat(String::from("Location for Geographic EPSG code was 0! Expected value! But none found."))));
-                    }
-                }
-            } else {
-                return Err(TIFFErrorState::GeoKeyDirectoryError(UnexpectedFormat(String::from(format!("Expected Geographic EPSG code to be single short! Actual got tag location: {}", v.location)))));
+        return Ok(GeoKeyDirectory {
+            header,
+            keys: map,
+            doubles: Vec::new(),
+            ascii: String::new(),
+        });
+    }
+
+    // Attaches the GeoDoubleParams and GeoAsciiParams values, when present in the IFD.
+    pub fn with_params(mut self, doubles: Option<Vec<f64>>, ascii: Option<String>) -> Self {
+        self.doubles = doubles.unwrap_or_default();
+        self.ascii = ascii.unwrap_or_default();
+        self
+    }
+
+    pub fn short(&self, id: u16) -> Option<u16> {
+        let key = self.keys.get(&id)?;
+        match key.location {
+            0 => key.value,
+            _ => None,
+        }
+    }
+
+    pub fn double(&self, id: u16) -> Option<f64> {
+        let key = self.keys.get(&id)?;
+        match key.location {
+            GEO_DOUBLE_PARAMS => self.doubles.get(key.value_offset as usize).copied(),
+            _ => None,
+        }
+    }
+
+    // ASCII values are pipe terminated within GeoAsciiParams.
+    pub fn ascii(&self, id: u16) -> Option<String> {
+        let key = self.keys.get(&id)?;
+        if key.location != GEO_ASCII_PARAMS {
+            return None;
+        }
+        let start = key.value_offset as usize;
+        let value = self.ascii.get(start..start + key.count as usize)?;
+        let value = value.trim_end_matches(['|', '\0']).trim();
+        (!value.is_empty()).then(|| value.to_string())
+    }
+
+    // First of the given double keys present, as GeoTIFF writers differ in which origin keys they use.
+    fn first_double(&self, ids: &[u16], default: f64) -> f64 {
+        ids.iter()
+            .find_map(|id| self.double(*id))
+            .unwrap_or(default)
+    }
+
+    // Citations and vertical CRS details, as metadata tags.
+    pub fn tags(&self) -> Vec<(String, String)> {
+        let mut tags = Vec::new();
+        for (id, name) in [
+            (1026, "Citation"),
+            (2049, "GeogCitation"),
+            (3073, "PCSCitation"),
+            (4097, "VerticalCitation"),
+        ] {
+            if let Some(citation) = self.ascii(id) {
+                tags.push((name.to_string(), citation));
             }
-        } else if let Some(v) = self.keys.get(&3072) {
-            if v.location == 0 {
-                match v.value {
-                    Some(v) => v,
-                    None => return Err(TIFFErrorState::GeoKeyDirectoryError(UnexpectedFormat(String::from("Location for Projected EPSG code was 0! Expected value! But none found."))))
-                }
+        }
+
+        match self.short(4096) {
+            Some(USER_DEFINED) => {
+                tags.push(("VerticalCRS".to_string(), "User-defined".to_string()))
+            }
+            Some(code) => tags.push(("VerticalCRS".to_string(), format!("EPSG:{code}"))),
+            None => {}
+        }
+        if let Some(datum) = self.short(4098) {
+            tags.push(("VerticalDatum".to_string(), format!("EPSG:{datum}")));
+        }
+        if let Some(units) = self.short(4099) {
+            tags.push(("VerticalUnits".to_string(), unit_name(units)));
+        }
+
+        match self.crs_code() {
+            Some(code) => tags.push(("CRS".to_string(), format!("EPSG:{code}"))),
+            None if self.short(3072) == Some(USER_DEFINED)
+                || self.short(2048) == Some(USER_DEFINED) =>
+            {
+                tags.push(("CRS".to_string(), "User-defined".to_string()))
+            }
+            None => {}
+        }
+        tags
+    }
+
+    // The model type, from GTModelTypeGeoKey; 1 projected, 2 geographic, 3 geocentric.
+    // Inferred from the CRS keys present when missing.
+    fn model_type(&self) -> Option<u16> {
+        self.short(1024).or_else(|| {
+            if self.keys.contains_key(&3072) || self.keys.contains_key(&3075) {
+                Some(1)
+            } else if self.keys.contains_key(&2048) {
+                Some(2)
             } else {
-                return Err(TIFFErrorState::GeoKeyDirectoryError(UnexpectedFormat(String::from(format!("Expected Projected EPSG code to be single short! Actual got tag location: {}", v.location)))));
+                None
             }
-        } else {
-            return Err(TIFFErrorState::NotEnoughGeoData);
+        })
+    }
+
+    // EPSG code of the CRS, if it names one rather than being user-defined.
+    pub fn crs_code(&self) -> Option<u16> {
+        let code = match self.model_type()? {
+            1 => self.short(3072)?,
+            2 => self.short(2048)?,
+            _ => return None,
         };
+        (code != 0 && code != USER_DEFINED).then_some(code)
+    }
+
+    pub fn get_projection(&self, crs: &dyn CRSDefinitions) -> Result<Proj, TIFFErrorState> {
+        let proj_string = self.proj_string(crs)?;
+        Proj::from_proj_string(&proj_string)
+            .map_err(|e| TIFFErrorState::ProjectionError(format!("Projection Error: {e:?}")))
+    }
+
+    pub fn proj_string(&self, crs: &dyn CRSDefinitions) -> Result<String, TIFFErrorState> {
+        if let Some(code) = self.crs_code() {
+            return crs.lookup(code as u32).ok_or_else(|| {
+                TIFFErrorState::ProjectionError(format!("Unsupported CRS: {code}"))
+            });
+        }
+
+        match self.model_type() {
+            Some(1) => self.user_defined_projection(crs),
+            Some(2) => Ok(format!("+proj=longlat {} +no_defs", self.datum_params(crs))),
+            Some(3) => Err(TIFFErrorState::ProjectionError(String::from(
+                "Geocentric GeoTIFFs are not supported!",
+            ))),
+            Some(model_type) => Err(TIFFErrorState::GeoKeyDirectoryError(UnexpectedFormat(
+                format!("Unexpected GTModelType: {model_type}"),
+            ))),
+            None => Err(TIFFErrorState::NotEnoughGeoData),
+        }
+    }
+
+    // Builds a projected CRS from ProjectionGeoKey, or the ProjCoordTrans and parameter keys.
+    fn user_defined_projection(&self, crs: &dyn CRSDefinitions) -> Result<String, TIFFErrorState> {
+        let datum = self.datum_params(crs);
+        let units = self.linear_units();
 
-        let def = if let Some(v) = crs_definitions::from_code(crs_code) {
-            v
-        } else {
-            return Err(TIFFErrorState::ProjectionError(format!(
-                "Unsupported CRS: {crs_code}"
+        // ProjectionGeoKey may name a standard projection, such as a UTM zone.
+        match self.short(3074) {
+            Some(code @ 16001..=16060) => {
+                return Ok(format!(
+                    "+proj=utm +zone={} {datum} {units} +no_defs",
+                    code - 16000
+                ))
+            }
+            Some(code @ 17001..=17060) => {
+                return Ok(format!(
+                    "+proj=utm +zone={} +south {datum} {units} +no_defs",
+                    code - 17000
+                ))
+            }
+            _ => {}
+        }
+
+        let Some(coord_trans) = self.short(3075) else {
+            return Err(TIFFErrorState::GeoKeyDirectoryError(UnexpectedFormat(
+                String::from("User-defined projection without ProjCoordTransGeoKey!"),
             )));
         };
 
-        return Ok(match Proj::from_proj_string(def.proj4) {
-            Ok(v) => v,
-            Err(e) => {
+        let false_easting = self.first_double(&[3082, 3086, 3090], 0.0);
+        let false_northing = self.first_double(&[3083, 3087, 3091], 0.0);
+        let origin_lat = self.first_double(&[3081, 3085, 3089], 0.0);
+        let origin_long = self.first_double(&[3080, 3084, 3088], 0.0);
+        let scale = self.first_double(&[3092, 3093], 1.0);
+        let offsets = format!("+x_0={false_easting} +y_0={false_northing}");
+
+        let projection = match coord_trans {
+            // TransverseMercator
+            1 => format!(
+                "+proj=tmerc +lat_0={origin_lat} +lon_0={origin_long} +k={scale} {offsets}"
+            ),
+            // Mercator, either by standard parallel or scale factor.
+            7 => match self.double(3078) {
+                Some(lat_ts) => format!("+proj=merc +lat_ts={lat_ts} +lon_0={origin_long} {offsets}"),
+                None => format!("+proj=merc +lon_0={origin_long} +k={scale} {offsets}"),
+            },
+            // LambertConfConic_2SP
+            8 => format!(
+                "+proj=lcc +lat_1={} +lat_2={} +lat_0={origin_lat} +lon_0={origin_long} {offsets}",
+                self.first_double(&[3078], origin_lat),
+                self.first_double(&[3079], origin_lat),
+            ),
+            // LambertConfConic_1SP
+            9 => format!(
+                "+proj=lcc +lat_1={origin_lat} +lat_0={origin_lat} +lon_0={origin_long} +k_0={scale} {offsets}"
+            ),
+            // LambertAzimEqualArea
+            10 => format!("+proj=laea +lat_0={origin_lat} +lon_0={origin_long} {offsets}"),
+            // AlbersEqualArea
+            11 => format!(
+                "+proj=aea +lat_1={} +lat_2={} +lat_0={origin_lat} +lon_0={origin_long} {offsets}",
+                self.first_double(&[3078], origin_lat),
+                self.first_double(&[3079], origin_lat),
+            ),
+            // PolarStereographic, the origin latitude being the latitude of true scale.
+            15 => format!(
+                "+proj=stere +lat_0={} +lat_ts={origin_lat} +lon_0={} +k={scale} {offsets}",
+                if origin_lat < 0.0 { -90 } else { 90 },
+                self.first_double(&[3095], origin_long),
+            ),
+            // ObliqueStereographic
+            16 => format!(
+                "+proj=sterea +lat_0={origin_lat} +lon_0={origin_long} +k={scale} {offsets}"
+            ),
+            // Equirectangular
+            17 => format!(
+                "+proj=eqc +lat_ts={} +lon_0={origin_long} {offsets}",
+                self.first_double(&[3078], origin_lat),
+            ),
+            _ => {
                 return Err(TIFFErrorState::ProjectionError(format!(
-                    "Projection Error: {e:?}"
+                    "Unsupported ProjCoordTrans: {coord_trans}"
                 )))
             }
-        });
+        };
+        Ok(format!("{projection} {datum} {units} +no_defs"))
+    }
+
+    // Datum or ellipsoid parameters; from the geographic CRS if it has a code,
+    // then the datum, ellipsoid, or explicit axes, defaulting to WGS84.
+    fn datum_params(&self, crs: &dyn CRSDefinitions) -> String {
+        if let Some(def) = self
+            .short(2048)
+            .filter(|code| *code != USER_DEFINED)
+            .and_then(|code| crs.proj_string(code as u32))
+        {
+            let params: Vec<&str> = def
+                .split_whitespace()
+                .filter(|p| {
+                    ["+datum=", "+ellps=", "+towgs84=", "+a=", "+b=", "+rf="]
+                        .iter()
+                        .any(|prefix| p.starts_with(prefix))
+                })
+                .collect();
+            if !params.is_empty() {
+                return params.join(" ");
+            }
+        }
+
+        let datum = match self.short(2050) {
+            Some(6326) => Some("+datum=WGS84"),
+            Some(6269) => Some("+datum=NAD83"),
+            Some(6267) => Some("+datum=NAD27"),
+            Some(6277) => Some("+datum=OSGB36"),
+            Some(6258) | Some(6283) => Some("+ellps=GRS80 +towgs84=0,0,0"),
+            Some(6230) => Some("+ellps=intl"),
+            _ => None,
+        };
+        if let Some(datum) = datum {
+            return datum.to_string();
+        }
+
+        let ellipsoid = match self.short(2056) {
+            Some(7030) => Some("+ellps=WGS84"),
+            Some(7019) => Some("+ellps=GRS80"),
+            Some(7001) => Some("+ellps=airy"),
+            Some(7004) => Some("+ellps=bessel"),
+            Some(7008) => Some("+ellps=clrk66"),
+            Some(7022) => Some("+ellps=intl"),
+            _ => None,
+        };
+        if let Some(ellipsoid) = ellipsoid {
+            return ellipsoid.to_string();
+        }
+
+        match (self.double(2057), self.double(2059), self.double(2058)) {
+            (Some(a), Some(rf), _) => format!("+a={a} +rf={rf}"),
+            (Some(a), None, Some(b)) => format!("+a={a} +b={b}"),
+            _ => "+datum=WGS84".to_string(),
+        }
+    }
+
+    fn linear_units(&self) -> String {
+        match (self.short(3076), self.double(3077)) {
+            (Some(9002), _) => "+units=ft".to_string(),
+            (Some(9003), _) => "+units=us-ft".to_string(),
+            (Some(9001), _) => "+units=m".to_string(),
+            (_, Some(size)) => format!("+to_meter={size}"),
+            _ => "+units=m".to_string(),
+        }
+    }
+}
+
+fn unit_name(code: u16) -> String {
+    match code {
+        9001 => "metre".to_string(),
+        9002 => "foot".to_string(),
+        9003 => "US survey foot".to_string(),
+        _ => format!("EPSG:{code}"),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::BuiltinCRS;
 
     #[test]
     fn test_geo_key_directory_header_from_shorts_valid() {
@@ -243,12 +507,18 @@ mod tests {
             location: 0,
             count: 1,
             value: Some(4326), // WGS 84
+            value_offset: 4326,
         };
 
         let mut keys = HashMap::new();
         keys.insert(geographic_type_geo_key.id, geographic_type_geo_key);
 
-        GeoKeyDirectory { header, keys }
+        GeoKeyDirectory {
+            header,
+            keys,
+            doubles: Vec::new(),
+            ascii: String::new(),
+        }
     }
 
     #[test]
@@ -256,7 +526,7 @@ mod tests {
         let directory = prepare_geo_key_directory_with_valid_crs();
         let target_epsg = "EPSG:4326"; // The target CRS code, used here as an example
 
-        let projection_result = directory.get_projection();
+        let projection_result = directory.get_projection(&BuiltinCRS);
 
         // Check if projection_result is Ok, the exact type of Ok value depends on the return type of Proj::from_proj_string
         assert!(
@@ -264,4 +534,189 @@ mod tests {
             "Failed to get projection for a valid CRS code"
         );
     }
+
+    fn directory(keys: &[[u16; 4]], doubles: &[f64], ascii: &str) -> GeoKeyDirectory {
+        let mut shorts = vec![1, 1, 0, keys.len() as u16];
+        shorts.extend(keys.iter().flatten());
+        GeoKeyDirectory::from_shorts(&shorts)
+            .unwrap()
+            .with_params(Some(doubles.to_vec()), Some(ascii.to_string()))
+    }
+
+    fn transform(directory: &GeoKeyDirectory, point: (f64, f64)) -> (f64, f64) {
+        let from = directory.get_projection(&BuiltinCRS).unwrap();
+        let to = Proj::from_proj_string(crs_definitions::EPSG_4326.proj4).unwrap();
+        let mut point = point;
+        proj4rs::transform::transform(&from, &to, &mut point).unwrap();
+        (point.0.to_degrees(), point.1.to_degrees())
+    }
+
+    #[test]
+    fn test_projected_code_takes_precedence_over_geographic() {
+        // OSGB36 geographic CRS alongside British National Grid.
+        let directory = directory(
+            &[[1024, 0, 1, 1], [2048, 0, 1, 4277], [3072, 0, 1, 27700]],
+            &[],
+            "",
+        );
+        assert_eq!(directory.crs_code(), Some(27700));
+        assert!(directory
+            .tags()
+            .contains(&("CRS".to_string(), "EPSG:27700".to_string())));
+    }
+
+    #[test]
+    fn test_geographic_model_uses_geographic_code() {
+        let directory = directory(&[[1024, 0, 1, 2], [2048, 0, 1, 4277]], &[], "");
+        assert_eq!(directory.crs_code(), Some(4277));
+    }
+
+    #[test]
+    fn test_user_defined_transverse_mercator() {
+        // British National Grid, defined by its parameters on the OSGB36 datum.
+        let directory = directory(
+            &[
+                [1024, 0, 1, 1],
+                [2048, 0, 1, 4277],
+                [3072, 0, 1, 32767],
+                [3075, 0, 1, 1],
+                [3076, 0, 1, 9001],
+                [3080, 34736, 1, 0],
+                [3081, 34736, 1, 1],
+                [3082, 34736, 1, 2],
+                [3083, 34736, 1, 3],
+                [3092, 34736, 1, 4],
+            ],
+            &[-2.0, 49.0, 400000.0, -100000.0, 0.9996012717],
+            "",
+        );
+
+        let proj_string = directory.proj_string(&BuiltinCRS).unwrap();
+        assert!(proj_string.starts_with("+proj=tmerc +lat_0=49 +lon_0=-2 +k=0.9996012717"));
+        assert!(proj_string.contains("+ellps=airy"));
+
+        let (lon, lat) = transform(&directory, (400000.0, -100000.0));
+        assert!((lon - -2.0).abs() < 0.01, "lon: {lon}");
+        assert!((lat - 49.0).abs() < 0.01, "lat: {lat}");
+        assert!(directory
+            .tags()
+            .contains(&("CRS".to_string(), "User-defined".to_string())));
+    }
+
+    #[test]
+    fn test_user_defined_utm_from_projection_key() {
+        let directory = directory(
+            &[
+                [1024, 0, 1, 1],
+                [2050, 0, 1, 6326],
+                [3072, 0, 1, 32767],
+                [3074, 0, 1, 16030],
+            ],
+            &[],
+            "",
+        );
+        assert_eq!(
+            directory.proj_string(&BuiltinCRS).unwrap(),
+            "+proj=utm +zone=30 +datum=WGS84 +units=m +no_defs"
+        );
+        let (lon, lat) = transform(&directory, (500000.0, 0.0));
+        assert!((lon - -3.0).abs() < 1e-6);
+        assert!(lat.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_user_defined_polar_stereographic() {
+        // Antarctic Polar Stereographic, true scale at 71S.
+        let directory = directory(
+            &[
+                [1024, 0, 1, 1],
+                [2048, 0, 1, 4326],
+                [3072, 0, 1, 32767],
+                [3075, 0, 1, 15],
+                [3081, 34736, 1, 0],
+                [3095, 34736, 1, 1],
+            ],
+            &[-71.0, 0.0],
+            "",
+        );
+        let proj_string = directory.proj_string(&BuiltinCRS).unwrap();
+        assert!(proj_string.starts_with("+proj=stere +lat_0=-90 +lat_ts=-71 +lon_0=0"));
+        let (_, lat) = transform(&directory, (0.0, 0.0));
+        assert!((lat - -90.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_user_defined_geographic_from_axes() {
+        let directory = directory(
+            &[
+                [1024, 0, 1, 2],
+                [2048, 0, 1, 32767],
+                [2057, 34736, 1, 0],
+                [2059, 34736, 1, 1],
+            ],
+            &[6378137.0, 298.257223563],
+            "",
+        );
+        assert_eq!(
+            directory.proj_string(&BuiltinCRS).unwrap(),
+            "+proj=longlat +a=6378137 +rf=298.257223563 +no_defs"
+        );
+        assert!(directory.get_projection(&BuiltinCRS).is_ok());
+    }
+
+    #[test]
+    fn test_linear_units_in_feet() {
+        let directory = directory(
+            &[
+                [1024, 0, 1, 1],
+                [3072, 0, 1, 32767],
+                [3074, 0, 1, 16030],
+                [3076, 0, 1, 9002],
+            ],
+            &[],
+            "",
+        );
+        assert!(directory
+            .proj_string(&BuiltinCRS)
+            .unwrap()
+            .contains("+units=ft"));
+    }
+
+    #[test]
+    fn test_unsupported_coord_trans() {
+        let directory = directory(
+            &[[1024, 0, 1, 1], [3072, 0, 1, 32767], [3075, 0, 1, 99]],
+            &[],
+            "",
+        );
+        assert!(matches!(
+            directory.proj_string(&BuiltinCRS),
+            Err(TIFFErrorState::ProjectionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_citations_and_vertical_crs() {
+        let ascii = "WGS 84 / UTM zone 30N|WGS 84|ODN height|";
+        let directory = directory(
+            &[
+                [1024, 0, 1, 1],
+                [1026, 34737, 22, 0],
+                [2049, 34737, 7, 22],
+                [3072, 0, 1, 32630],
+                [4096, 0, 1, 5701],
+                [4097, 34737, 11, 29],
+                [4099, 0, 1, 9001],
+            ],
+            &[],
+            ascii,
+        );
+        let tags = directory.tags();
+        assert!(tags.contains(&("Citation".to_string(), "WGS 84 / UTM zone 30N".to_string())));
+        assert!(tags.contains(&("GeogCitation".to_string(), "WGS 84".to_string())));
+        assert!(tags.contains(&("VerticalCitation".to_string(), "ODN height".to_string())));
+        assert!(tags.contains(&("VerticalCRS".to_string(), "EPSG:5701".to_string())));
+        assert!(tags.contains(&("VerticalUnits".to_string(), "metre".to_string())));
+        assert!(tags.contains(&("CRS".to_string(), "EPSG:32630".to_string())));
+    }
 }
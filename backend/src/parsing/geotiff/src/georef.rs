@@ -0,0 +1,253 @@
+use crate::error::TIFFErrorState;
+use crate::tfw::TFWData;
+
+// Affine raster to model transform, referenced to the outer edge of the top left pixel.
+//   x = a * col + b * row + c
+//   y = d * col + e * row + f
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoTransform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl GeoTransform {
+    // Single ModelTiepoint (I, J, K, X, Y, Z) and ModelPixelScale (Sx, Sy, Sz), assumes no rotation.
+    pub fn from_tiepoint_and_scale(
+        tiepoint: &[f64],
+        scale: &[f64],
+    ) -> Result<GeoTransform, TIFFErrorState> {
+        if tiepoint.len() < 6 || scale.len() < 2 {
+            return Err(TIFFErrorState::NotEnoughGeoData);
+        }
+        let (i, j, x, y) = (tiepoint[0], tiepoint[1], tiepoint[3], tiepoint[4]);
+        let (sx, sy) = (scale[0], scale[1]);
+        // Pixel scale is positive as rows go down, so y decreases.
+        Ok(GeoTransform {
+            a: sx,
+            b: 0.0,
+            c: x - i * sx,
+            d: 0.0,
+            e: -sy,
+            f: y + j * sy,
+        })
+    }
+
+    // ModelTransformationTag, a row major 4x4 matrix of 16 doubles.
+    pub fn from_matrix(matrix: &[f64]) -> Result<GeoTransform, TIFFErrorState> {
+        if matrix.len() != 16 {
+            return Err(TIFFErrorState::UnexpectedFormat(format!(
+                "Expected ModelTransformation to hold 16 values, got {}",
+                matrix.len()
+            )));
+        }
+        Ok(GeoTransform {
+            a: matrix[0],
+            b: matrix[1],
+            c: matrix[3],
+            d: matrix[4],
+            e: matrix[5],
+            f: matrix[7],
+        })
+    }
+
+    // Least squares fit of an affine transform to three or more ModelTiepoints.
+    pub fn from_tiepoints(tiepoints: &[f64]) -> Result<GeoTransform, TIFFErrorState> {
+        let points: Vec<&[f64]> = tiepoints.chunks_exact(6).collect();
+        if points.len() < 3 {
+            return Err(TIFFErrorState::NotEnoughGeoData);
+        }
+
+        // Normal equations, (A^T A) p = A^T b, where each row of A is [i, j, 1].
+        let mut ata = [[0f64; 3]; 3];
+        let mut atx = [0f64; 3];
+        let mut aty = [0f64; 3];
+        for point in points.iter() {
+            let row = [point[0], point[1], 1.0];
+            for r in 0..3 {
+                for c in 0..3 {
+                    ata[r][c] += row[r] * row[c];
+                }
+                atx[r] += row[r] * point[3];
+                aty[r] += row[r] * point[4];
+            }
+        }
+
+        let (Some(px), Some(py)) = (solve_3x3(ata, atx), solve_3x3(ata, aty)) else {
+            return Err(TIFFErrorState::UnexpectedFormat(String::from(
+                "ModelTiepoints are collinear, cannot fit an affine transform!",
+            )));
+        };
+
+        Ok(GeoTransform {
+            a: px[0],
+            b: px[1],
+            c: px[2],
+            d: py[0],
+            e: py[1],
+            f: py[2],
+        })
+    }
+
+    pub fn transform(&self, col: f64, row: f64) -> (f64, f64) {
+        (
+            self.a * col + self.b * row + self.c,
+            self.d * col + self.e * row + self.f,
+        )
+    }
+
+    // Model space position of the four image corners; top left, top right, bottom right, bottom left.
+    pub fn corners(&self, width: f64, height: f64) -> [(f64, f64); 4] {
+        [
+            self.transform(0.0, 0.0),
+            self.transform(width, 0.0),
+            self.transform(width, height),
+            self.transform(0.0, height),
+        ]
+    }
+}
+
+impl From<&TFWData> for GeoTransform {
+    // World files reference pixel centres, so shift the origin out by half a pixel.
+    fn from(tfw: &TFWData) -> Self {
+        let (c, f) = tfw.transform(-0.5, -0.5);
+        GeoTransform {
+            a: tfw.pixel_size_x,
+            b: tfw.rotation_x,
+            c,
+            d: tfw.rotation_y,
+            e: tfw.pixel_size_y,
+            f,
+        }
+    }
+}
+
+// Solves a 3x3 linear system with Cramer's rule, None when singular.
+fn solve_3x3(m: [[f64; 3]; 3], v: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(&m);
+    if d.abs() < 1e-12 {
+        return None;
+    }
+    let mut result = [0f64; 3];
+    for (col, value) in result.iter_mut().enumerate() {
+        let mut replaced = m;
+        for row in 0..3 {
+            replaced[row][col] = v[row];
+        }
+        *value = det(&replaced) / d;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_tiepoint_and_scale() {
+        let transform = GeoTransform::from_tiepoint_and_scale(
+            &[0.0, 0.0, 0.0, 100.0, 200.0, 0.0],
+            &[2.0, 2.0, 0.0],
+        )
+        .unwrap();
+        assert_eq!(transform.corners(10.0, 5.0)[0], (100.0, 200.0));
+        assert_eq!(transform.corners(10.0, 5.0)[2], (120.0, 190.0));
+    }
+
+    #[test]
+    fn test_from_tiepoint_not_at_origin() {
+        let transform = GeoTransform::from_tiepoint_and_scale(
+            &[5.0, 5.0, 0.0, 110.0, 190.0, 0.0],
+            &[2.0, 2.0, 0.0],
+        )
+        .unwrap();
+        assert_eq!(transform.transform(0.0, 0.0), (100.0, 200.0));
+    }
+
+    #[test]
+    fn test_from_matrix_with_rotation() {
+        #[rustfmt::skip]
+        let matrix = [
+            0.0, -1.0, 0.0, 10.0,
+            -1.0, 0.0, 0.0, 20.0,
+            0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ];
+        let transform = GeoTransform::from_matrix(&matrix).unwrap();
+        assert_eq!(
+            transform.corners(4.0, 2.0),
+            [(10.0, 20.0), (10.0, 16.0), (8.0, 16.0), (8.0, 20.0)]
+        );
+    }
+
+    #[test]
+    fn test_from_matrix_invalid_length() {
+        assert!(GeoTransform::from_matrix(&[0.0; 15]).is_err());
+    }
+
+    #[test]
+    fn test_from_tiepoints_fit() {
+        // Generated from x = 2i + 0.5j + 100, y = 0.5i - 2j + 200.
+        let tiepoints: Vec<f64> = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0), (10.0, 10.0)]
+            .iter()
+            .flat_map(|(i, j)| {
+                [
+                    *i,
+                    *j,
+                    0.0,
+                    2.0 * i + 0.5 * j + 100.0,
+                    0.5 * i - 2.0 * j + 200.0,
+                    0.0,
+                ]
+            })
+            .collect();
+        let transform = GeoTransform::from_tiepoints(&tiepoints).unwrap();
+        let (x, y) = transform.transform(4.0, 6.0);
+        assert!((x - 111.0).abs() < 1e-9);
+        assert!((y - 190.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_from_tiepoints_collinear() {
+        let tiepoints = [
+            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, //
+            1.0, 1.0, 0.0, 1.0, 1.0, 0.0, //
+            2.0, 2.0, 0.0, 2.0, 2.0, 0.0,
+        ];
+        assert!(GeoTransform::from_tiepoints(&tiepoints).is_err());
+    }
+
+    #[test]
+    fn test_from_tiepoints_too_few() {
+        assert!(matches!(
+            GeoTransform::from_tiepoints(&[0.0; 12]),
+            Err(TIFFErrorState::NotEnoughGeoData)
+        ));
+    }
+
+    #[test]
+    fn test_from_tfw_with_rotation() {
+        // 90 degree rotation, columns run south and rows run west.
+        let tfw = TFWData {
+            pixel_size_x: 0.0,
+            rotation_y: -1.0,
+            rotation_x: -1.0,
+            pixel_size_y: 0.0,
+            origin_x: 0.0,
+            origin_y: 0.0,
+        };
+        let transform = GeoTransform::from(&tfw);
+        assert_eq!(
+            transform.corners(4.0, 2.0),
+            [(0.5, 0.5), (0.5, -3.5), (-1.5, -3.5), (-1.5, 0.5)]
+        );
+    }
+}
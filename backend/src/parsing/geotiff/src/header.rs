@@ -1,9 +1,18 @@
 use crate::error::{HeaderErrorState, TIFFErrorState};
-use crate::util::{ByteOrder, FromBytes};
+use crate::util::{ByteOrder, FromBytes, TIFFVariant};
 use std::io::SeekFrom;
 
-pub fn parse_header(buffer: &[u8]) -> Result<(ByteOrder, SeekFrom), TIFFErrorState> {
-    if buffer.len() != 8 {
+// Length of the header, classic TIFF headers are 8 bytes and BigTIFF headers 16.
+// Only needs the first 4 bytes, so can be called on the start of either.
+pub fn header_length(buffer: &[u8]) -> usize {
+    match buffer.get(0..4) {
+        Some([73, 73, 43, 0]) | Some([77, 77, 0, 43]) => 16,
+        _ => 8,
+    }
+}
+
+pub fn parse_header(buffer: &[u8]) -> Result<(ByteOrder, SeekFrom, TIFFVariant), TIFFErrorState> {
+    if buffer.len() != 8 && buffer.len() != 16 {
         return Err(TIFFErrorState::HeaderError(
             HeaderErrorState::InvalidLength(buffer.len()),
         ));
@@ -20,16 +29,36 @@ pub fn parse_header(buffer: &[u8]) -> Result<(ByteOrder, SeekFrom), TIFFErrorSta
     };
 
     let magic_numbers = &buffer[2..4];
-    if !match byte_order {
-        ByteOrder::LittleEndian => (magic_numbers[0] == 42) && (magic_numbers[1] == 0),
-        ByteOrder::BigEndian => (magic_numbers[0] == 0) && (magic_numbers[1] == 42),
-    } {
+    let variant = match u16::from_bytes(magic_numbers, &byte_order) {
+        42 => TIFFVariant::Classic,
+        43 => TIFFVariant::BigTIFF,
+        _ => {
+            return Err(TIFFErrorState::HeaderError(
+                HeaderErrorState::UnexpectedMagicNumber([magic_numbers[0], magic_numbers[1]]),
+            ))
+        }
+    };
+
+    if buffer.len() != header_length(buffer) {
         return Err(TIFFErrorState::HeaderError(
-            HeaderErrorState::UnexpectedMagicNumber([magic_numbers[0], magic_numbers[1]]),
+            HeaderErrorState::InvalidLength(buffer.len()),
         ));
     }
-    let ifd_offset = SeekFrom::Start(u32::from_bytes(&buffer[4..8], &byte_order) as u64);
-    return Ok((byte_order, ifd_offset));
+
+    let ifd_offset = match variant {
+        TIFFVariant::Classic => u32::from_bytes(&buffer[4..8], &byte_order) as u64,
+        TIFFVariant::BigTIFF => {
+            // BigTIFF declares its offset size (always 8), followed by 2 reserved bytes.
+            let offset_size = u16::from_bytes(&buffer[4..6], &byte_order);
+            if offset_size != 8 || u16::from_bytes(&buffer[6..8], &byte_order) != 0 {
+                return Err(TIFFErrorState::HeaderError(
+                    HeaderErrorState::UnexpectedOffsetSize(offset_size),
+                ));
+            }
+            u64::from_bytes(&buffer[8..16], &byte_order)
+        }
+    };
+    Ok((byte_order, SeekFrom::Start(ifd_offset), variant))
 }
 
 #[cfg(test)]
@@ -42,7 +71,7 @@ mod tests {
         let buffer = [0x49, 0x49, 0x2A, 0x00, 0x08, 0x00, 0x00, 0x00];
         let result = parse_header(&buffer);
         assert!(result.is_ok());
-        let (byte_order, ifd_offset) = result.unwrap();
+        let (byte_order, ifd_offset, _) = result.unwrap();
         assert_eq!(byte_order, ByteOrder::LittleEndian);
         assert_eq!(ifd_offset, SeekFrom::Start(8));
     }
@@ -89,7 +118,7 @@ mod tests {
         let buffer = [0x4D, 0x4D, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x08];
         let result = parse_header(&buffer);
         assert!(result.is_ok());
-        let (byte_order, ifd_offset) = result.unwrap();
+        let (byte_order, ifd_offset, _) = result.unwrap();
         assert_eq!(byte_order, ByteOrder::BigEndian);
         assert_eq!(ifd_offset, SeekFrom::Start(8));
     }
@@ -138,7 +167,7 @@ mod tests {
         let buffer = [0x49, 0x49, 0x2A, 0x00, 0x00, 0x00, 0x00, 0x00];
         let result = parse_header(&buffer);
         assert!(result.is_ok());
-        let (_, ifd_offset) = result.unwrap();
+        let (_, ifd_offset, _) = result.unwrap();
         assert_eq!(ifd_offset, SeekFrom::Start(0));
     }
 
@@ -148,7 +177,7 @@ mod tests {
         let buffer = [0x49, 0x49, 0x2A, 0x00, 0xFF, 0xFF, 0xFF, 0xFF];
         let result = parse_header(&buffer);
         assert!(result.is_ok());
-        let (_, ifd_offset) = result.unwrap();
+        let (_, ifd_offset, _) = result.unwrap();
         assert_eq!(ifd_offset, SeekFrom::Start(4294967295));
     }
     #[test]
@@ -190,7 +219,66 @@ mod tests {
         let buffer = [0x49, 0x49, 0x2A, 0x00, 0xFF, 0xFF, 0xFF, 0xFF];
         let result = parse_header(&buffer);
         assert!(result.is_ok());
-        let (_, ifd_offset) = result.unwrap();
+        let (_, ifd_offset, _) = result.unwrap();
         assert_eq!(ifd_offset, SeekFrom::Start(u32::MAX as u64));
     }
+
+    #[test]
+    fn test_parse_header_classic_variant() {
+        let buffer = [0x49, 0x49, 0x2A, 0x00, 0x08, 0x00, 0x00, 0x00];
+        assert_eq!(header_length(&buffer), 8);
+        let (_, _, variant) = parse_header(&buffer).unwrap();
+        assert_eq!(variant, TIFFVariant::Classic);
+    }
+
+    #[test]
+    fn test_parse_header_bigtiff() {
+        let buffer = [
+            0x49, 0x49, 0x2B, 0x00, 0x08, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        assert_eq!(header_length(&buffer), 16);
+        let (byte_order, ifd_offset, variant) = parse_header(&buffer).unwrap();
+        assert_eq!(byte_order, ByteOrder::LittleEndian);
+        assert_eq!(ifd_offset, SeekFrom::Start(16));
+        assert_eq!(variant, TIFFVariant::BigTIFF);
+    }
+
+    #[test]
+    fn test_parse_header_bigtiff_big_endian_high_offset() {
+        // Offsets beyond 4GB are only reachable in BigTIFF.
+        let buffer = [
+            0x4D, 0x4D, 0x00, 0x2B, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+            0x00, 0x10,
+        ];
+        assert_eq!(header_length(&buffer[0..8]), 16);
+        let (byte_order, ifd_offset, _) = parse_header(&buffer).unwrap();
+        assert_eq!(byte_order, ByteOrder::BigEndian);
+        assert_eq!(ifd_offset, SeekFrom::Start(0x1_0000_0010));
+    }
+
+    #[test]
+    fn test_parse_header_bigtiff_truncated() {
+        let buffer = [0x49, 0x49, 0x2B, 0x00, 0x08, 0x00, 0x00, 0x00];
+        assert!(matches!(
+            parse_header(&buffer),
+            Err(TIFFErrorState::HeaderError(
+                HeaderErrorState::InvalidLength(8)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_parse_header_bigtiff_unexpected_offset_size() {
+        let buffer = [
+            0x49, 0x49, 0x2B, 0x00, 0x04, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        assert!(matches!(
+            parse_header(&buffer),
+            Err(TIFFErrorState::HeaderError(
+                HeaderErrorState::UnexpectedOffsetSize(4)
+            ))
+        ));
+    }
 }
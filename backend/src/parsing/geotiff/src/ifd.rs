@@ -0,0 +1,367 @@
+use crate::entry::{EntryValue, IFDEntry};
+use crate::error::TIFFErrorState;
+use crate::util::{ByteOrder, FromBytes, TIFFVariant};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use tracing::{event, Level};
+
+// Guards against corrupt files whose IFD chains never terminate.
+const MAX_IFDS: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubfileKind {
+    FullResolution,
+    ReducedResolution,
+    Mask,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageFileDirectory {
+    pub offset: u64,
+    pub entries: HashMap<u16, IFDEntry>,
+    pub width: u64,
+    pub height: u64,
+    pub kind: SubfileKind,
+    // Whether this was reached through a SubIFDs tag, rather than the main chain.
+    pub sub_ifd: bool,
+}
+
+impl ImageFileDirectory {
+    pub fn dimensions(&self) -> String {
+        format!("{}x{}", self.width, self.height)
+    }
+
+    fn is_georeferenced(&self) -> bool {
+        [34264, 33922, 34735]
+            .iter()
+            .any(|tag| self.entries.contains_key(tag))
+    }
+}
+
+// Walks the IFD chain from the first IFD, descending into any SubIFDs along the way.
+// Every directory after the first is optional, and skipped with a warning if it can't be read.
+pub fn read_directories(
+    reader: &mut BufReader<File>,
+    first_offset: u64,
+    byte_order: &ByteOrder,
+    variant: &TIFFVariant,
+) -> Result<Vec<ImageFileDirectory>, TIFFErrorState> {
+    let mut directories = Vec::new();
+    let mut visited = HashSet::new();
+    // Stack of (offset, reached through SubIFDs), popped in file order.
+    let mut pending = vec![(first_offset, false)];
+
+    while let Some((offset, sub_ifd)) = pending.pop() {
+        if offset == 0 || !visited.insert(offset) {
+            continue;
+        }
+        if visited.len() > MAX_IFDS {
+            event!(
+                Level::WARN,
+                "Stopped reading IFDs after {MAX_IFDS}, the chain may be cyclic."
+            );
+            break;
+        }
+
+        let first = offset == first_offset;
+        let directory = match read_directory(reader, offset, sub_ifd, byte_order, variant) {
+            Ok(directory) => directory,
+            Err(e) if first => return Err(e),
+            Err(e) => {
+                event!(
+                    Level::WARN,
+                    "Skipping unreadable IFD at offset {offset}, due to {e:?}"
+                );
+                continue;
+            }
+        };
+
+        // Next in this chain after any SubIFDs, so push first.
+        pending.push((directory.1, sub_ifd));
+        for sub_offset in directory.2.into_iter().rev() {
+            pending.push((sub_offset, true));
+        }
+        directories.push(directory.0);
+    }
+
+    Ok(directories)
+}
+
+// Reads and classifies a single IFD, returning it with its next IFD and SubIFD offsets.
+fn read_directory(
+    reader: &mut BufReader<File>,
+    offset: u64,
+    sub_ifd: bool,
+    byte_order: &ByteOrder,
+    variant: &TIFFVariant,
+) -> Result<(ImageFileDirectory, u64, Vec<u64>), TIFFErrorState> {
+    let (mut entries, next_offset) = read_ifd(reader, offset, byte_order, variant)?;
+    let width = resolve_dimension(&mut entries, 256, "ImageWidth", byte_order, reader)?;
+    let height = resolve_dimension(&mut entries, 257, "ImageLength", byte_order, reader)?;
+
+    // NewSubfileType is a bitfield; bit 0 marks reduced resolution, bit 2 a transparency mask.
+    // The deprecated SubfileType uses 2 for reduced resolution.
+    let new_subfile_type = resolve_integers(&mut entries, 254, byte_order, reader)?
+        .and_then(|v| v.first().copied())
+        .unwrap_or(0);
+    let subfile_type =
+        resolve_integers(&mut entries, 255, byte_order, reader)?.and_then(|v| v.first().copied());
+    let kind = if new_subfile_type & 4 != 0 {
+        SubfileKind::Mask
+    } else if new_subfile_type & 1 != 0 || subfile_type == Some(2) {
+        SubfileKind::ReducedResolution
+    } else {
+        SubfileKind::FullResolution
+    };
+
+    let sub_offsets = resolve_integers(&mut entries, 330, byte_order, reader)?.unwrap_or_default();
+
+    Ok((
+        ImageFileDirectory {
+            offset,
+            entries,
+            width,
+            height,
+            kind,
+            sub_ifd,
+        },
+        next_offset,
+        sub_offsets,
+    ))
+}
+
+// Index of the directory holding the main image; the largest full resolution image,
+// preferring those carrying georeferencing, so leading thumbnails are passed over.
+pub fn primary_directory(directories: &[ImageFileDirectory]) -> Option<usize> {
+    let full_resolution = directories
+        .iter()
+        .any(|d| d.kind == SubfileKind::FullResolution);
+    directories
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| !full_resolution || d.kind == SubfileKind::FullResolution)
+        // Reverse so ties resolve to the earliest directory.
+        .rev()
+        .max_by_key(|(_, d)| (d.is_georeferenced(), d.width.saturating_mul(d.height)))
+        .map(|(i, _)| i)
+}
+
+// Reads the IFD at the given offset, returning its entries and the offset of the next IFD (0 if last).
+fn read_ifd(
+    reader: &mut BufReader<File>,
+    offset: u64,
+    byte_order: &ByteOrder,
+    variant: &TIFFVariant,
+) -> Result<(HashMap<u16, IFDEntry>, u64), TIFFErrorState> {
+    // Parse the IFD header; (Get entry count)
+    // First, seek to start of header.
+    // Then read the count, 2 bytes in classic TIFF, or 8 in BigTIFF.
+    if let Err(e) = reader.seek(SeekFrom::Start(offset)) {
+        return Err(TIFFErrorState::UnexpectedFormat(format!(
+            "Seek to IFD failed: {:?}",
+            e
+        )));
+    }
+    let mut entry_count_buf = vec![0u8; variant.entry_count_size()];
+    if let Err(e) = reader.read_exact(&mut entry_count_buf) {
+        return Err(TIFFErrorState::UnexpectedFormat(format!(
+            "Read of IFD entry count failed: {:?}",
+            e
+        )));
+    }
+    let entry_count = match variant {
+        TIFFVariant::Classic => u16::from_bytes(&entry_count_buf, byte_order) as u64,
+        TIFFVariant::BigTIFF => u64::from_bytes(&entry_count_buf, byte_order),
+    };
+
+    // Init hashmap for entries, capped as the count is untrusted.
+    let mut entries: HashMap<u16, IFDEntry> =
+        HashMap::with_capacity(entry_count.min(u16::MAX as u64) as usize);
+
+    let mut entry_buf = vec![0u8; variant.entry_size()];
+    for entry_number in 0..entry_count {
+        match reader.read_exact(&mut entry_buf) {
+            Ok(_) => {
+                let entry = IFDEntry::new(&entry_buf, byte_order)?;
+                entries.insert(entry.tag, entry);
+            }
+            Err(e) => {
+                return Err(TIFFErrorState::UnexpectedFormat(format!(
+                    "Expected IFD Entry #{}, could not read, due to {:?}",
+                    entry_number, e
+                )))
+            }
+        }
+    }
+
+    // Offset of the next IFD follows the entries.
+    let mut next_buf = vec![0u8; variant.offset_size()];
+    let next_offset = match reader.read_exact(&mut next_buf) {
+        Ok(_) => match variant {
+            TIFFVariant::Classic => u32::from_bytes(&next_buf, byte_order) as u64,
+            TIFFVariant::BigTIFF => u64::from_bytes(&next_buf, byte_order),
+        },
+        // Some writers omit the terminating offset on the last IFD.
+        Err(_) => 0,
+    };
+
+    Ok((entries, next_offset))
+}
+
+// Resolves an image dimension, which may be of type SHORT or LONG (or LONG8 in BigTIFF).
+pub(crate) fn resolve_dimension(
+    entries: &mut HashMap<u16, IFDEntry>,
+    tag: u16,
+    name: &str,
+    byte_order: &ByteOrder,
+    reader: &mut BufReader<File>,
+) -> Result<u64, TIFFErrorState> {
+    // If the entry does not exist, return an error.
+    let Some(entry) = entries.get_mut(&tag) else {
+        return Err(TIFFErrorState::UnexpectedFormat(format!(
+            "Expected {name}!"
+        )));
+    };
+    let value = match entry.resolve(byte_order, reader)? {
+        EntryValue::SHORT(v) => v.first().map(|v| *v as u64),
+        EntryValue::LONG(v) => v.first().map(|v| *v as u64),
+        EntryValue::LONG8(v) => v.first().copied(),
+        _ => {
+            return Err(TIFFErrorState::UnexpectedFormat(format!(
+                "Expected {name} to be of type SHORT or LONG!"
+            )))
+        }
+    };
+    value.ok_or_else(|| TIFFErrorState::UnexpectedFormat(format!("Expected {name}!")))
+}
+
+// Resolves any unsigned integer entry (SHORT, LONG, LONG8, IFD or IFD8) if present, widened to u64.
+pub(crate) fn resolve_integers(
+    entries: &mut HashMap<u16, IFDEntry>,
+    tag: u16,
+    byte_order: &ByteOrder,
+    reader: &mut BufReader<File>,
+) -> Result<Option<Vec<u64>>, TIFFErrorState> {
+    let Some(entry) = entries.get_mut(&tag) else {
+        return Ok(None);
+    };
+    Ok(Some(match entry.resolve(byte_order, reader)? {
+        EntryValue::SHORT(v) => v.iter().map(|v| *v as u64).collect(),
+        EntryValue::LONG(v) | EntryValue::IFD(v) => v.iter().map(|v| *v as u64).collect(),
+        EntryValue::LONG8(v) | EntryValue::IFD8(v) => v.clone(),
+        _ => {
+            return Err(TIFFErrorState::UnexpectedFormat(format!(
+                "Expected tag {tag} to be an unsigned integer!"
+            )))
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{ifd_offset, longs, mock_tiff_ifds, shorts, MockEntry};
+
+    fn image(width: u16, height: u16, new_subfile_type: u32) -> Vec<MockEntry> {
+        vec![
+            (254, 4, 1, longs(&[new_subfile_type])),
+            (256, 3, 1, shorts(&[width])),
+            (257, 3, 1, shorts(&[height])),
+        ]
+    }
+
+    #[test]
+    fn test_read_directories_chain() {
+        let mut reader = mock_tiff_ifds(&[image(64, 64, 0), image(32, 32, 1)], 2);
+
+        let directories = read_directories(
+            &mut reader,
+            ifd_offset(0) as u64,
+            &ByteOrder::LittleEndian,
+            &TIFFVariant::Classic,
+        )
+        .unwrap();
+
+        assert_eq!(directories.len(), 2);
+        assert_eq!(directories[0].kind, SubfileKind::FullResolution);
+        assert_eq!(directories[1].kind, SubfileKind::ReducedResolution);
+        assert_eq!(directories[1].offset, ifd_offset(1) as u64);
+        assert_eq!(directories[1].dimensions(), "32x32");
+    }
+
+    #[test]
+    fn test_read_directories_sub_ifds() {
+        // The main image points to its two overviews through SubIFDs, rather than the chain.
+        let mut main = image(64, 64, 0);
+        main.push((330, 13, 2, longs(&[ifd_offset(1), ifd_offset(2)])));
+        let mut reader = mock_tiff_ifds(&[main, image(32, 32, 1), image(16, 16, 1)], 1);
+
+        let directories = read_directories(
+            &mut reader,
+            ifd_offset(0) as u64,
+            &ByteOrder::LittleEndian,
+            &TIFFVariant::Classic,
+        )
+        .unwrap();
+
+        assert_eq!(directories.len(), 3);
+        assert!(!directories[0].sub_ifd);
+        assert!(directories[1].sub_ifd && directories[2].sub_ifd);
+        assert_eq!(directories[2].dimensions(), "16x16");
+    }
+
+    #[test]
+    fn test_read_directories_cyclic_chain() {
+        let mut reader = mock_tiff_ifds(&[image(64, 64, 0), image(32, 32, 1)], 2);
+        // Point the second IFD back at the first.
+        let mut file = reader.get_mut().try_clone().unwrap();
+        let next_offset = ifd_offset(1) as u64 + 2 + 3 * 12;
+        file.seek(SeekFrom::Start(next_offset)).unwrap();
+        std::io::Write::write_all(&mut file, &ifd_offset(0).to_le_bytes()).unwrap();
+
+        let directories = read_directories(
+            &mut reader,
+            ifd_offset(0) as u64,
+            &ByteOrder::LittleEndian,
+            &TIFFVariant::Classic,
+        )
+        .unwrap();
+
+        assert_eq!(directories.len(), 2);
+    }
+
+    #[test]
+    fn test_read_directories_skips_unreadable_later_ifd() {
+        // Second IFD has no dimensions, so is skipped rather than failing the file.
+        let mut reader = mock_tiff_ifds(&[image(64, 64, 0), vec![(254, 4, 1, longs(&[1]))]], 2);
+
+        let directories = read_directories(
+            &mut reader,
+            ifd_offset(0) as u64,
+            &ByteOrder::LittleEndian,
+            &TIFFVariant::Classic,
+        )
+        .unwrap();
+
+        assert_eq!(directories.len(), 1);
+    }
+
+    #[test]
+    fn test_primary_directory_prefers_full_resolution() {
+        let mut reader = mock_tiff_ifds(
+            &[image(512, 512, 1), image(256, 256, 0), image(256, 256, 4)],
+            3,
+        );
+
+        let directories = read_directories(
+            &mut reader,
+            ifd_offset(0) as u64,
+            &ByteOrder::LittleEndian,
+            &TIFFVariant::Classic,
+        )
+        .unwrap();
+
+        assert_eq!(primary_directory(&directories), Some(1));
+    }
+}
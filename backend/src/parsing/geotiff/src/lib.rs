@@ -1,24 +1,31 @@
+use crate::crs::CRSDefinitions;
 use crate::entry::{EntryValue, IFDEntry};
 use crate::geokeydirectory::GeoKeyDirectory;
+use crate::georef::GeoTransform;
+use crate::ifd::SubfileKind;
 use crate::tfw::parse_tfw;
-use crate::util::FromBytes;
+use crate::util::{ByteOrder, TIFFVariant};
 pub use error::GeoKeyDirectoryErrorState;
 pub use error::HeaderErrorState;
 pub use error::IFDEntryErrorState;
 use error::TIFFErrorState;
 use error::TIFFErrorState::ProjectionError;
-use proj4rs::proj::ProjType;
 use proj4rs::Proj;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::PathBuf;
+use tracing::{event, Level};
 
+mod cog;
+pub mod crs;
 mod entry;
 mod error;
 mod geokeydirectory;
+mod georef;
 mod header;
+mod ifd;
 mod tfw;
 mod util;
 
@@ -33,35 +40,33 @@ pub struct GeoTiffMap {
     pub prj: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone)]
-pub struct GeoTiffRegion {
-    pub top_left: (f64, f64),
-    pub bottom_right: (f64, f64),
-}
-
 #[derive(Debug, Clone)]
 pub struct GeoTiffMetaData {
-    pub region: GeoTiffRegion,
+    // Image corners in the source CRS, clockwise from the top left, to be reprojected by the caller.
+    pub corners: [(f64, f64); 4],
+    pub projection: Proj,
     pub tags: Vec<(String, String)>,
 }
 
 pub fn parse_tiff(
     reader: &mut BufReader<File>,
     tfw_reader: Option<&mut BufReader<File>>,
+    prj_reader: Option<&mut BufReader<File>>,
+    crs: &dyn CRSDefinitions,
 ) -> Result<GeoTiffMetaData, TIFFErrorState> {
-    if tfw_reader.is_some() {
-        parse_tfw(&mut tfw_reader.unwrap());
-    }
-
-    let tags = vec![("Filetype".to_string(), "TIFF".to_string())];
+    let mut tags = vec![("Filetype".to_string(), "TIFF".to_string())];
     // Parse the file header.
-    // First, seek to the start of the file, and validate.
-    // Then read into an 8 byte buffer, and validate.
+    // First, seek to the start of the file, and read the 8 bytes common to both variants.
+    // BigTIFF headers run on for a further 8 bytes, holding a 64 bit IFD offset.
     // Then pass this buffer into parse_header. (unwrap as is using TIFFErrorState <3)
-    let (byte_order, initial_ifd_offset) = match reader.seek(SeekFrom::Start(0)) {
+    let (byte_order, initial_ifd_offset, variant) = match reader.seek(SeekFrom::Start(0)) {
         Ok(_) => {
-            let mut header_buf = [0u8; 8];
-            match reader.read_exact(&mut header_buf) {
+            let mut header_buf = vec![0u8; 8];
+            let result = reader.read_exact(&mut header_buf).and_then(|_| {
+                header_buf.resize(header::header_length(&header_buf), 0);
+                reader.read_exact(&mut header_buf[8..])
+            });
+            match result {
                 Ok(_) => header::parse_header(&header_buf)?,
                 Err(e) => {
                     return Err(TIFFErrorState::UnexpectedFormat(String::from(format!(
@@ -78,201 +83,185 @@ pub fn parse_tiff(
             ))))
         }
     };
+    if variant == TIFFVariant::BigTIFF {
+        tags.push(("BigTIFF".to_string(), "true".to_string()));
+    }
 
-    // Parse the IFD header; (Get entry count)
-    // First, seek to start of header.
-    // Then read into 2 byte buffer, and validate.
-    // Then parse this buf as a u16, and and return.
-    let entry_count = match reader.seek(initial_ifd_offset) {
-        Ok(_) => {
-            let mut entry_count_buf = [0u8; 2];
-            match reader.read_exact(&mut entry_count_buf) {
-                Ok(_) => u16::from_bytes(&entry_count_buf, &byte_order),
-                Err(e) => {
-                    return Err(TIFFErrorState::UnexpectedFormat(String::from(format!(
-                        "Read of IFD entry count failed: {:?}",
-                        e
-                    ))))
-                }
-            }
-        }
-        Err(e) => {
-            return Err(TIFFErrorState::UnexpectedFormat(String::from(format!(
-                "Seek to IFD failed: {:?}",
-                e
-            ))))
-        }
+    let SeekFrom::Start(initial_ifd_offset) = initial_ifd_offset else {
+        return Err(TIFFErrorState::UnexpectedFormat(String::from(
+            "Expected IFD offset from the start of the file!",
+        )));
     };
+    let mut directories = ifd::read_directories(reader, initial_ifd_offset, &byte_order, &variant)?;
+    tags.push(("IFDCount".to_string(), directories.len().to_string()));
+
+    let overviews: Vec<String> = directories
+        .iter()
+        .filter(|d| d.kind == SubfileKind::ReducedResolution)
+        .map(|d| d.dimensions())
+        .collect();
+    tags.push(("Overviews".to_string(), overviews.len().to_string()));
+    let sub_ifds = directories.iter().filter(|d| d.sub_ifd).count();
+    if sub_ifds > 0 {
+        tags.push(("SubIFDs".to_string(), sub_ifds.to_string()));
+    }
+    for dimensions in overviews {
+        tags.push(("OverviewDimensions".to_string(), dimensions));
+    }
 
-    // Init hashmap for entries.
-    let mut entries: HashMap<u16, IFDEntry> = HashMap::with_capacity(entry_count as usize);
-
-    for entry_number in 0..entry_count {
-        let mut entry_buf = [0u8; 12];
-        match reader.read_exact(&mut entry_buf) {
-            Ok(_) => {
-                let entry = IFDEntry::new(&entry_buf, &byte_order)?;
-                entries.insert(entry.tag, entry);
-            }
-            Err(e) => {
-                return Err(TIFFErrorState::UnexpectedFormat(String::from(format!(
-                    "Expected IFD Entry #{}, could not read, due to {:?}",
-                    entry_number, e
-                ))))
-            }
-        }
+    // Georeference from the main image, which is not necessarily the first IFD.
+    let Some(primary) = ifd::primary_directory(&directories) else {
+        return Err(TIFFErrorState::UnexpectedFormat(String::from(
+            "Expected at least one IFD!",
+        )));
+    };
+    if primary != 0 {
+        tags.push(("PrimaryIFD".to_string(), primary.to_string()));
     }
-    // Read next 4 bytes for next ifd if you care
-    // TODO: Implement support for multiple IFDs.
-    // println!("Entries: {:?}", entries);
 
-    let geo_key_directory = match entries.get_mut(&34735) {
-        Some(v) => {
-            if let EntryValue::SHORT(v) = v.resolve(&byte_order, reader)? {
-                v
-            } else {
-                return Err(TIFFErrorState::UnexpectedFormat(String::from(
-                    "Expected GeoKeyDirectory to be of type SHORT!",
-                )));
-            }
+    let cog_reasons = cog::validate_cog(&mut directories, primary, &byte_order, &variant, reader);
+    tags.push(("COG".to_string(), cog_reasons.is_empty().to_string()));
+    for reason in cog_reasons {
+        tags.push(("COGReason".to_string(), reason));
+    }
+    let directory = &mut directories[primary];
+    tags.push(("Dimensions".to_string(), directory.dimensions()));
+    let (x, y) = (directory.width, directory.height);
+    let entries = &mut directory.entries;
+
+    // Prefer the GeoKeyDirectory for the CRS, falling back to the .prj sidecar.
+    let geo_key_projection = match resolve_geo_keys(entries, &byte_order, reader)? {
+        Some(Ok(directory)) => {
+            tags.extend(directory.tags());
+            Some(directory.get_projection(crs))
         }
-        None => return Err(TIFFErrorState::NotEnoughGeoData),
+        Some(Err(e)) => Some(Err(e)),
+        None => None,
     };
 
-    let geo_key_directory = GeoKeyDirectory::from_shorts(geo_key_directory)?;
-
-    // println!("GeoKeyDirectory: {:?}", geo_key_directory);
-
-    // let projection = geo_key_directory.get_projection("EPSG:4326")?;
-    let projection = geo_key_directory.get_projection()?;
+    let projection = match (geo_key_projection, prj_reader) {
+        (Some(Ok(projection)), _) => {
+            tags.push(("CRSSource".to_string(), "GeoKeys".to_string()));
+            projection
+        }
+        (_, Some(prj_reader)) => {
+            tags.push(("CRSSource".to_string(), "prj".to_string()));
+            let (projection, label) = projection_from_prj(prj_reader, crs)?;
+            tags.push(("CRS".to_string(), label));
+            projection
+        }
+        (Some(Err(e)), None) => return Err(e),
+        (None, None) => return Err(TIFFErrorState::NotEnoughGeoData),
+    };
 
-    let top_left = match entries.get_mut(&33922) {
-        None => return Err(TIFFErrorState::NotEnoughGeoData),
-        Some(v) => {
-            if let EntryValue::DOUBLE(v) = v.resolve(&byte_order, reader)? {
-                if let (Some(x), Some(y)) = (v.get(3), v.get(4)) {
-                    (x.clone(), y.clone())
-                } else {
-                    return Err(TIFFErrorState::NotEnoughGeoData);
-                }
-            } else {
-                return Err(TIFFErrorState::UnexpectedFormat(String::from(
-                    "Expected ModelTiePoint to be of type DOUBLE!",
-                )));
-            }
+    // Georeference in order of precedence; ModelTransformation, ModelTiepoint(s), then world file.
+    let transformation = resolve_doubles(entries, 34264, &byte_order, reader)?;
+    let tiepoints = resolve_doubles(entries, 33922, &byte_order, reader)?;
+    let scale = resolve_doubles(entries, 33550, &byte_order, reader)?;
+
+    let embedded = match (transformation, tiepoints, scale) {
+        (Some(matrix), _, _) => Some((GeoTransform::from_matrix(&matrix)?, "ModelTransformation")),
+        (None, Some(tiepoints), Some(scale)) => Some((
+            GeoTransform::from_tiepoint_and_scale(&tiepoints, &scale)?,
+            "ModelTiepoint",
+        )),
+        // Under three tiepoints without a scale can't place the image, leave that to a world file if present.
+        (None, Some(tiepoints), None) if tiepoints.len() < 18 && tfw_reader.is_some() => None,
+        (None, Some(tiepoints), None) => {
+            Some((GeoTransform::from_tiepoints(&tiepoints)?, "ModelTiepoints"))
         }
+        (None, None, _) => None,
     };
-    let scale = match entries.get_mut(&33550) {
-        None => return Err(TIFFErrorState::NotEnoughGeoData),
-        Some(v) => {
-            if let EntryValue::DOUBLE(v) = v.resolve(&byte_order, reader)? {
-                if let (Some(x), Some(y)) = (v.get(0), v.get(1)) {
-                    (x.clone(), y.clone())
-                } else {
-                    return Err(TIFFErrorState::NotEnoughGeoData);
-                }
-            } else {
-                return Err(TIFFErrorState::UnexpectedFormat(String::from(
-                    "Expected ModelTiePoint to be of type DOUBLE!",
-                )));
+    // The world file is only used without embedded tags, a broken one alongside them is just logged.
+    let (transform, method) = match (embedded, tfw_reader) {
+        (Some((transform, method)), tfw_reader) => {
+            if let Some(Err(e)) = tfw_reader.map(parse_tfw) {
+                event!(
+                    Level::WARN,
+                    "Ignoring world file, as the TIFF is georeferenced by {method}. Reason: {e}"
+                );
             }
+            (transform, method)
         }
+        (None, Some(tfw_reader)) => (GeoTransform::from(&parse_tfw(tfw_reader)?), "WorldFile"),
+        (None, None) => return Err(TIFFErrorState::NotEnoughGeoData),
     };
-    let x = if let Some(entry) = entries.get_mut(&256) {
-        // Resolve the entry to a specific value (like width).
-        if let EntryValue::SHORT(v) = entry.resolve(&byte_order, reader)? {
-            if let Some(x) = v.get(0) {
-                x.clone()
+    tags.push(("Georeferencing".to_string(), method.to_string()));
+
+    Ok(GeoTiffMetaData {
+        corners: transform.corners(x as f64, y as f64),
+        projection,
+        tags,
+    })
+}
+
+// Reads the GeoKeyDirectory, along with the GeoDoubleParams and GeoAsciiParams its keys may index into.
+fn resolve_geo_keys(
+    entries: &mut HashMap<u16, IFDEntry>,
+    byte_order: &ByteOrder,
+    reader: &mut BufReader<File>,
+) -> Result<Option<Result<GeoKeyDirectory, TIFFErrorState>>, TIFFErrorState> {
+    let shorts = match entries.get_mut(&34735) {
+        Some(v) => {
+            if let EntryValue::SHORT(v) = v.resolve(byte_order, reader)? {
+                v.clone()
             } else {
                 return Err(TIFFErrorState::UnexpectedFormat(String::from(
-                    "Expected ImageWidth!",
+                    "Expected GeoKeyDirectory to be of type SHORT!",
                 )));
             }
-        } else {
-            return Err(TIFFErrorState::UnexpectedFormat(String::from(
-                "Expected ImageWidth to be of type SHORT!",
-            )));
         }
-    } else {
-        // If the entry for ImageWidth does not exist, return an error.
-        return Err(TIFFErrorState::UnexpectedFormat(String::from(
-            "Expected ImageWidth!",
-        )));
+        None => return Ok(None),
     };
-
-    let y = if let Some(entry) = entries.get_mut(&257) {
-        // Resolve the entry to a specific value (like width).
-        if let EntryValue::SHORT(v) = entry.resolve(&byte_order, reader)? {
-            if let Some(y) = v.get(0) {
-                y.clone()
-            } else {
+    let doubles = resolve_doubles(entries, 34736, byte_order, reader)?;
+    let ascii = match entries.get_mut(&34737) {
+        Some(v) => match v.resolve(byte_order, reader)? {
+            EntryValue::ASCII(v) => v.first().cloned(),
+            _ => {
                 return Err(TIFFErrorState::UnexpectedFormat(String::from(
-                    "Expected ImageLength!",
-                )));
+                    "Expected GeoAsciiParams to be of type ASCII!",
+                )))
             }
-        } else {
-            return Err(TIFFErrorState::UnexpectedFormat(String::from(
-                "Expected ImageLength to be of type SHORT!",
-            )));
-        }
-    } else {
-        // If the entry for ImageWidth does not exist, return an error.
-        return Err(TIFFErrorState::UnexpectedFormat(String::from(
-            "Expected ImageLength!",
-        )));
+        },
+        None => None,
     };
-
-    let region = calculate_extent(top_left, scale, (x, y), projection)?;
-
-    return Ok(GeoTiffMetaData { region, tags });
+    Ok(Some(
+        GeoKeyDirectory::from_shorts(&shorts).map(|d| d.with_params(doubles, ascii)),
+    ))
 }
 
-fn calculate_extent(
-    top_left: (f64, f64),
-    scale: (f64, f64),
-    image_dimensions: (u16, u16),
-    from_proj: Proj,
-) -> Result<GeoTiffRegion, TIFFErrorState> {
-    // Initialize the Proj struct with the known CRS (Coordinate Reference System)
-    let to_proj = Proj::from_proj_string(crs_definitions::EPSG_4326.proj4)
-        .expect("FAILED TO BUILD DEFAULT PROJ!");
-
-    let mut top_left = top_left.clone();
-
-    // Calculate the bottom-right coordinates in the image's CRS
-    let mut bottom_right = (
-        top_left.0 + (scale.0 * image_dimensions.0 as f64),
-        top_left.1 - (scale.1 * image_dimensions.1 as f64), // subtract because pixel scale is usually positive as you go down
-    );
-
-    let (mut top_left_r, mut bottom_right_r) = match from_proj.projection_type() {
-        ProjType::Latlong => (
-            (top_left.0.to_radians(), top_left.1.to_radians()),
-            (bottom_right.0.to_radians(), bottom_right.1.to_radians()),
-        ),
-        ProjType::Other => (top_left, bottom_right),
-        ProjType::Geocentric => {
-            return Err(ProjectionError(format!("Unsupported projection! From GEOCENTRIC! Please contact developer, and send file content for implementation.")));
-        }
-    };
-
-    if let Err(e) = proj4rs::transform::transform(&from_proj, &to_proj, &mut top_left_r) {
-        return Err(ProjectionError(format!("Failed to apply tranformation for {from_proj:?} to {to_proj:?}, for points: {top_left:?}, with reason {e:?}")));
-    } else {
-    };
-    if let Err(e) = proj4rs::transform::transform(&from_proj, &to_proj, &mut bottom_right_r) {
-        return Err(ProjectionError(format!("Failed to apply tranformation for {from_proj:?} to {to_proj:?}, for points: {bottom_right:?}, with reason {e:?}")));
-    } else {
-    };
+// Resolves a DOUBLE entry if present, erroring if it is of any other type.
+fn resolve_doubles(
+    entries: &mut HashMap<u16, IFDEntry>,
+    tag: u16,
+    byte_order: &ByteOrder,
+    reader: &mut BufReader<File>,
+) -> Result<Option<Vec<f64>>, TIFFErrorState> {
+    match entries.get_mut(&tag) {
+        None => Ok(None),
+        Some(entry) => match entry.resolve(byte_order, reader)? {
+            EntryValue::DOUBLE(v) => Ok(Some(v.clone())),
+            _ => Err(TIFFErrorState::UnexpectedFormat(format!(
+                "Expected tag {tag} to be of type DOUBLE!"
+            ))),
+        },
+    }
+}
 
-    return Ok(GeoTiffRegion {
-        top_left: (top_left_r.0.to_degrees(), top_left_r.1.to_degrees()),
-        bottom_right: (bottom_right_r.0.to_degrees(), bottom_right_r.1.to_degrees()),
-    });
+// Builds a projection from the WKT held within a .prj sidecar, along with a label for the CRS.
+fn projection_from_prj(
+    prj_reader: &mut BufReader<File>,
+    crs: &dyn CRSDefinitions,
+) -> Result<(Proj, String), TIFFErrorState> {
+    let mut wkt = String::new();
+    prj_reader
+        .read_to_string(&mut wkt)
+        .map_err(|e| ProjectionError(format!("Failed to read .prj file: {e:?}")))?;
+    crs.projection_from_wkt(&wkt).map_err(ProjectionError)
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
     use std::fs::File;
@@ -370,11 +359,489 @@ mod tests {
             .expect("Failed to seek to start of file");
 
         let mut reader = BufReader::new(file);
-        let tfw_reader = None;
-        let result = parse_tiff(&mut reader, tfw_reader);
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS);
         assert!(
             matches!(result, Err(TIFFErrorState::UnexpectedFormat(_))),
             "Expected an error due to incomplete header"
         );
     }
+
+    pub(crate) type MockEntry = (u16, u16, u32, Vec<u8>);
+
+    // Each mock IFD gets a fixed size slot, so tests can reference IFDs by offset.
+    // The IFDs follow space reserved for a ghost area, directly after the header.
+    pub(crate) const IFD_SLOT: usize = 1024;
+    pub(crate) const GHOST_AREA: usize = 256;
+
+    pub(crate) fn ifd_offset(index: usize) -> u32 {
+        (8 + GHOST_AREA + index * IFD_SLOT) as u32
+    }
+
+    // Builds a little endian classic TIFF, holding a single IFD of entries (tag, type, count, value bytes).
+    // Values over 4 bytes are written after the IFD, and referenced by offset.
+    fn mock_tiff(entries: &[MockEntry]) -> BufReader<File> {
+        mock_tiff_ifds(&[entries.to_vec()], 1)
+    }
+
+    // As mock_tiff, but with several IFDs. The first `linked` form the main IFD chain,
+    // with the rest unlinked, so only reachable through SubIFDs.
+    pub(crate) fn mock_tiff_ifds(ifds: &[Vec<MockEntry>], linked: usize) -> BufReader<File> {
+        mock_tiff_ifds_with_ghost("", ifds, linked)
+    }
+
+    // As mock_tiff_ifds, with the ghost area holding the given text.
+    pub(crate) fn mock_tiff_ifds_with_ghost(
+        ghost: &str,
+        ifds: &[Vec<MockEntry>],
+        linked: usize,
+    ) -> BufReader<File> {
+        let mut data = vec![0x49, 0x49, 0x2A, 0x00];
+        data.write_u32::<LittleEndian>(ifd_offset(0)).unwrap();
+        data.extend_from_slice(ghost.as_bytes());
+        data.resize(ifd_offset(0) as usize, 0);
+        for (index, entries) in ifds.iter().enumerate() {
+            let mut values = Vec::new();
+            let values_offset = ifd_offset(index) as usize + 2 + entries.len() * 12 + 4;
+            data.write_u16::<LittleEndian>(entries.len() as u16)
+                .unwrap();
+            for (tag, field_type, count, value) in entries {
+                data.write_u16::<LittleEndian>(*tag).unwrap();
+                data.write_u16::<LittleEndian>(*field_type).unwrap();
+                data.write_u32::<LittleEndian>(*count).unwrap();
+                if value.len() > 4 {
+                    data.write_u32::<LittleEndian>((values_offset + values.len()) as u32)
+                        .unwrap();
+                    values.extend_from_slice(value);
+                } else {
+                    let mut inline = value.clone();
+                    inline.resize(4, 0);
+                    data.extend_from_slice(&inline);
+                }
+            }
+            let next = if index + 1 < linked {
+                ifd_offset(index + 1)
+            } else {
+                0
+            };
+            data.write_u32::<LittleEndian>(next).unwrap();
+            data.extend_from_slice(&values);
+            assert!(data.len() <= ifd_offset(index + 1) as usize);
+            data.resize(ifd_offset(index + 1) as usize, 0);
+        }
+
+        let mut file = tempfile().unwrap();
+        file.write_all(&data).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        BufReader::new(file)
+    }
+
+    pub(crate) fn shorts(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn doubles(values: &[f64]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    // Only the definitions built into crs_definitions, standing in for the backend's registry.
+    pub(crate) struct BuiltinCRS;
+
+    impl CRSDefinitions for BuiltinCRS {
+        fn proj_string(&self, code: u32) -> Option<String> {
+            u16::try_from(code)
+                .ok()
+                .and_then(crs_definitions::from_code)
+                .map(|def| def.proj4.to_string())
+        }
+
+        fn projection_from_wkt(&self, wkt: &str) -> Result<(Proj, String), String> {
+            let proj_string = proj4wkt::wkt_to_projstring(wkt).map_err(|e| format!("{e:?}"))?;
+            let projection = Proj::from_proj_string(&proj_string).map_err(|e| format!("{e:?}"))?;
+            Ok((projection, "WKT".to_string()))
+        }
+    }
+
+    // Bounding box of the corners, as top left and bottom right, in the source CRS.
+    fn envelope(result: &GeoTiffMetaData) -> ((f64, f64), (f64, f64)) {
+        let (xs, ys): (Vec<f64>, Vec<f64>) = result.corners.iter().copied().unzip();
+        let min = |v: &[f64]| v.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = |v: &[f64]| v.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        ((min(&xs), max(&ys)), (max(&xs), min(&ys)))
+    }
+
+    fn mock_sidecar(content: &str) -> BufReader<File> {
+        let mut file = tempfile().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        BufReader::new(file)
+    }
+
+    #[test]
+    fn test_parse_tiff_with_world_file_and_prj() {
+        let mut reader = mock_tiff(&[(256, 3, 1, shorts(&[10])), (257, 3, 1, shorts(&[5]))]);
+        let mut tfw = mock_sidecar("0.1\n0.0\n0.0\n-0.1\n-1.95\n52.95\n");
+        let mut prj = mock_sidecar(crs_definitions::EPSG_4326.wkt);
+
+        let result = parse_tiff(&mut reader, Some(&mut tfw), Some(&mut prj), &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).0 .0 - -2.0).abs() < 1e-9);
+        assert!((envelope(&result).0 .1 - 53.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .0 - -1.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 52.5).abs() < 1e-9);
+        assert!(result
+            .tags
+            .contains(&("Georeferencing".to_string(), "WorldFile".to_string())));
+        assert!(result
+            .tags
+            .contains(&("CRSSource".to_string(), "prj".to_string())));
+    }
+
+    #[test]
+    fn test_parse_tiff_with_projected_world_file() {
+        let mut reader = mock_tiff(&[(256, 3, 1, shorts(&[100])), (257, 3, 1, shorts(&[100]))]);
+        // 100m pixels in British National Grid, with a slight rotation.
+        let mut tfw = mock_sidecar("100\n5\n5\n-100\n450050\n209950\n");
+        let mut prj = mock_sidecar(crs_definitions::EPSG_27700.wkt);
+
+        let result = parse_tiff(&mut reader, Some(&mut tfw), Some(&mut prj), &BuiltinCRS).unwrap();
+
+        // Corners stay in British National Grid, shifted out by half a (rotated) pixel.
+        let (left, top) = result.corners[0];
+        assert!((left - 449997.5).abs() < 1e-6, "left: {left}");
+        assert!((top - 209997.5).abs() < 1e-6, "top: {top}");
+        assert!(matches!(
+            result.projection.projection_type(),
+            proj4rs::proj::ProjType::Other
+        ));
+    }
+
+    #[test]
+    fn test_parse_tiff_world_file_without_crs() {
+        let mut reader = mock_tiff(&[(256, 3, 1, shorts(&[10])), (257, 3, 1, shorts(&[5]))]);
+        let mut tfw = mock_sidecar("0.1\n0.0\n0.0\n-0.1\n-1.95\n52.95\n");
+
+        let result = parse_tiff(&mut reader, Some(&mut tfw), None, &BuiltinCRS);
+
+        assert!(matches!(result, Err(TIFFErrorState::NotEnoughGeoData)));
+    }
+
+    // GeoKeyDirectory declaring EPSG:4326.
+    fn wgs84_geokeys() -> (u16, u16, u32, Vec<u8>) {
+        (34735, 3, 8, shorts(&[1, 1, 0, 1, 2048, 0, 1, 4326]))
+    }
+
+    #[test]
+    fn test_parse_tiff_with_model_transformation() {
+        // Rotated 90 degrees, columns run south and rows run west.
+        #[rustfmt::skip]
+        let matrix = doubles(&[
+            0.0, -0.1, 0.0, 10.0,
+            -0.1, 0.0, 0.0, 50.0,
+            0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ]);
+        let mut reader = mock_tiff(&[
+            (256, 3, 1, shorts(&[20])),
+            (257, 3, 1, shorts(&[10])),
+            (34264, 12, 16, matrix),
+            wgs84_geokeys(),
+        ]);
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).0 .0 - 9.0).abs() < 1e-9);
+        assert!((envelope(&result).0 .1 - 50.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .0 - 10.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 48.0).abs() < 1e-9);
+        assert!(result.tags.contains(&(
+            "Georeferencing".to_string(),
+            "ModelTransformation".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_parse_tiff_with_multiple_tiepoints() {
+        // Four corner tiepoints of a 10x10 image, sheared eastwards as rows go down.
+        #[rustfmt::skip]
+        let tiepoints = doubles(&[
+            0.0, 0.0, 0.0, 0.0, 10.0, 0.0,
+            10.0, 0.0, 0.0, 1.0, 10.0, 0.0,
+            0.0, 10.0, 0.0, 0.5, 9.0, 0.0,
+            10.0, 10.0, 0.0, 1.5, 9.0, 0.0,
+        ]);
+        let mut reader = mock_tiff(&[
+            (256, 3, 1, shorts(&[10])),
+            (257, 3, 1, shorts(&[10])),
+            (33922, 12, 24, tiepoints),
+            wgs84_geokeys(),
+        ]);
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).0 .0 - 0.0).abs() < 1e-9);
+        assert!((envelope(&result).0 .1 - 10.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .0 - 1.5).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 9.0).abs() < 1e-9);
+        assert!(result
+            .tags
+            .contains(&("Georeferencing".to_string(), "ModelTiepoints".to_string())));
+
+        // The corners follow the shear, so the bottom left is east of the top left.
+        assert_eq!(result.corners[3], (0.5, 9.0));
+    }
+
+    #[test]
+    fn test_parse_tiff_with_tiepoint_ignores_broken_world_file() {
+        let mut reader = mock_tiff(&[
+            (256, 3, 1, shorts(&[10])),
+            (257, 3, 1, shorts(&[20])),
+            (33550, 12, 3, doubles(&[0.5, 0.25, 0.0])),
+            (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, -5.0, 55.0, 0.0])),
+            wgs84_geokeys(),
+        ]);
+        let mut tfw = mock_sidecar("not\na\nworld\nfile\n");
+
+        let result = parse_tiff(&mut reader, Some(&mut tfw), None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).0 .0 - -5.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 50.0).abs() < 1e-9);
+        assert!(result
+            .tags
+            .contains(&("Georeferencing".to_string(), "ModelTiepoint".to_string())));
+    }
+
+    #[test]
+    fn test_parse_tiff_with_single_tiepoint_and_world_file() {
+        let mut reader = mock_tiff(&[
+            (256, 3, 1, shorts(&[10])),
+            (257, 3, 1, shorts(&[5])),
+            (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, -2.0, 53.0, 0.0])),
+            wgs84_geokeys(),
+        ]);
+        let mut tfw = mock_sidecar("0.1\n0.0\n0.0\n-0.1\n-1.95\n52.95\n");
+
+        let result = parse_tiff(&mut reader, Some(&mut tfw), None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).0 .0 - -2.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 52.5).abs() < 1e-9);
+        assert!(result
+            .tags
+            .contains(&("Georeferencing".to_string(), "WorldFile".to_string())));
+
+        // Without the world file, the lone tiepoint isn't enough.
+        let mut reader = mock_tiff(&[
+            (256, 3, 1, shorts(&[10])),
+            (257, 3, 1, shorts(&[5])),
+            (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, -2.0, 53.0, 0.0])),
+            wgs84_geokeys(),
+        ]);
+        assert!(parse_tiff(&mut reader, None, None, &BuiltinCRS).is_err());
+    }
+
+    #[test]
+    fn test_parse_tiff_with_tiepoint_and_scale() {
+        let mut reader = mock_tiff(&[
+            (256, 3, 1, shorts(&[10])),
+            (257, 3, 1, shorts(&[20])),
+            (33550, 12, 3, doubles(&[0.5, 0.25, 0.0])),
+            (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, -5.0, 55.0, 0.0])),
+            wgs84_geokeys(),
+        ]);
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).0 .0 - -5.0).abs() < 1e-9);
+        assert!((envelope(&result).0 .1 - 55.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .0 - 0.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 50.0).abs() < 1e-9);
+    }
+
+    pub(crate) fn longs(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    // BigTIFF equivalent of mock_tiff, 20 byte entries with values over 8 bytes referenced by offset.
+    fn mock_bigtiff(entries: &[(u16, u16, u64, Vec<u8>)]) -> BufReader<File> {
+        let mut data = vec![0x49, 0x49, 0x2B, 0x00, 0x08, 0x00, 0x00, 0x00];
+        data.write_u64::<LittleEndian>(16).unwrap();
+        let mut values = Vec::new();
+        let values_offset = 16 + 8 + entries.len() * 20 + 8;
+        data.write_u64::<LittleEndian>(entries.len() as u64)
+            .unwrap();
+        for (tag, field_type, count, value) in entries {
+            data.write_u16::<LittleEndian>(*tag).unwrap();
+            data.write_u16::<LittleEndian>(*field_type).unwrap();
+            data.write_u64::<LittleEndian>(*count).unwrap();
+            if value.len() > 8 {
+                data.write_u64::<LittleEndian>((values_offset + values.len()) as u64)
+                    .unwrap();
+                values.extend_from_slice(value);
+            } else {
+                let mut inline = value.clone();
+                inline.resize(8, 0);
+                data.extend_from_slice(&inline);
+            }
+        }
+        data.write_u64::<LittleEndian>(0).unwrap();
+        data.extend_from_slice(&values);
+
+        let mut file = tempfile().unwrap();
+        file.write_all(&data).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        BufReader::new(file)
+    }
+
+    #[test]
+    fn test_parse_tiff_with_long_dimensions() {
+        let mut reader = mock_tiff(&[
+            (256, 4, 1, longs(&[100_000])),
+            (257, 4, 1, longs(&[50_000])),
+            (33550, 12, 3, doubles(&[0.0001, 0.0001, 0.0])),
+            (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, 0.0, 10.0, 0.0])),
+            wgs84_geokeys(),
+        ]);
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).1 .0 - 10.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_tiff_with_rational_dimensions() {
+        let mut reader = mock_tiff(&[(256, 5, 1, longs(&[1, 1])), (257, 3, 1, shorts(&[5]))]);
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS);
+
+        assert!(matches!(result, Err(TIFFErrorState::UnexpectedFormat(_))));
+    }
+
+    #[test]
+    fn test_parse_bigtiff() {
+        let (_, _, _, geokeys) = wgs84_geokeys();
+        let mut reader = mock_bigtiff(&[
+            (256, 16, 1, 200_000u64.to_le_bytes().to_vec()),
+            (257, 4, 1, longs(&[100_000])),
+            (33550, 12, 3, doubles(&[0.0001, 0.0001, 0.0])),
+            (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, 0.0, 10.0, 0.0])),
+            (34735, 3, 8, geokeys),
+        ]);
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).0 .0 - 0.0).abs() < 1e-9);
+        assert!((envelope(&result).0 .1 - 10.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .0 - 20.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 0.0).abs() < 1e-9);
+        assert!(result
+            .tags
+            .contains(&("BigTIFF".to_string(), "true".to_string())));
+    }
+
+    #[test]
+    fn test_parse_tiff_with_leading_thumbnail() {
+        // A reduced resolution thumbnail first, followed by the georeferenced full image.
+        let mut reader = mock_tiff_ifds(
+            &[
+                vec![
+                    (254, 4, 1, longs(&[1])),
+                    (256, 3, 1, shorts(&[16])),
+                    (257, 3, 1, shorts(&[8])),
+                ],
+                vec![
+                    (256, 3, 1, shorts(&[200])),
+                    (257, 3, 1, shorts(&[100])),
+                    (33550, 12, 3, doubles(&[0.01, 0.01, 0.0])),
+                    (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, 0.0, 1.0, 0.0])),
+                    wgs84_geokeys(),
+                ],
+            ],
+            2,
+        );
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).1 .0 - 2.0).abs() < 1e-9);
+        assert!((envelope(&result).1 .1 - 0.0).abs() < 1e-9);
+        assert!(result
+            .tags
+            .contains(&("PrimaryIFD".to_string(), "1".to_string())));
+        assert!(result
+            .tags
+            .contains(&("Dimensions".to_string(), "200x100".to_string())));
+        assert!(result
+            .tags
+            .contains(&("OverviewDimensions".to_string(), "16x8".to_string())));
+    }
+
+    #[test]
+    fn test_parse_tiff_with_overviews() {
+        let mut reader = mock_tiff_ifds(
+            &[
+                vec![
+                    (256, 3, 1, shorts(&[400])),
+                    (257, 3, 1, shorts(&[200])),
+                    (33550, 12, 3, doubles(&[0.01, 0.01, 0.0])),
+                    (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, 0.0, 2.0, 0.0])),
+                    wgs84_geokeys(),
+                ],
+                vec![
+                    (254, 4, 1, longs(&[1])),
+                    (256, 3, 1, shorts(&[200])),
+                    (257, 3, 1, shorts(&[100])),
+                ],
+                vec![
+                    (254, 4, 1, longs(&[1])),
+                    (256, 3, 1, shorts(&[100])),
+                    (257, 3, 1, shorts(&[50])),
+                ],
+                // Mask of the full resolution image, not an overview.
+                vec![
+                    (254, 4, 1, longs(&[4])),
+                    (256, 3, 1, shorts(&[400])),
+                    (257, 3, 1, shorts(&[200])),
+                ],
+            ],
+            4,
+        );
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS).unwrap();
+
+        assert!((envelope(&result).1 .0 - 4.0).abs() < 1e-9);
+        assert!(result
+            .tags
+            .contains(&("IFDCount".to_string(), "4".to_string())));
+        assert!(result
+            .tags
+            .contains(&("Overviews".to_string(), "2".to_string())));
+        let overviews: Vec<&String> = result
+            .tags
+            .iter()
+            .filter(|(k, _)| k == "OverviewDimensions")
+            .map(|(_, v)| v)
+            .collect();
+        assert_eq!(overviews, vec!["200x100", "100x50"]);
+        assert!(!result.tags.iter().any(|(k, _)| k == "PrimaryIFD"));
+    }
+
+    #[test]
+    fn test_parse_tiff_not_cog() {
+        let mut reader = mock_tiff(&[
+            (256, 3, 1, shorts(&[10])),
+            (257, 3, 1, shorts(&[20])),
+            (33550, 12, 3, doubles(&[0.5, 0.25, 0.0])),
+            (33922, 12, 6, doubles(&[0.0, 0.0, 0.0, -5.0, 55.0, 0.0])),
+            wgs84_geokeys(),
+        ]);
+
+        let result = parse_tiff(&mut reader, None, None, &BuiltinCRS).unwrap();
+
+        assert!(result
+            .tags
+            .contains(&("COG".to_string(), "false".to_string())));
+        assert!(result.tags.contains(&(
+            "COGReason".to_string(),
+            "IFD 0 (10x20) is not tiled".to_string()
+        )));
+    }
 }
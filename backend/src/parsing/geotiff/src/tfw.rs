@@ -1,18 +1,116 @@
-use crate::error::TIFFErrorState::NotEnoughGeoData;
+use crate::error::TIFFErrorState;
 use serde::{Deserialize, Serialize};
-use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TFWData {}
-pub fn parse_tfw(reader: &mut BufReader<File>) -> Result<TFWData, Box<dyn Error>> {
-    let lines = reader.lines().take(6);
-    let strings: Result<Vec<String>, _> = lines.collect();
-    let strings: Vec<String> = strings?;
-    eprintln!("Failed to parse tiff file with accompanying sidecar file!");
-    eprintln!("This is unstable feature; not currently fully implemented.");
-    eprintln!("TFW Parsing Implementation as of current moment incomplete.");
-    eprintln!("Ignoring file!");
-    return Err(NotEnoughGeoData.into());
+// World file parameters, in file order.
+// Maps pixel centres (col, row) to model space with:
+//   x = pixel_size_x * col + rotation_x * row + origin_x
+//   y = rotation_y * col + pixel_size_y * row + origin_y
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TFWData {
+    pub pixel_size_x: f64,
+    pub rotation_y: f64,
+    pub rotation_x: f64,
+    pub pixel_size_y: f64,
+    pub origin_x: f64,
+    pub origin_y: f64,
+}
+
+impl TFWData {
+    pub fn from_lines(lines: &[String]) -> Result<TFWData, TIFFErrorState> {
+        let values = lines
+            .iter()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| {
+                l.parse::<f64>().map_err(|e| {
+                    TIFFErrorState::UnexpectedFormat(format!(
+                        "Failed to parse world file parameter: {l:?}, with err: {e:?}"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<f64>, _>>()?;
+
+        if values.len() != 6 {
+            return Err(TIFFErrorState::UnexpectedFormat(format!(
+                "Expected 6 world file parameters, got {}",
+                values.len()
+            )));
+        }
+        if values.iter().any(|v| !v.is_finite()) {
+            return Err(TIFFErrorState::UnexpectedFormat(format!(
+                "World file parameters must be finite, got {values:?}"
+            )));
+        }
+
+        Ok(TFWData {
+            pixel_size_x: values[0],
+            rotation_y: values[1],
+            rotation_x: values[2],
+            pixel_size_y: values[3],
+            origin_x: values[4],
+            origin_y: values[5],
+        })
+    }
+
+    // Applies the affine transform to a pixel coordinate.
+    pub fn transform(&self, col: f64, row: f64) -> (f64, f64) {
+        (
+            self.pixel_size_x * col + self.rotation_x * row + self.origin_x,
+            self.rotation_y * col + self.pixel_size_y * row + self.origin_y,
+        )
+    }
+}
+
+pub fn parse_tfw(reader: &mut BufReader<File>) -> Result<TFWData, TIFFErrorState> {
+    let lines = reader
+        .lines()
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| {
+            TIFFErrorState::UnexpectedFormat(format!("Failed to read world file: {e:?}"))
+        })?;
+    TFWData::from_lines(&lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom, Write};
+    use tempfile::tempfile;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_from_lines_valid() {
+        let tfw = TFWData::from_lines(&lines("2.0\n0.0\n0.0\n-2.0\n100.0\n200.0\n")).unwrap();
+        assert_eq!(tfw.pixel_size_x, 2.0);
+        assert_eq!(tfw.pixel_size_y, -2.0);
+        assert_eq!(tfw.origin_x, 100.0);
+        assert_eq!(tfw.origin_y, 200.0);
+    }
+
+    #[test]
+    fn test_from_lines_wrong_count() {
+        let result = TFWData::from_lines(&lines("2.0\n0.0\n0.0\n-2.0\n100.0\n"));
+        assert!(matches!(result, Err(TIFFErrorState::UnexpectedFormat(_))));
+    }
+
+    #[test]
+    fn test_from_lines_unparsable() {
+        let result = TFWData::from_lines(&lines("2.0\n0.0\nabc\n-2.0\n100.0\n200.0"));
+        assert!(matches!(result, Err(TIFFErrorState::UnexpectedFormat(_))));
+    }
+
+    #[test]
+    fn test_parse_tfw_file() {
+        let mut file = tempfile().unwrap();
+        file.write_all(b"0.5\r\n0.0\r\n0.0\r\n-0.5\r\n-2.0\r\n52.0\r\n")
+            .unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let tfw = parse_tfw(&mut BufReader::new(file)).unwrap();
+        assert_eq!(tfw.transform(0.0, 0.0), (-2.0, 52.0));
+    }
 }
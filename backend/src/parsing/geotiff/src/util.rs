@@ -5,8 +5,40 @@ pub enum ByteOrder {
     BigEndian,
 }
 
+// Classic TIFF uses 32 bit offsets, BigTIFF widens offsets and counts to 64 bits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TIFFVariant {
+    Classic,
+    BigTIFF,
+}
+
+impl TIFFVariant {
+    // Size of an offset, and of the value field within an IFD entry.
+    pub fn offset_size(&self) -> usize {
+        match self {
+            TIFFVariant::Classic => 4,
+            TIFFVariant::BigTIFF => 8,
+        }
+    }
+
+    // Size of the entry count at the start of an IFD.
+    pub fn entry_count_size(&self) -> usize {
+        match self {
+            TIFFVariant::Classic => 2,
+            TIFFVariant::BigTIFF => 8,
+        }
+    }
+
+    pub fn entry_size(&self) -> usize {
+        match self {
+            TIFFVariant::Classic => 12,
+            TIFFVariant::BigTIFF => 20,
+        }
+    }
+}
+
 // Integer parsing impls.
-// Implemented for u16, u32, and f64
+// Implemented for u16, u32, u64, and f64
 pub trait FromBytes {
     fn from_bytes(bytes: &[u8], byte_order: &ByteOrder) -> Self;
 }
@@ -30,6 +62,17 @@ impl FromBytes for u32 {
         };
     }
 }
+
+impl FromBytes for u64 {
+    fn from_bytes(bytes: &[u8], byte_order: &ByteOrder) -> Self {
+        let bytes: [u8; 8] = bytes.try_into().unwrap();
+        match byte_order {
+            ByteOrder::LittleEndian => u64::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u64::from_be_bytes(bytes),
+        }
+    }
+}
+
 impl FromBytes for f64 {
     fn from_bytes(bytes: &[u8], byte_order: &ByteOrder) -> Self {
         let bytes: [u8; 8] = bytes.try_into().unwrap();
@@ -70,6 +113,19 @@ mod tests {
         assert_eq!(result_be, 0x01020304);
     }
 
+    #[test]
+    fn test_u64_endianness() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+        assert_eq!(
+            u64::from_bytes(&bytes, &ByteOrder::LittleEndian),
+            0x0807060504030201
+        );
+        assert_eq!(
+            u64::from_bytes(&bytes, &ByteOrder::BigEndian),
+            0x0102030405060708
+        );
+    }
+
     #[test]
     fn test_f64_endianness() {
         // Test LittleEndian
@@ -1,16 +1,32 @@
-use crate::parsing::mbtiles::{MBTilesMetaData, MBTilesRegion};
-use crate::spatial::Coordinate;
-use rusqlite::{Connection, Result};
+use crate::parsing::crs;
+use crate::parsing::reproject;
+use proj4rs::Proj;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
+use tracing::{event, Level};
 
-pub struct GPKG {
-    min_x: f64,
-    min_y: f64,
-    max_x: f64,
-    max_y: f64,
+// Extent as (min_x, min_y, max_x, max_y).
+type Bounds = (f64, f64, f64, f64);
+
+// A single row of gpkg_contents, bounds are optional in the spec.
+#[derive(Debug)]
+pub struct GPKGLayer {
+    pub table_name: String,
+    pub data_type: String,
+    pub srs_id: Option<i64>,
+    min_x: Option<f64>,
+    min_y: Option<f64>,
+    max_x: Option<f64>,
+    max_y: Option<f64>,
+}
+
+impl GPKGLayer {
+    fn bounds(&self) -> Option<Bounds> {
+        Some((self.min_x?, self.min_y?, self.max_x?, self.max_y?))
+    }
 }
 
 #[derive(Debug)]
@@ -19,6 +35,21 @@ pub struct GPKGRegion {
     pub bottom_right: (f64, f64),
 }
 
+impl GPKGRegion {
+    fn union(&self, other: &GPKGRegion) -> GPKGRegion {
+        GPKGRegion {
+            top_left: (
+                self.top_left.0.min(other.top_left.0),
+                self.top_left.1.max(other.top_left.1),
+            ),
+            bottom_right: (
+                self.bottom_right.0.max(other.bottom_right.0),
+                self.bottom_right.1.min(other.bottom_right.1),
+            ),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct GPKGMetaData {
     pub region: GPKGRegion,
@@ -30,63 +61,446 @@ pub struct GPKGMap {
     pub(crate) path: PathBuf,
 }
 
-pub fn parse_gpkg(filepath: &str) -> Result<GPKGMetaData> {
-    //Tags for metadata
-    let mut tags = vec![("Filetype".to_string(), "GPKG".to_string())];
+#[derive(Debug)]
+pub enum GPKGErrorState {
+    ConnectionError(rusqlite::Error),
+    QueryError(rusqlite::Error),
+    ProjectionError(String),
+    NotEnoughGeoData,
+}
 
-    let conn_result = Connection::open(filepath);
+impl Display for GPKGErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                GPKGErrorState::ConnectionError(e) =>
+                    format!("Failed to open GeoPackage database: {e}"),
+                GPKGErrorState::QueryError(e) =>
+                    format!("Failed to query GeoPackage database: {e}"),
+                GPKGErrorState::ProjectionError(s) => format!("ProjectionError, reason: {s}"),
+                GPKGErrorState::NotEnoughGeoData =>
+                    "Not enough geographic data within the file to establish a boundary!"
+                        .to_string(),
+            }
+        )
+    }
+}
 
-    //Some error handling
-    let conn = match conn_result {
-        Ok(conn) => conn,
-        Err(error) => panic!("Problem Occurred when connecting to DB file: {:?}", error),
+impl Error for GPKGErrorState {}
+
+// Extends a (min_x, min_y, max_x, max_y) extent to include another.
+fn extend_bounds(bounds: Option<Bounds>, other: Bounds) -> Option<Bounds> {
+    Some(match bounds {
+        Some(b) => (
+            b.0.min(other.0),
+            b.1.min(other.1),
+            b.2.max(other.2),
+            b.3.max(other.3),
+        ),
+        None => other,
+    })
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn read_f64(buf: &[u8], pos: usize, little_endian: bool) -> Option<f64> {
+    let bytes: [u8; 8] = buf.get(pos..pos + 8)?.try_into().ok()?;
+    Some(if little_endian {
+        f64::from_le_bytes(bytes)
+    } else {
+        f64::from_be_bytes(bytes)
+    })
+}
+
+fn read_u32(buf: &[u8], pos: usize, little_endian: bool) -> Option<u32> {
+    let bytes: [u8; 4] = buf.get(pos..pos + 4)?.try_into().ok()?;
+    Some(if little_endian {
+        u32::from_le_bytes(bytes)
+    } else {
+        u32::from_be_bytes(bytes)
+    })
+}
+
+// Deepest nesting of collections read_wkb follows, so a crafted blob can't overflow the stack.
+const MAX_WKB_DEPTH: usize = 32;
+
+// Walks a WKB geometry starting at pos, extending bounds with every coordinate.
+// Returns the position after the geometry, or None if the geometry is malformed, unsupported or
+// nested deeper than MAX_WKB_DEPTH.
+fn read_wkb(buf: &[u8], pos: usize, depth: usize, bounds: &mut Option<Bounds>) -> Option<usize> {
+    if depth > MAX_WKB_DEPTH {
+        return None;
+    }
+    let little_endian = *buf.get(pos)? == 1;
+    let wkb_type = read_u32(buf, pos + 1, little_endian)?;
+    let mut pos = pos + 5;
+
+    // Handle both EWKB high bit flags and ISO thousands for Z/M dimensions.
+    let ewkb_dims = (wkb_type & 0x8000_0000 != 0) as usize + (wkb_type & 0x4000_0000 != 0) as usize;
+    let iso_type = wkb_type & 0x0FFF_FFFF;
+    let iso_dims = match iso_type / 1000 {
+        0 => 0,
+        1 | 2 => 1,
+        3 => 2,
+        _ => return None,
     };
+    let dims = 2 + ewkb_dims + iso_dims;
 
-    let mut stmt_result = conn.prepare("SELECT min_x,min_y,max_x,max_y FROM gpkg_contents");
+    let read_points = |pos: usize, count: usize, bounds: &mut Option<Bounds>| {
+        let end = pos.checked_add(count.checked_mul(dims * 8)?)?;
+        if end > buf.len() {
+            return None;
+        }
+        for i in 0..count {
+            let x = read_f64(buf, pos + i * dims * 8, little_endian)?;
+            let y = read_f64(buf, pos + i * dims * 8 + 8, little_endian)?;
+            // Empty points are encoded as NaN.
+            if !x.is_nan() && !y.is_nan() {
+                *bounds = extend_bounds(*bounds, (x, y, x, y));
+            }
+        }
+        Some(end)
+    };
 
-    let mut stmt = match stmt_result {
-        Ok(stmt) => stmt,
-        Err(error) => panic!("Problem Occurred when querying the DB: {:?}", error),
+    match iso_type % 1000 {
+        // Point
+        1 => pos = read_points(pos, 1, bounds)?,
+        // LineString
+        2 => {
+            let count = read_u32(buf, pos, little_endian)? as usize;
+            pos = read_points(pos + 4, count, bounds)?;
+        }
+        // Polygon
+        3 => {
+            let rings = read_u32(buf, pos, little_endian)?;
+            pos += 4;
+            for _ in 0..rings {
+                let count = read_u32(buf, pos, little_endian)? as usize;
+                pos = read_points(pos + 4, count, bounds)?;
+            }
+        }
+        // MultiPoint, MultiLineString, MultiPolygon, GeometryCollection
+        4..=7 => {
+            let count = read_u32(buf, pos, little_endian)?;
+            pos += 4;
+            for _ in 0..count {
+                pos = read_wkb(buf, pos, depth + 1, bounds)?;
+            }
+        }
+        _ => return None,
+    }
+    Some(pos)
+}
+
+// Reads the extent of a GeoPackage binary geometry, from its header envelope if present, else its WKB body.
+fn geometry_bounds(blob: &[u8]) -> Option<Bounds> {
+    if blob.get(0..2)? != b"GP" {
+        return None;
+    }
+    let flags = *blob.get(3)?;
+    // Empty geometry flag.
+    if flags & 0b0001_0000 != 0 {
+        return None;
+    }
+    let little_endian = flags & 1 == 1;
+    let envelope_size = match (flags >> 1) & 0b111 {
+        0 => 0,
+        1 => 32,
+        2 | 3 => 48,
+        4 => 64,
+        _ => return None,
     };
 
-    let coords_iter_result = stmt.query_map([], |row| {
-        Ok(GPKG {
-            min_x: row.get(0)?,
-            min_y: row.get(1)?,
-            max_x: row.get(2)?,
-            max_y: row.get(3)?,
-        })
-    });
+    if envelope_size > 0 {
+        // Envelope is always ordered min_x, max_x, min_y, max_y.
+        let min_x = read_f64(blob, 8, little_endian)?;
+        let max_x = read_f64(blob, 16, little_endian)?;
+        let min_y = read_f64(blob, 24, little_endian)?;
+        let max_y = read_f64(blob, 32, little_endian)?;
+        return Some((min_x, min_y, max_x, max_y));
+    }
 
-    let coords_iter = match coords_iter_result {
-        Ok(coords_iter) => coords_iter,
-        Err(error) => panic!(
-            "Problem Occurred when iterating through query results: {:?}",
-            error
-        ),
+    let mut bounds = None;
+    read_wkb(blob, 8, 0, &mut bounds)?;
+    bounds
+}
+
+// Derives bounds for a layer which has NULL bounds in gpkg_contents.
+// Tiles use gpkg_tile_matrix_set, features use the rtree index if present, else every geometry blob.
+fn fallback_bounds(
+    conn: &Connection,
+    layer: &GPKGLayer,
+) -> Result<Option<(Bounds, &'static str)>, GPKGErrorState> {
+    if layer.data_type != "features" {
+        let bounds = conn
+            .query_row(
+                "SELECT min_x, min_y, max_x, max_y FROM gpkg_tile_matrix_set WHERE table_name = ?1",
+                [&layer.table_name],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()
+            .map_err(GPKGErrorState::QueryError)?;
+        return Ok(bounds.map(|b| (b, "tile_matrix_set")));
+    }
+
+    let column: Option<String> = conn
+        .query_row(
+            "SELECT column_name FROM gpkg_geometry_columns WHERE table_name = ?1",
+            [&layer.table_name],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(GPKGErrorState::QueryError)?;
+    let Some(column) = column else {
+        return Ok(None);
     };
 
-    let mut top_left_result: (f64, f64) = (0.0, 0.0);
-    let mut bottom_right_result: (f64, f64) = (0.0, 0.0);
+    // Spatial index tables are named rtree_<table>_<column>.
+    let rtree = format!("rtree_{}_{}", layer.table_name, column);
+    let has_rtree = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE name = ?1",
+            [&rtree],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(GPKGErrorState::QueryError)?
+        .is_some();
+
+    if has_rtree {
+        let bounds: (Option<f64>, Option<f64>, Option<f64>, Option<f64>) = conn
+            .query_row(
+                &format!(
+                    "SELECT MIN(minx), MIN(miny), MAX(maxx), MAX(maxy) FROM {}",
+                    quote_identifier(&rtree)
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .map_err(GPKGErrorState::QueryError)?;
+        if let (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) = bounds {
+            return Ok(Some(((min_x, min_y, max_x, max_y), "rtree")));
+        }
+    }
 
-    //Iterate through results, should only be one set of coords
-    for coords in coords_iter {
-        let temp = match coords {
-            Ok(temp) => temp,
-            Err(error) => panic!("Problem Occurred when retrieving coordinates: {:?}", error),
-        };
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM {}",
+            quote_identifier(&column),
+            quote_identifier(&layer.table_name)
+        ))
+        .map_err(GPKGErrorState::QueryError)?;
+    let mut rows = stmt.query([]).map_err(GPKGErrorState::QueryError)?;
 
-        top_left_result = (temp.min_x, temp.max_y);
-        bottom_right_result = (temp.max_x, temp.min_y);
+    let mut bounds = None;
+    while let Some(row) = rows.next().map_err(GPKGErrorState::QueryError)? {
+        let blob: Option<Vec<u8>> = row.get(0).map_err(GPKGErrorState::QueryError)?;
+        if let Some(geometry) = blob.as_deref().and_then(geometry_bounds) {
+            bounds = extend_bounds(bounds, geometry);
+        }
     }
+    Ok(bounds.map(|b| (b, "geometry")))
+}
+
+// Builds the projection for a srs_id, using the definition stored within gpkg_spatial_ref_sys,
+// along with a label identifying the CRS. The projection is None when the layer is already in WGS84.
+fn layer_projection(
+    conn: &Connection,
+    srs_id: i64,
+) -> Result<(Option<Proj>, String), GPKGErrorState> {
+    // The spec reserves 0 for "undefined geographic", treated as WGS84, and -1 for "undefined
+    // Cartesian", which can't be placed on the globe. Every other id is defined by the file.
+    match srs_id {
+        0 => return Ok((None, "EPSG:4326".to_string())),
+        -1 => {
+            return Err(GPKGErrorState::ProjectionError(
+                "srs_id -1 is an undefined Cartesian CRS!".to_string(),
+            ))
+        }
+        _ => {}
+    }
+
+    let srs: Option<(String, i64, String)> = conn
+        .query_row(
+            "SELECT organization, organization_coordsys_id, definition FROM gpkg_spatial_ref_sys WHERE srs_id = ?1",
+            [srs_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(GPKGErrorState::QueryError)?;
+
+    let Some((organization, code, definition)) = srs else {
+        return Err(GPKGErrorState::ProjectionError(format!(
+            "srs_id {srs_id} not found in gpkg_spatial_ref_sys!"
+        )));
+    };
 
-    return Ok(GPKGMetaData {
-        region: GPKGRegion {
-            top_left: top_left_result,
-            bottom_right: bottom_right_result,
+    // Prefer the registry's EPSG definition, falling back to the WKT held in the file.
+    let epsg_code = u32::try_from(code)
+        .ok()
+        .filter(|_| organization.eq_ignore_ascii_case("EPSG"));
+    if epsg_code == Some(4326) {
+        return Ok((None, "EPSG:4326".to_string()));
+    }
+    let registry = crs::registry();
+    let failed = |e: String| {
+        GPKGErrorState::ProjectionError(format!(
+            "Failed to build projection for srs_id {srs_id}: {e}"
+        ))
+    };
+    let projection = match epsg_code {
+        Some(code) => match registry.proj_string(code) {
+            Some(proj_string) => {
+                Proj::from_proj_string(&proj_string).map_err(|e| failed(format!("{e:?}")))?
+            }
+            // Reports the unknown code, if the WKT can't be used either.
+            None => registry
+                .projection_from_wkt(&definition)
+                .or_else(|_| registry.projection(code))
+                .map_err(|e| failed(e.to_string()))?,
         },
-        tags,
-    });
+        None => registry
+            .projection_from_wkt(&definition)
+            .map_err(|e| failed(e.to_string()))?,
+    };
+
+    Ok((
+        Some(projection),
+        format!("{}:{code}", organization.to_ascii_uppercase()),
+    ))
+}
+
+// Reprojects an extent into EPSG:4326, densifying its edges so curved extents are covered.
+fn reproject_bounds(from_proj: &Proj, bounds: Bounds) -> Result<GPKGRegion, GPKGErrorState> {
+    let extent = reproject::reproject_bounds(bounds, from_proj)
+        .map_err(|e| GPKGErrorState::ProjectionError(e.to_string()))?;
+    Ok(GPKGRegion {
+        top_left: extent.top_left(),
+        bottom_right: extent.bottom_right(),
+    })
+}
+
+pub fn parse_gpkg(filepath: &str) -> Result<GPKGMetaData, GPKGErrorState> {
+    //Tags for metadata
+    let mut tags = vec![("Filetype".to_string(), "GPKG".to_string())];
+
+    // Open read only, we never want indexing to create or modify a database.
+    let conn = Connection::open_with_flags(filepath, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(GPKGErrorState::ConnectionError)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT table_name, data_type, srs_id, min_x, min_y, max_x, max_y FROM gpkg_contents",
+        )
+        .map_err(GPKGErrorState::QueryError)?;
+
+    let layers = stmt
+        .query_map([], |row| {
+            Ok(GPKGLayer {
+                table_name: row.get(0)?,
+                data_type: row.get(1)?,
+                srs_id: row.get(2)?,
+                min_x: row.get(3)?,
+                min_y: row.get(4)?,
+                max_x: row.get(5)?,
+                max_y: row.get(6)?,
+            })
+        })
+        .map_err(GPKGErrorState::QueryError)?
+        .collect::<Result<Vec<GPKGLayer>, _>>()
+        .map_err(GPKGErrorState::QueryError)?;
+
+    tags.push(("LayerCount".to_string(), layers.len().to_string()));
+
+    let mut region: Option<GPKGRegion> = None;
+    let mut crs_labels: Vec<String> = Vec::new();
+
+    // Union the extent of every layer, each reprojected from its own SRS.
+    for layer in layers.iter() {
+        let srs_id = layer.srs_id.unwrap_or(0);
+        tags.push((
+            "Layer".to_string(),
+            format!(
+                "{} ({}, srs_id {srs_id})",
+                layer.table_name, layer.data_type
+            ),
+        ));
+
+        let bounds = match layer.bounds() {
+            Some(bounds) => bounds,
+            None => match fallback_bounds(&conn, layer) {
+                Ok(Some((bounds, source))) => {
+                    event!(
+                        Level::INFO,
+                        "GeoPackage layer {} has no bounds in gpkg_contents, derived {bounds:?} from {source}.",
+                        layer.table_name
+                    );
+                    tags.push((
+                        "LayerBoundsSource".to_string(),
+                        format!("{}: {source}", layer.table_name),
+                    ));
+                    bounds
+                }
+                Ok(None) => {
+                    event!(
+                        Level::WARN,
+                        "GeoPackage layer {} has no bounds, and none could be derived, skipping.",
+                        layer.table_name
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    event!(
+                        Level::WARN,
+                        "Failed to derive bounds for GeoPackage layer {}, skipping. Reason: {e}",
+                        layer.table_name
+                    );
+                    continue;
+                }
+            },
+        };
+
+        let layer_region = match layer_projection(&conn, srs_id).and_then(|(proj, label)| {
+            if !crs_labels.contains(&label) {
+                crs_labels.push(label);
+            }
+            match proj {
+                Some(proj) => reproject_bounds(&proj, bounds),
+                None => Ok(GPKGRegion {
+                    top_left: (bounds.0, bounds.3),
+                    bottom_right: (bounds.2, bounds.1),
+                }),
+            }
+        }) {
+            Ok(r) => r,
+            Err(e) => {
+                event!(
+                    Level::WARN,
+                    "Failed to reproject GeoPackage layer {}, skipping. Reason: {e}",
+                    layer.table_name
+                );
+                continue;
+            }
+        };
+
+        region = Some(match region {
+            Some(region) => region.union(&layer_region),
+            None => layer_region,
+        });
+    }
+
+    for label in crs_labels {
+        tags.push(("CRS".to_string(), label));
+    }
+
+    match region {
+        Some(region) => Ok(GPKGMetaData { region, tags }),
+        None => Err(GPKGErrorState::NotEnoughGeoData),
+    }
 }
 
 #[cfg(test)]
@@ -95,20 +509,35 @@ mod tests {
     use rusqlite::{params, Connection};
     use tempfile::NamedTempFile;
 
+    // Creates the gpkg_contents and gpkg_spatial_ref_sys tables, with WGS84 and British National Grid defined.
+    fn create_gpkg_schema(conn: &Connection) {
+        conn.execute(
+            "CREATE TABLE gpkg_spatial_ref_sys (srs_name TEXT, srs_id INTEGER, organization TEXT, organization_coordsys_id INTEGER, definition TEXT);",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO gpkg_spatial_ref_sys VALUES ('WGS 84', 4326, 'EPSG', 4326, ?1), ('OSGB 1936 / British National Grid', 27700, 'EPSG', 27700, ?2)",
+            params![crs_definitions::EPSG_4326.wkt, crs_definitions::EPSG_27700.wkt],
+        )
+        .unwrap();
+        conn.execute(
+            "CREATE TABLE gpkg_contents (table_name TEXT, data_type TEXT, min_x REAL, min_y REAL, max_x REAL, max_y REAL, srs_id INTEGER);",
+            [],
+        )
+        .unwrap();
+    }
+
     // Creates a temporary GPKG file for testing
     fn create_test_gpkg() -> NamedTempFile {
         let temp_file = NamedTempFile::new().unwrap();
         let conn = Connection::open(temp_file.path()).unwrap();
 
         // Create gpkg_contents table and insert test coordinates
-        conn.execute(
-            "CREATE TABLE gpkg_contents (min_x REAL, min_y REAL, max_x REAL, max_y REAL);",
-            [],
-        )
-        .unwrap();
+        create_gpkg_schema(&conn);
 
         conn.execute(
-            "INSERT INTO gpkg_contents (min_x, min_y, max_x, max_y) VALUES (?1, ?2, ?3, ?4)",
+            "INSERT INTO gpkg_contents (table_name, data_type, min_x, min_y, max_x, max_y, srs_id) VALUES ('roads', 'features', ?1, ?2, ?3, ?4, 4326)",
             params![10.1, 20.2, 30.3, 40.4], // Sample bounds
         )
         .unwrap();
@@ -132,35 +561,288 @@ mod tests {
             .tags
             .iter()
             .any(|(key, value)| key == "Filetype" && value == "GPKG"));
+        assert!(metadata.tags.contains(&(
+            "Layer".to_string(),
+            "roads (features, srs_id 4326)".to_string()
+        )));
     }
 
-    #[test] //Panic?
-    #[should_panic]
+    #[test]
     fn test_parse_gpkg_empty() {
         let temp_file = NamedTempFile::new().unwrap();
         let temp_file_path = temp_file.path().to_str().unwrap();
 
         let metadata = parse_gpkg(temp_file_path);
 
-        assert!(metadata.is_err());
+        assert!(matches!(metadata, Err(GPKGErrorState::QueryError(_))));
     }
+
     #[test]
-    fn test_parse_gpkg_multiple_entries() {
+    fn test_parse_gpkg_null_bounds() {
         let temp_file = NamedTempFile::new().unwrap();
         let conn = Connection::open(temp_file.path()).unwrap();
-
+        create_gpkg_schema(&conn);
         conn.execute(
-            "CREATE TABLE gpkg_contents (min_x REAL, min_y REAL, max_x REAL, max_y REAL);",
+            "INSERT INTO gpkg_contents (table_name, data_type, srs_id) VALUES ('roads', 'features', 4326)",
             [],
         )
         .unwrap();
 
-        conn.execute("INSERT INTO gpkg_contents (min_x, min_y, max_x, max_y) VALUES (1.0, 2.0, 3.0, 4.0), (5.0, 6.0, 7.0, 8.0);", []).unwrap();
+        let metadata = parse_gpkg(temp_file.path().to_str().unwrap());
+
+        assert!(matches!(metadata, Err(GPKGErrorState::NotEnoughGeoData)));
+    }
+
+    #[test]
+    fn test_parse_gpkg_multiple_entries() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        create_gpkg_schema(&conn);
+
+        conn.execute("INSERT INTO gpkg_contents (table_name, data_type, min_x, min_y, max_x, max_y, srs_id) VALUES ('a', 'features', 1.0, 2.0, 3.0, 4.0, 4326), ('b', 'tiles', 5.0, 6.0, 7.0, 8.0, 4326);", []).unwrap();
 
         let temp_file_path = temp_file.path().to_str().unwrap();
         let metadata = parse_gpkg(temp_file_path).unwrap();
 
-        assert_eq!(metadata.region.top_left, (5.0, 8.0));
-        assert_eq!(metadata.region.bottom_right, (7.0, 6.0));
+        // Region should be the union of every layer.
+        assert_eq!(metadata.region.top_left, (1.0, 8.0));
+        assert_eq!(metadata.region.bottom_right, (7.0, 2.0));
+        assert!(metadata
+            .tags
+            .contains(&("LayerCount".to_string(), "2".to_string())));
+    }
+
+    #[test]
+    fn test_parse_gpkg_projected_layer() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        create_gpkg_schema(&conn);
+
+        // Roughly Oxford to Cambridge, in British National Grid metres.
+        conn.execute("INSERT INTO gpkg_contents (table_name, data_type, min_x, min_y, max_x, max_y, srs_id) VALUES ('bng', 'features', 450000.0, 200000.0, 550000.0, 260000.0, 27700);", []).unwrap();
+
+        let metadata = parse_gpkg(temp_file.path().to_str().unwrap()).unwrap();
+
+        let (west, north) = metadata.region.top_left;
+        let (east, south) = metadata.region.bottom_right;
+        assert!((-1.4..-1.2).contains(&west), "west: {west}");
+        assert!((0.1..0.3).contains(&east), "east: {east}");
+        assert!((51.6..51.8).contains(&south), "south: {south}");
+        assert!((52.2..52.3).contains(&north), "north: {north}");
+        assert!(metadata
+            .tags
+            .contains(&("CRS".to_string(), "EPSG:27700".to_string())));
+    }
+
+    #[test]
+    fn test_parse_gpkg_srs_id_from_table() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        create_gpkg_schema(&conn);
+        // srs_id 4326 is only a row id, here it's defined as British National Grid.
+        conn.execute("DELETE FROM gpkg_spatial_ref_sys WHERE srs_id = 4326", [])
+            .unwrap();
+        conn.execute(
+            "UPDATE gpkg_spatial_ref_sys SET srs_id = 4326 WHERE srs_id = 27700",
+            [],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO gpkg_contents (table_name, data_type, min_x, min_y, max_x, max_y, srs_id) VALUES ('a', 'features', 529000.0, 179000.0, 531000.0, 181000.0, 4326)", []).unwrap();
+
+        let metadata = parse_gpkg(temp_file.path().to_str().unwrap()).unwrap();
+
+        let (west, north) = metadata.region.top_left;
+        assert!((-0.15..-0.1).contains(&west), "west: {west}");
+        assert!((51.5..51.55).contains(&north), "north: {north}");
+        assert!(metadata
+            .tags
+            .contains(&("CRS".to_string(), "EPSG:27700".to_string())));
+    }
+
+    #[test]
+    fn test_parse_gpkg_undefined_cartesian_is_skipped() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        create_gpkg_schema(&conn);
+        conn.execute("INSERT INTO gpkg_contents (table_name, data_type, min_x, min_y, max_x, max_y, srs_id) VALUES ('a', 'features', 1.0, 2.0, 3.0, 4.0, 0), ('b', 'features', 500.0, 600.0, 700.0, 800.0, -1);", []).unwrap();
+
+        let metadata = parse_gpkg(temp_file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(metadata.region.top_left, (1.0, 4.0));
+        assert_eq!(metadata.region.bottom_right, (3.0, 2.0));
+    }
+
+    #[test]
+    fn test_parse_gpkg_unknown_srs_is_skipped() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        create_gpkg_schema(&conn);
+
+        conn.execute("INSERT INTO gpkg_contents (table_name, data_type, min_x, min_y, max_x, max_y, srs_id) VALUES ('a', 'features', 1.0, 2.0, 3.0, 4.0, 4326), ('b', 'features', 5.0, 6.0, 7.0, 8.0, 999999);", []).unwrap();
+
+        let metadata = parse_gpkg(temp_file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(metadata.region.top_left, (1.0, 4.0));
+        assert_eq!(metadata.region.bottom_right, (3.0, 2.0));
+        let crs: Vec<_> = metadata.tags.iter().filter(|(k, _)| k == "CRS").collect();
+        assert_eq!(crs, vec![&("CRS".to_string(), "EPSG:4326".to_string())]);
+    }
+
+    // Builds a little endian GeoPackage binary point, optionally with an xy envelope.
+    fn gpkg_point(x: f64, y: f64, envelope: bool) -> Vec<u8> {
+        let mut blob = vec![b'G', b'P', 0, if envelope { 0b11 } else { 0b1 }];
+        blob.extend_from_slice(&4326i32.to_le_bytes());
+        if envelope {
+            for v in [x, x, y, y] {
+                blob.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        blob.push(1);
+        blob.extend_from_slice(&1u32.to_le_bytes());
+        blob.extend_from_slice(&x.to_le_bytes());
+        blob.extend_from_slice(&y.to_le_bytes());
+        blob
+    }
+
+    fn create_features_without_bounds(conn: &Connection) {
+        create_gpkg_schema(conn);
+        conn.execute(
+            "CREATE TABLE gpkg_geometry_columns (table_name TEXT, column_name TEXT, geometry_type_name TEXT, srs_id INTEGER, z INTEGER, m INTEGER);",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO gpkg_geometry_columns VALUES ('points', 'geom', 'POINT', 4326, 0, 0)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO gpkg_contents (table_name, data_type, srs_id) VALUES ('points', 'features', 4326)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "CREATE TABLE points (fid INTEGER PRIMARY KEY, geom BLOB);",
+            [],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_parse_gpkg_bounds_from_rtree() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        create_features_without_bounds(&conn);
+        conn.execute(
+            "CREATE TABLE rtree_points_geom (id INTEGER, minx REAL, maxx REAL, miny REAL, maxy REAL);",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO rtree_points_geom VALUES (1, 1.0, 1.0, 2.0, 2.0), (2, 3.0, 5.0, -1.0, 0.0)",
+            [],
+        )
+        .unwrap();
+
+        let metadata = parse_gpkg(temp_file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(metadata.region.top_left, (1.0, 2.0));
+        assert_eq!(metadata.region.bottom_right, (5.0, -1.0));
+        assert!(metadata
+            .tags
+            .contains(&("LayerBoundsSource".to_string(), "points: rtree".to_string())));
+    }
+
+    #[test]
+    fn test_parse_gpkg_bounds_from_geometry_blobs() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        create_features_without_bounds(&conn);
+        conn.execute(
+            "INSERT INTO points (geom) VALUES (?1), (?2), (NULL)",
+            params![gpkg_point(-3.0, 50.0, true), gpkg_point(2.0, 55.0, false)],
+        )
+        .unwrap();
+
+        let metadata = parse_gpkg(temp_file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(metadata.region.top_left, (-3.0, 55.0));
+        assert_eq!(metadata.region.bottom_right, (2.0, 50.0));
+        assert!(metadata.tags.contains(&(
+            "LayerBoundsSource".to_string(),
+            "points: geometry".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_parse_gpkg_bounds_from_tile_matrix_set() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        create_gpkg_schema(&conn);
+        conn.execute(
+            "CREATE TABLE gpkg_tile_matrix_set (table_name TEXT, srs_id INTEGER, min_x REAL, min_y REAL, max_x REAL, max_y REAL);",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO gpkg_tile_matrix_set VALUES ('imagery', 4326, -10.0, 40.0, 10.0, 60.0)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO gpkg_contents (table_name, data_type, srs_id) VALUES ('imagery', 'tiles', 4326)",
+            [],
+        )
+        .unwrap();
+
+        let metadata = parse_gpkg(temp_file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(metadata.region.top_left, (-10.0, 60.0));
+        assert_eq!(metadata.region.bottom_right, (10.0, 40.0));
+    }
+
+    #[test]
+    fn test_geometry_bounds_multipoint_wkb() {
+        let mut blob = vec![b'G', b'P', 0, 0b1];
+        blob.extend_from_slice(&0i32.to_le_bytes());
+        // Big endian MultiPoint Z (ISO 1004) holding two points.
+        blob.push(0);
+        blob.extend_from_slice(&1004u32.to_be_bytes());
+        blob.extend_from_slice(&2u32.to_be_bytes());
+        for (x, y) in [(1.0f64, 2.0f64), (-4.0, 8.0)] {
+            blob.push(0);
+            blob.extend_from_slice(&1001u32.to_be_bytes());
+            for v in [x, y, 100.0] {
+                blob.extend_from_slice(&v.to_be_bytes());
+            }
+        }
+
+        assert_eq!(geometry_bounds(&blob), Some((-4.0, 2.0, 1.0, 8.0)));
+        assert_eq!(geometry_bounds(&blob[..blob.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_geometry_bounds_deeply_nested_collection() {
+        // GeometryCollections each holding the next, with a point at the bottom.
+        let nested = |depth: usize| {
+            let mut blob = vec![b'G', b'P', 0, 0b1];
+            blob.extend_from_slice(&0i32.to_le_bytes());
+            for _ in 0..depth {
+                blob.push(1);
+                blob.extend_from_slice(&7u32.to_le_bytes());
+                blob.extend_from_slice(&1u32.to_le_bytes());
+            }
+            blob.push(1);
+            blob.extend_from_slice(&1u32.to_le_bytes());
+            blob.extend_from_slice(&1.0f64.to_le_bytes());
+            blob.extend_from_slice(&2.0f64.to_le_bytes());
+            blob
+        };
+
+        assert_eq!(
+            geometry_bounds(&nested(MAX_WKB_DEPTH)),
+            Some((1.0, 2.0, 1.0, 2.0))
+        );
+        assert_eq!(geometry_bounds(&nested(100_000)), None);
     }
 }
@@ -0,0 +1,376 @@
+use crate::parsing::gpx::GPXErrorState::{NotEnoughGeoData, UnexpectedFormat};
+use crate::spatial::{antimeridian_footprint, Coordinate, FootprintBuffer, Region};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tracing::{event, Level};
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GPXMap {
+    pub(crate) path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct GPXMetaData {
+    pub region: Region,
+    pub tags: Vec<(String, String)>,
+    pub footprint: Option<Vec<Coordinate>>,
+}
+
+#[derive(Debug)]
+pub enum GPXErrorState {
+    UnexpectedFormat(String),
+    NotEnoughGeoData,
+}
+
+impl Display for GPXErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnexpectedFormat(s) => write!(f, "UnexpectedFormatError: {s}"),
+            NotEnoughGeoData => write!(
+                f,
+                "Not enough geographic data within the file to establish a boundary!"
+            ),
+        }
+    }
+}
+impl Error for GPXErrorState {}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+// An xsd:dateTime, with any offset and fractional seconds. Times without an offset are taken as UTC.
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|t| t.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f").map(|t| t.and_utc())
+        })
+        .ok()
+}
+
+// A lat or lon attribute, which GPX requires to be within range.
+fn parse_degrees(
+    attributes: &[OwnedAttribute],
+    name: &str,
+    limit: f64,
+) -> Result<f64, GPXErrorState> {
+    let value = attribute(attributes, name)
+        .ok_or_else(|| UnexpectedFormat(format!("Missing {name} attribute!")))?;
+    match f64::from_str(value.trim()) {
+        Ok(v) if (-limit..=limit).contains(&v) => Ok(v),
+        e => Err(UnexpectedFormat(format!(
+            "Failed to parse {name}: {value} with err: {e:?}"
+        ))),
+    }
+}
+
+fn parse_point(attributes: &[OwnedAttribute]) -> Result<Coordinate, GPXErrorState> {
+    Ok((
+        parse_degrees(attributes, "lon", 180.0)?,
+        parse_degrees(attributes, "lat", 90.0)?,
+    ))
+}
+
+pub fn parse_gpx<R: Read>(reader: &mut R) -> Result<GPXMetaData, GPXErrorState> {
+    let reader = EventReader::new(reader);
+    let mut tags = vec![("Filetype".to_string(), "GPX".to_string())];
+    let mut coordinates = FootprintBuffer::default();
+    let mut bounds: Option<[Coordinate; 2]> = None;
+    // Whether any track or route crosses the antimeridian, judged from consecutive points.
+    let mut wraps = false;
+    let mut previous: Option<f64> = None;
+
+    let (mut waypoints, mut tracks, mut track_points, mut routes, mut route_points) =
+        (0, 0, 0, 0, 0);
+    let (mut start, mut end): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) = (None, None);
+
+    // Local names of the open elements, so both GPX 1.0 and 1.1 namespaces are treated alike.
+    let mut stack: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    for event in reader {
+        let event = event.map_err(|e| UnexpectedFormat(format!("Invalid XML: {e}")))?;
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                match name.local_name.as_str() {
+                    "gpx" => {
+                        if let Some(version) = attribute(&attributes, "version") {
+                            tags.push(("GPXVersion".to_string(), version.to_string()));
+                        }
+                        if let Some(creator) = attribute(&attributes, "creator") {
+                            tags.push(("Creator".to_string(), creator.to_string()));
+                        }
+                    }
+                    "bounds" => {
+                        let min = (
+                            parse_degrees(&attributes, "minlon", 180.0)?,
+                            parse_degrees(&attributes, "minlat", 90.0)?,
+                        );
+                        let max = (
+                            parse_degrees(&attributes, "maxlon", 180.0)?,
+                            parse_degrees(&attributes, "maxlat", 90.0)?,
+                        );
+                        wraps |= min.0 > max.0;
+                        bounds = Some([min, max]);
+                    }
+                    "wpt" => {
+                        coordinates.push(parse_point(&attributes)?);
+                        waypoints += 1;
+                    }
+                    element @ ("trkpt" | "rtept") => {
+                        let point = parse_point(&attributes)?;
+                        if let Some(longitude) = previous {
+                            wraps |= (point.0 - longitude).abs() > 180.0;
+                        }
+                        previous = Some(point.0);
+                        coordinates.push(point);
+                        match element {
+                            "trkpt" => track_points += 1,
+                            _ => route_points += 1,
+                        }
+                    }
+                    "trk" => tracks += 1,
+                    "rte" => {
+                        routes += 1;
+                        previous = None;
+                    }
+                    "trkseg" => previous = None,
+                    _ => {}
+                }
+                stack.push(name.local_name);
+                text.clear();
+            }
+            XmlEvent::Characters(s) | XmlEvent::CData(s) => text.push_str(&s),
+            XmlEvent::EndElement { name } => {
+                stack.pop();
+                let parent = stack.last().map(String::as_str);
+                match (name.local_name.as_str(), parent) {
+                    // The file's name is within metadata in GPX 1.1, and directly within gpx in 1.0.
+                    ("name", Some("metadata" | "gpx")) => {
+                        tags.push(("Name".to_string(), text.trim().to_string()))
+                    }
+                    ("name", Some("trk")) => {
+                        tags.push(("Track".to_string(), text.trim().to_string()))
+                    }
+                    ("name", Some("rte")) => {
+                        tags.push(("Route".to_string(), text.trim().to_string()))
+                    }
+                    ("time", Some("wpt" | "trkpt" | "rtept")) => {
+                        let time = text.trim();
+                        if let Some(time) = parse_time(time) {
+                            start = Some(start.map_or(time, |s| s.min(time)));
+                            end = Some(end.map_or(time, |e| e.max(time)));
+                        } else if !time.is_empty() {
+                            event!(Level::WARN, "Ignoring invalid GPX time {time:?}");
+                        }
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+            _ => {}
+        }
+    }
+
+    let coordinates = coordinates.points;
+    tags.push(("WaypointCount".to_string(), waypoints.to_string()));
+    tags.push(("TrackCount".to_string(), tracks.to_string()));
+    tags.push(("TrackPointCount".to_string(), track_points.to_string()));
+    tags.push(("RouteCount".to_string(), routes.to_string()));
+    tags.push(("RoutePointCount".to_string(), route_points.to_string()));
+    if let (Some(start), Some(end)) = (start, end) {
+        // Normalised to UTC, so times from files with different offsets compare.
+        let format = |t: DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        tags.push(("StartTime".to_string(), format(start)));
+        tags.push(("EndTime".to_string(), format(end)));
+    }
+
+    // The declared bounds are used, but widened should any point fall outside them.
+    let mut extent = match bounds {
+        Some([min, max]) => {
+            let outside = |&(x, y): &Coordinate| x < min.0 || x > max.0 || y < min.1 || y > max.1;
+            if !wraps && coordinates.iter().any(outside) {
+                event!(
+                    Level::WARN,
+                    "GPX points lie outside of the declared bounds {min:?} to {max:?}."
+                );
+            }
+            Region::envelope(&[&[min, max][..], &coordinates[..]].concat())
+        }
+        None if coordinates.is_empty() => return Err(NotEnoughGeoData),
+        None => Region::envelope(&coordinates),
+    };
+    let footprint = antimeridian_footprint(
+        coordinates,
+        wraps,
+        (&mut extent.top_left.0, &mut extent.bottom_right.0),
+        &mut tags,
+    );
+    Ok(GPXMetaData {
+        region: extent,
+        tags,
+        footprint,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::tests::tags;
+
+    fn parse(gpx: &str) -> Result<GPXMetaData, GPXErrorState> {
+        parse_gpx(&mut gpx.as_bytes())
+    }
+
+    const TRACK: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <gpx version="1.1" creator="Field Logger" xmlns="http://www.topografix.com/GPX/1/1">
+            <metadata><name>Survey</name><time>2024-01-01T00:00:00Z</time></metadata>
+            <wpt lat="51.5" lon="-0.1"><name>Camp</name><time>2024-05-01T07:55:00Z</time></wpt>
+            <trk>
+                <name>Morning</name>
+                <trkseg>
+                    <trkpt lat="51.51" lon="-0.12"><ele>10</ele><time>2024-05-01T08:00:00Z</time></trkpt>
+                    <trkpt lat="51.52" lon="-0.11"><time>2024-05-01T08:30:00Z</time></trkpt>
+                </trkseg>
+                <trkseg>
+                    <trkpt lat="51.53" lon="-0.13"><time>2024-05-01T09:15:00Z</time></trkpt>
+                </trkseg>
+            </trk>
+            <trk><name>Afternoon</name><trkseg><trkpt lat="51.49" lon="-0.14"/></trkseg></trk>
+            <rte><name>Return</name><rtept lat="51.5" lon="-0.1"/><rtept lat="51.48" lon="-0.09"/></rte>
+        </gpx>"#;
+
+    #[test]
+    fn test_parse_gpx() {
+        let metadata = parse(TRACK).unwrap();
+        assert_eq!(metadata.region.top_left, (-0.14, 51.53));
+        assert_eq!(metadata.region.bottom_right, (-0.09, 51.48));
+        assert_eq!(metadata.footprint.as_ref().unwrap().len(), 7);
+        assert_eq!(tags(&metadata.tags, "Filetype"), vec!["GPX"]);
+        assert_eq!(tags(&metadata.tags, "GPXVersion"), vec!["1.1"]);
+        assert_eq!(tags(&metadata.tags, "Creator"), vec!["Field Logger"]);
+        assert_eq!(tags(&metadata.tags, "Name"), vec!["Survey"]);
+        assert_eq!(tags(&metadata.tags, "Track"), vec!["Morning", "Afternoon"]);
+        assert_eq!(tags(&metadata.tags, "Route"), vec!["Return"]);
+        assert_eq!(tags(&metadata.tags, "WaypointCount"), vec!["1"]);
+        assert_eq!(tags(&metadata.tags, "TrackCount"), vec!["2"]);
+        assert_eq!(tags(&metadata.tags, "TrackPointCount"), vec!["4"]);
+        assert_eq!(tags(&metadata.tags, "RouteCount"), vec!["1"]);
+        assert_eq!(tags(&metadata.tags, "RoutePointCount"), vec!["2"]);
+        // The metadata time is when the file was made, not part of the range.
+        assert_eq!(
+            tags(&metadata.tags, "StartTime"),
+            vec!["2024-05-01T07:55:00Z"]
+        );
+        assert_eq!(
+            tags(&metadata.tags, "EndTime"),
+            vec!["2024-05-01T09:15:00Z"]
+        );
+    }
+
+    #[test]
+    fn test_parse_gpx_times() {
+        // Offsets and fractional seconds are compared as instants, not as text.
+        let metadata = parse(
+            r#"<gpx><wpt lat="1" lon="2"><time>2024-05-01T09:00:00+02:00</time></wpt>
+                <wpt lat="1" lon="2"><time>2024-05-01T07:30:00.5Z</time></wpt>
+                <wpt lat="1" lon="2"><time>2024-05-01T07:30:00</time></wpt>
+                <wpt lat="1" lon="2"><time>2024-05-01T05:15:00-03:00</time></wpt>
+                <wpt lat="1" lon="2"><time>yesterday</time></wpt></gpx>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            tags(&metadata.tags, "StartTime"),
+            vec!["2024-05-01T07:00:00Z"]
+        );
+        assert_eq!(
+            tags(&metadata.tags, "EndTime"),
+            vec!["2024-05-01T08:15:00Z"]
+        );
+    }
+
+    #[test]
+    fn test_parse_gpx_bounds() {
+        let metadata = parse(
+            r#"<gpx version="1.1"><metadata><bounds minlat="50" minlon="-1" maxlat="52" maxlon="1"/></metadata>
+                <wpt lat="51" lon="0"/></gpx>"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.region.top_left, (-1.0, 52.0));
+        assert_eq!(metadata.region.bottom_right, (1.0, 50.0));
+
+        // Bounds alone are enough, and points outside them widen the extent.
+        let metadata =
+            parse(r#"<gpx><bounds minlat="50" minlon="-1" maxlat="52" maxlon="1"/></gpx>"#)
+                .unwrap();
+        assert_eq!(metadata.region.top_left, (-1.0, 52.0));
+        assert!(metadata.footprint.is_none());
+        let metadata = parse(
+            r#"<gpx><bounds minlat="50" minlon="-1" maxlat="52" maxlon="1"/><wpt lat="53" lon="2"/></gpx>"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.region.top_left, (-1.0, 53.0));
+        assert_eq!(metadata.region.bottom_right, (2.0, 50.0));
+    }
+
+    #[test]
+    fn test_parse_gpx_version_1_0() {
+        let metadata = parse(
+            r#"<gpx version="1.0" xmlns="http://www.topografix.com/GPX/1/0"><name>Old</name>
+                <trk><name>Walk</name><trkseg><trkpt lat="1" lon="2"/></trkseg></trk></gpx>"#,
+        )
+        .unwrap();
+        assert_eq!(tags(&metadata.tags, "Name"), vec!["Old"]);
+        assert_eq!(tags(&metadata.tags, "Track"), vec!["Walk"]);
+        assert!(tags(&metadata.tags, "StartTime").is_empty());
+    }
+
+    #[test]
+    fn test_parse_gpx_across_antimeridian() {
+        let metadata = parse(
+            r#"<gpx><trk><trkseg><trkpt lat="-17" lon="179.5"/><trkpt lat="-17.1" lon="-179.5"/></trkseg></trk></gpx>"#,
+        )
+        .unwrap();
+        assert_eq!(tags(&metadata.tags, "CrossesAntimeridian"), vec!["true"]);
+        assert_eq!(metadata.region.top_left.0, -180.0);
+        assert_eq!(metadata.region.bottom_right.0, 180.0);
+        assert!(metadata.footprint.is_none());
+
+        // Separate segments needn't be joined.
+        let metadata = parse(
+            r#"<gpx><trk><trkseg><trkpt lat="-17" lon="179.5"/></trkseg><trkseg><trkpt lat="-17" lon="-179.5"/></trkseg></trk></gpx>"#,
+        )
+        .unwrap();
+        assert!(tags(&metadata.tags, "CrossesAntimeridian").is_empty());
+    }
+
+    #[test]
+    fn test_parse_gpx_invalid() {
+        assert!(matches!(parse("<gpx></gpx>"), Err(NotEnoughGeoData)));
+        assert!(matches!(
+            parse(r#"<gpx><wpt lat="91" lon="0"/></gpx>"#),
+            Err(UnexpectedFormat(_))
+        ));
+        assert!(matches!(
+            parse(r#"<gpx><wpt lon="0"/></gpx>"#),
+            Err(UnexpectedFormat(_))
+        ));
+        assert!(matches!(
+            parse(r#"<gpx><wpt lat="1" lon="0"></gpx>"#),
+            Err(UnexpectedFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::spatial::{Coordinate, Region};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HGTMap {
+    pub(crate) path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum HGTErrorState {
+    UnexpectedFormat(String),
+    // Tiles are named by their south west corner, such as N51W002.
+    InvalidName(String),
+    // Size in bytes, which isn't that of a 1 or 3 arc second tile.
+    InvalidSize(u64),
+}
+
+impl Display for HGTErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HGTErrorState::UnexpectedFormat(s) => write!(f, "UnexpectedFormatError: {s}"),
+            HGTErrorState::InvalidName(name) => {
+                write!(
+                    f,
+                    "{name:?} isn't named like N51W002 by its south west corner"
+                )
+            }
+            HGTErrorState::InvalidSize(size) => {
+                write!(f, "{size} bytes isn't the size of a 1 or 3 arc second tile")
+            }
+        }
+    }
+}
+
+impl Error for HGTErrorState {}
+
+#[derive(Debug)]
+pub struct HGTMetaData {
+    pub region: Region,
+    pub tags: Vec<(String, String)>,
+    pub footprint: Option<Vec<Coordinate>>,
+}
+
+// Elevation for posts without data.
+const VOID_ELEVATION: i16 = -32768;
+// Posts along each side of a tile, and the arc seconds between them.
+const RESOLUTIONS: [(u64, u32); 2] = [(3601, 1), (1201, 3)];
+
+// South west corner, as (long, lat), from a name such as N51W002 or s12e130.SRTMGL1.
+pub fn tile_origin(name: &str) -> Result<Coordinate, HGTErrorState> {
+    let invalid = || HGTErrorState::InvalidName(name.to_string());
+    // Checked before slicing by byte, which would panic within a multibyte character.
+    let tile = name.get(..7).filter(|t| t.is_ascii()).ok_or_else(invalid)?;
+    let tile = tile.to_ascii_uppercase();
+    let latitude: f64 = tile[1..3].parse().map_err(|_| invalid())?;
+    let longitude: f64 = tile[4..7].parse().map_err(|_| invalid())?;
+    let latitude = match &tile[0..1] {
+        "N" => latitude,
+        "S" => -latitude,
+        _ => return Err(invalid()),
+    };
+    let longitude = match &tile[3..4] {
+        "E" => longitude,
+        "W" => -longitude,
+        _ => return Err(invalid()),
+    };
+    if !(-90.0..90.0).contains(&latitude) || !(-180.0..180.0).contains(&longitude) {
+        return Err(invalid());
+    }
+    Ok((longitude, latitude))
+}
+
+// Reads every post, tagging the elevation statistics and count of voids.
+fn scan_posts(
+    reader: &mut BufReader<File>,
+    posts: u64,
+    tags: &mut Vec<(String, String)>,
+) -> Result<(), HGTErrorState> {
+    let (mut min, mut max) = (i16::MAX, i16::MIN);
+    let (mut sum, mut count, mut voids) = (0f64, 0usize, 0usize);
+    let mut row = vec![0u8; posts as usize * 2];
+    for _ in 0..posts {
+        reader
+            .read_exact(&mut row)
+            .map_err(|e| HGTErrorState::UnexpectedFormat(format!("Failed to read bytes: {e:?}")))?;
+        for elevation in row.chunks_exact(2) {
+            let elevation = i16::from_be_bytes([elevation[0], elevation[1]]);
+            if elevation == VOID_ELEVATION {
+                voids += 1;
+                continue;
+            }
+            min = min.min(elevation);
+            max = max.max(elevation);
+            sum += elevation as f64;
+            count += 1;
+        }
+    }
+    if count > 0 {
+        tags.push(("MinElevation".to_string(), min.to_string()));
+        tags.push(("MaxElevation".to_string(), max.to_string()));
+        tags.push((
+            "MeanElevation".to_string(),
+            format!("{:.1}", sum / count as f64),
+        ));
+    }
+    if voids > 0 {
+        tags.push(("VoidCount".to_string(), voids.to_string()));
+    }
+    Ok(())
+}
+
+// Tiles have no header, being rows of big endian elevations from the north west post.
+// The name gives the position and the size the resolution, with scan every post is read for the statistics.
+pub fn parse_hgt(
+    name: &str,
+    reader: &mut BufReader<File>,
+    scan: bool,
+) -> Result<HGTMetaData, HGTErrorState> {
+    let origin = tile_origin(name)?;
+    let size = reader
+        .get_ref()
+        .metadata()
+        .map_err(|e| HGTErrorState::UnexpectedFormat(format!("Failed to read metadata: {e:?}")))?
+        .len();
+    let Some(&(posts, arc_seconds)) = RESOLUTIONS
+        .iter()
+        .find(|(posts, _)| posts * posts * 2 == size)
+    else {
+        return Err(HGTErrorState::InvalidSize(size));
+    };
+
+    let mut tags = vec![
+        ("Filetype".to_string(), "HGT".to_string()),
+        ("Product".to_string(), format!("SRTM{arc_seconds}")),
+        ("Resolution".to_string(), arc_seconds.to_string()),
+    ];
+
+    if scan {
+        scan_posts(reader, posts, &mut tags)?;
+    }
+
+    let (west, south) = origin;
+    let corners = vec![
+        (west, south + 1.0),
+        (west + 1.0, south + 1.0),
+        (west + 1.0, south),
+        (west, south),
+    ];
+    Ok(HGTMetaData {
+        region: Region::envelope(&corners),
+        tags,
+        footprint: Some(corners),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::tests::tag;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn mock_hgt(posts: usize, elevation: impl Fn(usize) -> i16) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        let data: Vec<u8> = (0..posts * posts)
+            .flat_map(|post| elevation(post).to_be_bytes())
+            .collect();
+        file.write_all(&data).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_tile_origin() {
+        assert_eq!(tile_origin("N51W002").unwrap(), (-2.0, 51.0));
+        assert_eq!(tile_origin("s12e130.SRTMGL1").unwrap(), (130.0, -12.0));
+        assert!(tile_origin("N51").is_err());
+        assert!(tile_origin("X51W002").is_err());
+        assert!(tile_origin("N91W002").is_err());
+        assert_eq!(tile_origin("N51W180").unwrap(), (-180.0, 51.0));
+        assert!(tile_origin("N51E180").is_err());
+        assert!(tile_origin("N5éW002").is_err());
+        assert!(tile_origin("Né1W002").is_err());
+        assert!(tile_origin("N51W00é").is_err());
+    }
+
+    #[test]
+    fn test_parse_hgt() {
+        // Every other post void, the rest rising from 0.
+        let file = mock_hgt(1201, |post| match post % 2 {
+            0 => VOID_ELEVATION,
+            _ => (post % 1000) as i16,
+        });
+        let metadata =
+            parse_hgt("N51W002", &mut BufReader::new(file.reopen().unwrap()), true).unwrap();
+        assert_eq!(metadata.region.top_left, (-2.0, 52.0));
+        assert_eq!(metadata.region.bottom_right, (-1.0, 51.0));
+        assert_eq!(tag(&metadata.tags, "Product"), Some("SRTM3"));
+        assert_eq!(tag(&metadata.tags, "Resolution"), Some("3"));
+        assert_eq!(tag(&metadata.tags, "MinElevation"), Some("1"));
+        assert_eq!(tag(&metadata.tags, "MaxElevation"), Some("999"));
+        assert_eq!(tag(&metadata.tags, "VoidCount"), Some("721201"));
+    }
+
+    #[test]
+    fn test_parse_hgt_one_arc_second() {
+        let file = mock_hgt(3601, |_| 25);
+        let metadata =
+            parse_hgt("S34E151", &mut BufReader::new(file.reopen().unwrap()), true).unwrap();
+        assert_eq!(metadata.region.top_left, (151.0, -33.0));
+        assert_eq!(tag(&metadata.tags, "Product"), Some("SRTM1"));
+        assert_eq!(tag(&metadata.tags, "MeanElevation"), Some("25.0"));
+        assert!(tag(&metadata.tags, "VoidCount").is_none());
+    }
+
+    #[test]
+    fn test_parse_hgt_without_scan() {
+        // Only the name and size are needed, so the posts aren't read.
+        let file = mock_hgt(1201, |_| 25);
+        let metadata = parse_hgt(
+            "N51W002",
+            &mut BufReader::new(file.reopen().unwrap()),
+            false,
+        )
+        .unwrap();
+        assert_eq!(metadata.region.top_left, (-2.0, 52.0));
+        assert_eq!(tag(&metadata.tags, "Product"), Some("SRTM3"));
+        assert!(tag(&metadata.tags, "MinElevation").is_none());
+    }
+
+    #[test]
+    fn test_parse_hgt_invalid_size() {
+        let file = mock_hgt(100, |_| 0);
+        assert!(matches!(
+            parse_hgt("N51W002", &mut BufReader::new(file.reopen().unwrap()), true),
+            Err(HGTErrorState::InvalidSize(20000))
+        ));
+    }
+}
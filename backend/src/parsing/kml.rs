@@ -1,12 +1,12 @@
 use crate::parsing::kml::KMLErrorState::{NotEnoughGeoData, UnexpectedFormat};
-use crate::spatial::Coordinate;
+use crate::spatial::{antimeridian_footprint, Coordinate, FootprintBuffer};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::io::BufReader;
+use std::io::Read;
 use std::path::PathBuf;
 use std::str::FromStr;
+use tracing::{event, Level};
 use xml::reader::{EventReader, XmlEvent};
 
 pub fn get_boundaries(coordinates: Vec<Coordinate>) -> (Coordinate, Coordinate) {
@@ -40,6 +40,7 @@ pub struct KMLRegion {
 pub struct KMLMetadata {
     pub region: KMLRegion,
     pub tags: Vec<(String, String)>,
+    pub footprint: Option<Vec<Coordinate>>,
 }
 
 #[derive(Debug)]
@@ -69,75 +70,250 @@ impl Display for KMLErrorState {
 }
 impl Error for KMLErrorState {}
 
-pub fn parse_kml(reader: &mut BufReader<File>) -> Result<KMLMetadata, KMLErrorState> {
-    // Initialise Event iterator, as well as coordinate buffer.
-    let mut reader = EventReader::new(reader).into_iter();
-    let tags = vec![("Filetype".to_string(), "KML".to_string())];
-    let mut coordinates: Vec<(f64, f64)> = vec![];
+// Elements which are KML features, the outermost of which describes the whole file.
+const FEATURES: [&str; 8] = [
+    "Document",
+    "Folder",
+    "Placemark",
+    "GroundOverlay",
+    "ScreenOverlay",
+    "PhotoOverlay",
+    "NetworkLink",
+    "Tour",
+];
+// Most ExtendedData entries taken as tags, later ones are ignored.
+const MAX_EXTENDED_DATA: usize = 64;
 
-    while let Some(Ok(event)) = reader.next() {
-        // Capture events until file over.
+fn parse_number(value: &str) -> Result<f64, KMLErrorState> {
+    match f64::from_str(value.trim()) {
+        Ok(v) if v.is_finite() => Ok(v),
+        e => Err(UnexpectedFormat(format!(
+            "Failed to parse floating point coord: {value} with err: {e:?}"
+        ))),
+    }
+}
+
+// Tuples of longitude,latitude[,altitude] separated by whitespace.
+// Whitespace around the commas, which some writers add, is tolerated.
+fn parse_coordinates(text: &str) -> Result<Vec<Coordinate>, KMLErrorState> {
+    let mut normalised = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == ',' {
+            normalised.truncate(normalised.trim_end().len());
+            normalised.push(c);
+        } else if !(c.is_whitespace() && normalised.ends_with(',')) {
+            normalised.push(c);
+        }
+    }
+    normalised
+        .split_whitespace()
+        .map(|tuple| {
+            let values: Vec<&str> = tuple.split(',').collect();
+            if values.len() < 2 {
+                return Err(UnexpectedFormat(format!(
+                    "Expected coordinate pair of len 2, got: {:?}",
+                    values
+                )));
+            }
+            Ok((parse_number(values[0])?, parse_number(values[1])?))
+        })
+        .collect()
+}
+
+// A gx:coord is a single space separated longitude latitude altitude.
+fn parse_gx_coord(text: &str) -> Result<Coordinate, KMLErrorState> {
+    let values: Vec<&str> = text.split_whitespace().collect();
+    if values.len() < 2 {
+        return Err(UnexpectedFormat(format!(
+            "Expected gx:coord of at least 2 values, got: {:?}",
+            values
+        )));
+    }
+    Ok((parse_number(values[0])?, parse_number(values[1])?))
+}
+
+// A LatLonBox of a GroundOverlay, or LatLonAltBox of a Region.
+#[derive(Debug, Default)]
+struct LatLonBox {
+    north: Option<f64>,
+    south: Option<f64>,
+    east: Option<f64>,
+    west: Option<f64>,
+    // Degrees anticlockwise, only GroundOverlays are rotated.
+    rotation: f64,
+}
+
+impl LatLonBox {
+    fn set(&mut self, edge: &str, value: f64) {
+        match edge {
+            "north" => self.north = Some(value),
+            "south" => self.south = Some(value),
+            "east" => self.east = Some(value),
+            "west" => self.west = Some(value),
+            "rotation" => self.rotation = value,
+            _ => {}
+        }
+    }
+
+    // Corners, rotated about the centre. Across the antimeridian east is beyond 180.
+    fn corners(&self) -> Option<[Coordinate; 4]> {
+        let (north, south, west) = (self.north?, self.south?, self.west?);
+        let mut east = self.east?;
+        if east < west {
+            east += 360.0;
+        }
+        let centre = ((west + east) / 2.0, (north + south) / 2.0);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        Some(
+            [(west, south), (east, south), (east, north), (west, north)].map(|(x, y)| {
+                let (dx, dy) = (x - centre.0, y - centre.1);
+                (
+                    centre.0 + dx * cos - dy * sin,
+                    centre.1 + dx * sin + dy * cos,
+                )
+            }),
+        )
+    }
+}
+
+pub fn parse_kml<R: Read>(reader: &mut R) -> Result<KMLMetadata, KMLErrorState> {
+    let reader = EventReader::new(reader);
+    let mut tags = vec![("Filetype".to_string(), "KML".to_string())];
+    let mut coordinates = FootprintBuffer::default();
+    // Whether any geometry crosses the antimeridian.
+    let mut wraps = false;
+
+    // Local names of the open elements, so namespaced and unprefixed elements are treated alike.
+    let mut stack: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut outer_feature: Option<usize> = None;
+    let mut lat_lon_box: Option<LatLonBox> = None;
+    let mut data_name: Option<String> = None;
+    let mut extended_data = 0;
+
+    for event in reader {
+        let event = event.map_err(|e| UnexpectedFormat(format!("Invalid XML: {e}")))?;
         match event {
-            XmlEvent::StartElement { name, .. } if name.local_name == "coordinates" => {
-                // When Coordinate element starts...
-                while let Some(Ok(event)) = reader.next() {
-                    // Start capturing all events until Coordinate element ends.
-                    match event {
-                        XmlEvent::Characters(_0) => {
-                            // While capturing, get all raw chars.
-                            // Conform data into coordinate pairs...
-                            let _0 = _0.replace("\n", "");
-                            let coordinate_pairs = _0.split_whitespace(); // Split by whitespace, each coord set is space seperated.
-                            for coordinate_pair in coordinate_pairs {
-                                let coordinate_strs: Vec<&str> =
-                                    coordinate_pair.split(",").collect();
-                                if coordinate_strs.len() < 2 {
-                                    return Err(UnexpectedFormat(format!(
-                                        "Expected coordinate pair of len 2, got: {:?}",
-                                        coordinate_strs
-                                    )));
-                                }
-                                coordinates.push((
-                                    match f64::from_str(coordinate_strs[0]) {
-                                        Ok(v) => v,
-                                        Err(e) => {
-                                            return Err(UnexpectedFormat(format!("Failed to parse floating point coord: {} with err: {:?}", coordinate_strs[0], e)));
-                                        }
-                                    }, match f64::from_str(coordinate_strs[1]) {
-                                        Ok(v) => v,
-                                        Err(e) => {
-                                            return Err(UnexpectedFormat(format!("Failed to parse floating point coord: {} with err: {:?}", coordinate_strs[1], e)));
-                                        }
-                                    }
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                match name.local_name.as_str() {
+                    "kml" => {
+                        // Such as http://www.opengis.net/kml/2.2 or http://earth.google.com/kml/2.1
+                        if let Some(version) =
+                            name.namespace.as_deref().and_then(|n| n.rsplit('/').next())
+                        {
+                            tags.push(("KMLVersion".to_string(), version.to_string()));
+                        }
+                    }
+                    "LatLonBox" | "LatLonAltBox" => lat_lon_box = Some(LatLonBox::default()),
+                    "Data" | "SimpleData" => {
+                        data_name = attributes
+                            .into_iter()
+                            .find(|a| a.name.local_name == "name")
+                            .map(|a| a.value)
+                    }
+                    feature if FEATURES.contains(&feature) && outer_feature.is_none() => {
+                        outer_feature = Some(stack.len())
+                    }
+                    _ => {}
+                }
+                stack.push(name.local_name);
+                text.clear();
+            }
+            XmlEvent::Characters(s) | XmlEvent::CData(s) => text.push_str(&s),
+            XmlEvent::EndElement { name } => {
+                stack.pop();
+                let parent = stack.last().map(String::as_str);
+                match name.local_name.as_str() {
+                    // Also the corners of a gx:LatLonQuad.
+                    "coordinates" => coordinates.extend(parse_coordinates(&text)?),
+                    // Points of a gx:Track.
+                    "coord" => coordinates.push(parse_gx_coord(&text)?),
+                    edge @ ("north" | "south" | "east" | "west" | "rotation") => {
+                        if let Some(lat_lon_box) = lat_lon_box.as_mut() {
+                            lat_lon_box.set(edge, parse_number(&text)?);
+                        }
+                    }
+                    element @ ("LatLonBox" | "LatLonAltBox") => {
+                        match lat_lon_box.take().and_then(|b| b.corners()) {
+                            Some(corners) => {
+                                wraps |= corners.iter().any(|(x, _)| *x > 180.0);
+                                coordinates.extend(corners);
+                            }
+                            None => event!(
+                                Level::WARN,
+                                "Ignoring {element} without all of north, south, east and west."
+                            ),
+                        }
+                    }
+                    element @ ("name" | "description")
+                        if outer_feature.map(|depth| depth + 1) == Some(stack.len()) =>
+                    {
+                        let key = match element {
+                            "name" => "Name",
+                            _ => "Description",
+                        };
+                        tags.push((key.to_string(), text.trim().to_string()));
+                    }
+                    element @ ("value" | "SimpleData")
+                        if element == "SimpleData" || parent == Some("Data") =>
+                    {
+                        if let Some(data_name) = data_name.take() {
+                            if extended_data < MAX_EXTENDED_DATA {
+                                tags.push((
+                                    format!("ExtendedData.{data_name}"),
+                                    text.trim().to_string(),
                                 ));
                             }
+                            extended_data += 1;
                         }
-                        XmlEvent::EndElement { name } if name.local_name == "coordinates" => break, // Handle end of coordinate element
-                        _ => {} // Ignore contained elems
                     }
+                    // Files linked to can't be followed, but are noted.
+                    "href" if stack.iter().any(|e| e == "NetworkLink") => {
+                        tags.push(("NetworkLink".to_string(), text.trim().to_string()));
+                    }
+                    // Images of Ground, Screen and PhotoOverlays.
+                    "href"
+                        if parent == Some("Icon")
+                            && stack.iter().any(|e| e.ends_with("Overlay")) =>
+                    {
+                        tags.push(("Overlay".to_string(), text.trim().to_string()));
+                    }
+                    _ => {}
                 }
+                text.clear();
             }
-            _ => {} // Ignore all but start elem.
+            _ => {}
         }
     }
 
-    if coordinates.len() == 0 {
+    let coordinates = coordinates.points;
+    if coordinates.is_empty() {
         return Err(NotEnoughGeoData);
     }
 
-    let (bottom_left, top_right) = get_boundaries(coordinates); // Draw a bounding box around given coords
+    let (mut bottom_left, mut top_right) = get_boundaries(coordinates.clone()); // Draw a bounding box around given coords
+    let footprint = antimeridian_footprint(
+        coordinates,
+        wraps,
+        (&mut bottom_left.0, &mut top_right.0),
+        &mut tags,
+    );
     return Ok(KMLMetadata {
         region: KMLRegion {
             bottom_left,
             top_right,
         },
         tags,
+        footprint,
     }); // Return region defined by file.
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parsing::tests::tag;
     use std::io::BufReader;
     use std::io::{Seek, Write};
     use tempfile::tempfile;
@@ -301,4 +477,159 @@ mod tests {
 
         assert!(result.is_ok());
     }
+
+    fn parse(kml_data: &str) -> Result<KMLMetadata, KMLErrorState> {
+        let mut file = tempfile().unwrap();
+        write!(file, "{}", kml_data).unwrap();
+        file.seek(std::io::SeekFrom::Start(0)).unwrap();
+        parse_kml(&mut BufReader::new(file))
+    }
+
+    #[test]
+    fn test_parse_kml_ground_overlay() {
+        let result = parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <kml xmlns="http://www.opengis.net/kml/2.2">
+                <GroundOverlay>
+                    <name>Overlay</name>
+                    <Icon><href>overlay.png</href></Icon>
+                    <LatLonBox>
+                        <north>10.0</north>
+                        <south>8.0</south>
+                        <east>4.0</east>
+                        <west>2.0</west>
+                    </LatLonBox>
+                </GroundOverlay>
+            </kml>"#,
+        )
+        .unwrap();
+        assert_eq!(result.region.bottom_left, (2.0, 8.0));
+        assert_eq!(result.region.top_right, (4.0, 10.0));
+        assert_eq!(tag(&result.tags, "KMLVersion"), Some("2.2"));
+        assert_eq!(tag(&result.tags, "Name"), Some("Overlay"));
+        assert_eq!(tag(&result.tags, "Overlay"), Some("overlay.png"));
+        assert!(tag(&result.tags, "NetworkLink").is_none());
+    }
+
+    #[test]
+    fn test_parse_kml_rotated_lat_lon_box() {
+        let result = parse(
+            r#"<kml><GroundOverlay><LatLonBox>
+                <north>1</north><south>-1</south><east>1</east><west>-1</west>
+                <rotation>45</rotation>
+            </LatLonBox></GroundOverlay></kml>"#,
+        )
+        .unwrap();
+        let diagonal = 2f64.sqrt();
+        assert!((result.region.top_right.0 - diagonal).abs() < 1e-9);
+        assert!((result.region.top_right.1 - diagonal).abs() < 1e-9);
+        assert!((result.region.bottom_left.0 + diagonal).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_kml_lat_lon_box_across_antimeridian() {
+        let result = parse(
+            r#"<kml><GroundOverlay><LatLonBox>
+                <north>-10</north><south>-20</south><east>-170</east><west>170</west>
+            </LatLonBox></GroundOverlay></kml>"#,
+        )
+        .unwrap();
+        assert_eq!(result.region.bottom_left, (-180.0, -20.0));
+        assert_eq!(result.region.top_right, (180.0, -10.0));
+        assert_eq!(tag(&result.tags, "CrossesAntimeridian"), Some("true"));
+        assert!(result.footprint.is_none());
+    }
+
+    #[test]
+    fn test_parse_kml_network_link_region() {
+        let result = parse(
+            r#"<kml xmlns="http://earth.google.com/kml/2.1">
+                <NetworkLink>
+                    <Region>
+                        <LatLonAltBox>
+                            <north>51</north><south>50</south><east>1</east><west>0</west>
+                            <minAltitude>0</minAltitude>
+                        </LatLonAltBox>
+                    </Region>
+                    <Link><href>http://example.com/tiles.kml</href></Link>
+                </NetworkLink>
+            </kml>"#,
+        )
+        .unwrap();
+        assert_eq!(result.region.bottom_left, (0.0, 50.0));
+        assert_eq!(result.region.top_right, (1.0, 51.0));
+        assert_eq!(tag(&result.tags, "KMLVersion"), Some("2.1"));
+        assert_eq!(
+            tag(&result.tags, "NetworkLink"),
+            Some("http://example.com/tiles.kml")
+        );
+    }
+
+    #[test]
+    fn test_parse_kml_gx_track_and_lat_lon_quad() {
+        let result = parse(
+            r#"<kml:kml xmlns:kml="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2">
+                <kml:Document>
+                    <kml:Placemark>
+                        <gx:Track>
+                            <kml:when>2010-05-28T02:02:09Z</kml:when>
+                            <gx:coord>-122.207881 37.371915 156.0</gx:coord>
+                            <gx:coord>-122.205712 37.373288 152.0</gx:coord>
+                        </gx:Track>
+                    </kml:Placemark>
+                    <kml:GroundOverlay>
+                        <gx:LatLonQuad>
+                            <kml:coordinates>
+                                -123.0,37.0 -122.5,37.0
+                                -122.5, 37.5 -123.0, 37.5
+                            </kml:coordinates>
+                        </gx:LatLonQuad>
+                    </kml:GroundOverlay>
+                </kml:Document>
+            </kml:kml>"#,
+        )
+        .unwrap();
+        assert_eq!(result.region.bottom_left, (-123.0, 37.0));
+        assert_eq!(result.region.top_right, (-122.205712, 37.5));
+        assert_eq!(tag(&result.tags, "KMLVersion"), Some("2.2"));
+    }
+
+    #[test]
+    fn test_parse_kml_document_tags() {
+        let result = parse(
+            r#"<kml xmlns="http://www.opengis.net/kml/2.2">
+                <Document>
+                    <name>Survey</name>
+                    <description><![CDATA[<b>Field</b> survey]]></description>
+                    <ExtendedData>
+                        <Data name="author"><displayName>Author</displayName><value>A. Surveyor</value></Data>
+                        <SchemaData schemaUrl="schema.kml"><SimpleData name="year">2021</SimpleData></SchemaData>
+                    </ExtendedData>
+                    <Placemark>
+                        <name>Site 1</name>
+                        <description>Not the document's</description>
+                        <Point><coordinates>1,2</coordinates></Point>
+                    </Placemark>
+                </Document>
+            </kml>"#,
+        )
+        .unwrap();
+        assert_eq!(tag(&result.tags, "Name"), Some("Survey"));
+        assert_eq!(
+            tag(&result.tags, "Description"),
+            Some("<b>Field</b> survey")
+        );
+        assert_eq!(
+            tag(&result.tags, "ExtendedData.author"),
+            Some("A. Surveyor")
+        );
+        assert_eq!(tag(&result.tags, "ExtendedData.year"), Some("2021"));
+        assert_eq!(result.tags.iter().filter(|(k, _)| k == "Name").count(), 1);
+    }
+
+    #[test]
+    fn test_parse_kml_invalid_xml() {
+        let result = parse(r#"<kml><Placemark><coordinates>1,2</coordinates></kml>"#);
+        assert!(matches!(result, Err(UnexpectedFormat(_))));
+    }
 }
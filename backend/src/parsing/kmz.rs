@@ -0,0 +1,234 @@
+use crate::parsing::kml::{parse_kml, KMLErrorState, KMLMetadata};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{BufReader, Read, Seek};
+use std::path::PathBuf;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+// Largest root KML that is decompressed, guarding against zip bombs.
+const MAX_KML_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KMZMap {
+    pub(crate) path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum KMZErrorState {
+    ZipError(ZipError),
+    KMLError(KMLErrorState),
+    NoRootKML,
+    // Uncompressed size of the root KML, in bytes.
+    KMLTooLarge(u64),
+}
+
+impl Display for KMZErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KMZErrorState::ZipError(e) => write!(f, "ZipError: {e}"),
+            KMZErrorState::KMLError(e) => write!(f, "KMLError: {e}"),
+            KMZErrorState::NoRootKML => write!(f, "No KML document within the KMZ archive!"),
+            KMZErrorState::KMLTooLarge(size) => write!(
+                f,
+                "Root KML of {size} bytes is over the limit of {MAX_KML_SIZE} bytes!"
+            ),
+        }
+    }
+}
+
+impl Error for KMZErrorState {}
+
+impl From<ZipError> for KMZErrorState {
+    fn from(value: ZipError) -> Self {
+        KMZErrorState::ZipError(value)
+    }
+}
+
+impl From<KMLErrorState> for KMZErrorState {
+    fn from(value: KMLErrorState) -> Self {
+        KMZErrorState::KMLError(value)
+    }
+}
+
+fn is_kml(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".kml")
+}
+
+// As Google Earth does, the first KML at the top level of the archive, usually doc.kml.
+// Archives with KML only in folders fall back to the first of those.
+fn root_kml(names: &[String]) -> Option<&String> {
+    names
+        .iter()
+        .find(|name| is_kml(name) && !name.contains('/'))
+        .or_else(|| names.iter().find(|name| is_kml(name)))
+}
+
+// Path within the archive of a link relative to the root KML, None for absolute links.
+fn resolve(root: &str, href: &str) -> Option<String> {
+    if href.contains("://") || href.starts_with('/') {
+        return None;
+    }
+    let mut path: Vec<&str> = root.split('/').collect();
+    path.pop();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                path.pop()?;
+            }
+            part => path.push(part),
+        }
+    }
+    Some(path.join("/"))
+}
+
+// Indexes a KMZ by its root KML, recording which of the overlay images it uses are within the archive.
+pub fn parse_kmz<R: Read + Seek>(reader: R) -> Result<KMLMetadata, KMZErrorState> {
+    parse_kmz_with_limit(reader, MAX_KML_SIZE)
+}
+
+fn parse_kmz_with_limit<R: Read + Seek>(
+    reader: R,
+    max_size: u64,
+) -> Result<KMLMetadata, KMZErrorState> {
+    let mut archive = ZipArchive::new(reader)?;
+    // In central directory order, as file_names() isn't.
+    let names = (0..archive.len())
+        .map(|i| Ok(archive.by_index_raw(i)?.name().to_string()))
+        .collect::<Result<Vec<String>, ZipError>>()?;
+    let root = root_kml(&names).ok_or(KMZErrorState::NoRootKML)?.clone();
+
+    let mut metadata = {
+        let document = archive.by_name(&root)?;
+        if document.size() > max_size {
+            return Err(KMZErrorState::KMLTooLarge(document.size()));
+        }
+        // The recorded size may understate the data, so decompression stops at the limit regardless.
+        parse_kml(&mut BufReader::new(document.take(max_size)))?
+    };
+
+    for (key, value) in metadata.tags.iter_mut() {
+        if key == "Filetype" {
+            *value = "KMZ".to_string();
+        }
+    }
+    let embedded: Vec<String> = metadata
+        .tags
+        .iter()
+        .filter(|(key, _)| key == "Overlay")
+        .filter_map(|(_, href)| resolve(&root, href.trim()))
+        .filter(|path| names.contains(path))
+        .collect();
+    metadata.tags.push(("RootKML".to_string(), root));
+    for path in embedded {
+        metadata.tags.push(("EmbeddedOverlay".to_string(), path));
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::tests::tags;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    const PACKAGED_KML: &[u8] = br#"<kml xmlns="http://www.opengis.net/kml/2.2"><Document><name>Packaged</name><GroundOverlay><name>Overlay</name><Icon><href>files/overlay.png</href></Icon><LatLonBox><north>10.0</north><south>8.0</south><east>4.0</east><west>2.0</west></LatLonBox></GroundOverlay><Placemark><name>Point</name><Point><coordinates>3.0,9.0,0</coordinates></Point></Placemark></Document></kml>"#;
+
+    // Archive of DEFLATE compressed entries, in the order given.
+    fn mock_kmz(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, data) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_parse_kmz() {
+        let archive = mock_kmz(&[("doc.kml", PACKAGED_KML), ("files/overlay.png", b"png")]);
+        let metadata = parse_kmz(Cursor::new(archive)).unwrap();
+        assert_eq!(metadata.region.bottom_left, (2.0, 8.0));
+        assert_eq!(metadata.region.top_right, (4.0, 10.0));
+        assert_eq!(tags(&metadata.tags, "Filetype"), vec!["KMZ"]);
+        assert_eq!(tags(&metadata.tags, "Name"), vec!["Packaged"]);
+        assert_eq!(tags(&metadata.tags, "RootKML"), vec!["doc.kml"]);
+        assert_eq!(
+            tags(&metadata.tags, "EmbeddedOverlay"),
+            vec!["files/overlay.png"]
+        );
+    }
+
+    #[test]
+    fn test_root_kml() {
+        let kml =
+            br#"<kml><Placemark><Point><coordinates>1,2</coordinates></Point></Placemark></kml>"#;
+        let other =
+            br#"<kml><Placemark><Point><coordinates>5,6</coordinates></Point></Placemark></kml>"#;
+        // Top level KML is preferred, whatever its name and position.
+        let archive = mock_kmz(&[("files/other.kml", other), ("Root.KML", kml)]);
+        let metadata = parse_kmz(Cursor::new(archive)).unwrap();
+        assert_eq!(metadata.region.bottom_left, (1.0, 2.0));
+        assert_eq!(tags(&metadata.tags, "RootKML"), vec!["Root.KML"]);
+
+        let archive = mock_kmz(&[("files/other.kml", other)]);
+        let metadata = parse_kmz(Cursor::new(archive)).unwrap();
+        assert_eq!(metadata.region.bottom_left, (5.0, 6.0));
+    }
+
+    #[test]
+    fn test_overlay_outside_archive() {
+        let kml = br#"<kml><GroundOverlay><Icon><href>http://example.com/a.png</href></Icon>
+            <LatLonBox><north>1</north><south>0</south><east>1</east><west>0</west></LatLonBox>
+            </GroundOverlay><GroundOverlay><Icon><href>missing.png</href></Icon>
+            <LatLonBox><north>1</north><south>0</south><east>1</east><west>0</west></LatLonBox>
+            </GroundOverlay></kml>"#;
+        let archive = mock_kmz(&[("doc.kml", kml)]);
+        let metadata = parse_kmz(Cursor::new(archive)).unwrap();
+        assert_eq!(tags(&metadata.tags, "Overlay").len(), 2);
+        assert!(tags(&metadata.tags, "EmbeddedOverlay").is_empty());
+    }
+
+    #[test]
+    fn test_no_root_kml() {
+        let archive = mock_kmz(&[("image.png", b"png")]);
+        assert!(matches!(
+            parse_kmz(Cursor::new(archive)),
+            Err(KMZErrorState::NoRootKML)
+        ));
+    }
+
+    #[test]
+    fn test_kml_too_large() {
+        let archive = mock_kmz(&[("doc.kml", PACKAGED_KML)]);
+        assert!(matches!(
+            parse_kmz_with_limit(Cursor::new(archive), 100),
+            Err(KMZErrorState::KMLTooLarge(size)) if size == PACKAGED_KML.len() as u64
+        ));
+    }
+
+    #[test]
+    fn test_not_a_zip() {
+        assert!(matches!(
+            parse_kmz(Cursor::new(PACKAGED_KML.to_vec())),
+            Err(KMZErrorState::ZipError(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("doc.kml", "files/a.png").unwrap(), "files/a.png");
+        assert_eq!(resolve("kml/doc.kml", "./a.png").unwrap(), "kml/a.png");
+        assert_eq!(
+            resolve("kml/doc.kml", "../images/a.png").unwrap(),
+            "images/a.png"
+        );
+        assert!(resolve("doc.kml", "../a.png").is_none());
+        assert!(resolve("doc.kml", "https://example.com/a.png").is_none());
+    }
+}
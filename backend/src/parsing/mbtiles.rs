@@ -1,9 +1,12 @@
 use crate::spatial::Coordinate;
-use rusqlite::{Connection, Result};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
+use tracing::{event, Level};
 
 #[derive(Debug)]
 pub struct MBTilesRegion {
@@ -22,71 +25,206 @@ pub struct MBTilesMap {
     pub(crate) path: PathBuf,
 }
 
-pub fn parse_mbtiles(filepath: &str) -> Result<MBTilesMetaData> {
-    //Tags for metadata
-    let mut tags = vec![("Filetype".to_string(), "MBTILES".to_string())];
+#[derive(Debug)]
+pub enum MBTilesErrorState {
+    ConnectionError(rusqlite::Error),
+    QueryError(rusqlite::Error),
+    UnexpectedFormat(String),
+    NotEnoughGeoData,
+}
+
+impl Display for MBTilesErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MBTilesErrorState::ConnectionError(e) =>
+                    format!("Failed to open MBTiles database: {e}"),
+                MBTilesErrorState::QueryError(e) =>
+                    format!("Failed to query MBTiles database: {e}"),
+                MBTilesErrorState::UnexpectedFormat(s) => format!("UnexpectedFormatError: {s}"),
+                MBTilesErrorState::NotEnoughGeoData =>
+                    "Not enough geographic data within the file to establish a boundary!"
+                        .to_string(),
+            }
+        )
+    }
+}
 
-    let conn_result = Connection::open(filepath);
+impl Error for MBTilesErrorState {}
 
-    //Some error handling
-    let conn = match conn_result {
-        Ok(conn) => conn,
-        Err(error) => panic!("Problem Occurred when connecting to DB file: {:?}", error),
-    };
+// Deepest zoom level accepted from the tiles table, well before 2^zoom overflows.
+const MAX_ZOOM: u32 = 30;
+
+// Metadata keys copied straight into tags, when present.
+const METADATA_TAGS: [(&str, &str); 4] = [
+    ("name", "Name"),
+    ("format", "Format"),
+    ("minzoom", "MinZoom"),
+    ("maxzoom", "MaxZoom"),
+];
+
+// Converts a tile column at a given zoom into the longitude of its western edge.
+fn tile_x_to_long(x: f64, zoom: u32) -> f64 {
+    x / 2f64.powi(zoom as i32) * 360.0 - 180.0
+}
+
+// Converts an XYZ (origin top left) tile row at a given zoom into the latitude of its northern edge.
+fn tile_y_to_lat(y: f64, zoom: u32) -> f64 {
+    let n = std::f64::consts::PI * (1.0 - 2.0 * y / 2f64.powi(zoom as i32));
+    n.sinh().atan().to_degrees()
+}
 
-    //Prepares a query to return bounds of MBTiles using metadata table
-    let stmt_result = conn.prepare("SELECT * FROM metadata WHERE name = 'bounds'");
+// Parses the metadata bounds string, stored as left, bottom, right, top.
+fn parse_bounds(value: &str) -> Result<MBTilesRegion, MBTilesErrorState> {
+    let values = value
+        .split(',')
+        .map(|s| s.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|e| {
+            MBTilesErrorState::UnexpectedFormat(format!(
+                "Failed to parse bounds: {value:?}, with err: {e:?}"
+            ))
+        })?;
 
-    //Some error handling
-    let mut stmt = match stmt_result {
-        Ok(stmt) => stmt,
-        Err(error) => panic!("Problem Occurred when querying the DB: {:?}", error),
+    if values.len() != 4 {
+        return Err(MBTilesErrorState::UnexpectedFormat(format!(
+            "Expected 4 comma separated bounds values, got: {value:?}"
+        )));
+    }
+
+    //Goes long then lat
+    Ok(MBTilesRegion {
+        top_left: (values[0], values[3]),
+        bottom_right: (values[2], values[1]),
+    })
+}
+
+// Derives bounds from the tile indices present at the deepest zoom level.
+// MBTiles stores rows in TMS order (origin bottom left), unless the legacy `scheme` key says xyz.
+fn bounds_from_tiles(conn: &Connection, xyz: bool) -> Result<MBTilesRegion, MBTilesErrorState> {
+    let extent: Option<(u32, u32, u32, u32, u32)> = conn
+        .query_row(
+            "SELECT zoom_level, MIN(tile_column), MAX(tile_column), MIN(tile_row), MAX(tile_row) \
+             FROM tiles WHERE zoom_level = (SELECT MAX(zoom_level) FROM tiles) GROUP BY zoom_level",
+            [],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(MBTilesErrorState::QueryError)?;
+
+    let Some((zoom, min_col, max_col, min_row, max_row)) = extent else {
+        return Err(MBTilesErrorState::NotEnoughGeoData);
     };
 
-    let mut rows = stmt.query([])?;
+    if zoom > MAX_ZOOM {
+        return Err(MBTilesErrorState::UnexpectedFormat(format!(
+            "Zoom level {zoom} is beyond the maximum of {MAX_ZOOM}!"
+        )));
+    }
+    let tiles = 2u64.pow(zoom) as f64;
+    if max_col as f64 >= tiles || max_row as f64 >= tiles {
+        return Err(MBTilesErrorState::UnexpectedFormat(format!(
+            "Tile index out of range for zoom level {zoom}!"
+        )));
+    }
+
+    // Convert to XYZ rows, where the smallest row is the northernmost.
+    let (north_row, south_row) = if xyz {
+        (min_row as f64, max_row as f64)
+    } else {
+        (tiles - 1.0 - max_row as f64, tiles - 1.0 - min_row as f64)
+    };
 
-    let mut top_left_result: (f64, f64) = (0.0, 0.0);
-    let mut bottom_right_result: (f64, f64) = (0.0, 0.0);
+    Ok(MBTilesRegion {
+        top_left: (
+            tile_x_to_long(min_col as f64, zoom),
+            tile_y_to_lat(north_row, zoom),
+        ),
+        bottom_right: (
+            tile_x_to_long(max_col as f64 + 1.0, zoom),
+            tile_y_to_lat(south_row + 1.0, zoom),
+        ),
+    })
+}
 
-    //Iterate through result rows of query
-    while let Some(row) = rows.next().unwrap() {
-        let value: String = row.get("value").unwrap();
+pub fn parse_mbtiles(filepath: &str) -> Result<MBTilesMetaData, MBTilesErrorState> {
+    //Tags for metadata
+    let mut tags = vec![("Filetype".to_string(), "MBTILES".to_string())];
 
-        //Mbtiles bounds coordinates are stored as left, bottom, right, top
-        let values_result = value
-            .split(',')
-            .map(|s| s.trim().parse::<f64>())
-            .collect::<Result<_, _>>();
+    // Open read only, we never want indexing to create or modify a database.
+    let conn = Connection::open_with_flags(filepath, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(MBTilesErrorState::ConnectionError)?;
 
-        //Some error handling
-        let values: Vec<f64> = match values_result {
-            Ok(values) => values,
-            Err(error) => panic!(
-                "Problem Occurred when parsing coordinates to float64: {:?}",
-                error
-            ),
+    // Read the whole metadata table, it is a small key value store.
+    let mut stmt = conn
+        .prepare("SELECT name, value FROM metadata")
+        .map_err(MBTilesErrorState::QueryError)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Value>(1)?))
+        })
+        .map_err(MBTilesErrorState::QueryError)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(MBTilesErrorState::QueryError)?;
+    // Values aren't always stored as text, such as minzoom and maxzoom written as integers.
+    let mut metadata = HashMap::new();
+    for (key, value) in rows {
+        let value = match value {
+            Value::Text(text) => text,
+            Value::Integer(number) => number.to_string(),
+            Value::Real(number) => number.to_string(),
+            value => {
+                event!(
+                    Level::WARN,
+                    "Ignoring MBTiles metadata {key} of {filepath}, with unreadable value {value:?}"
+                );
+                continue;
+            }
         };
+        metadata.insert(key, value);
+    }
 
-        //Goes long then lat
-
-        bottom_right_result = (values[2], values[1]);
-        top_left_result = (values[0], values[3]);
+    for (key, tag) in METADATA_TAGS {
+        if let Some(value) = metadata.get(key) {
+            tags.push((tag.to_string(), value.clone()));
+        }
     }
 
-    return Ok(MBTilesMetaData {
-        region: MBTilesRegion {
-            top_left: top_left_result,
-            bottom_right: bottom_right_result,
-        },
-        tags,
-    });
+    let region = match metadata.get("bounds") {
+        Some(value) => {
+            tags.push(("BoundsSource".to_string(), "metadata".to_string()));
+            parse_bounds(value)?
+        }
+        None => {
+            let xyz = metadata.get("scheme").is_some_and(|s| s == "xyz");
+            let region = bounds_from_tiles(&conn, xyz)?;
+            event!(
+                Level::INFO,
+                "MBTiles {filepath} has no bounds metadata, derived {region:?} from tiles table."
+            );
+            tags.push(("BoundsSource".to_string(), "tiles".to_string()));
+            region
+        }
+    };
+
+    Ok(MBTilesMetaData { region, tags })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use rusqlite::{params, Connection};
-    use std::fs;
+    use std::io::Write;
     use tempfile::NamedTempFile;
 
     fn create_test_mbtiles() -> NamedTempFile {
@@ -126,6 +264,33 @@ mod tests {
             .any(|(key, value)| key == "Filetype" && value == "MBTILES"));
     }
 
+    #[test]
+    fn test_parse_mbtiles_numeric_metadata() {
+        let temp_file = create_test_mbtiles();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        // Declared without a type, so values keep their storage class.
+        conn.execute("DROP TABLE metadata;", []).unwrap();
+        conn.execute("CREATE TABLE metadata (name TEXT, value);", [])
+            .unwrap();
+        conn.execute(
+            "INSERT INTO metadata (name, value) VALUES ('bounds', '10.1,20.2,30.3,40.4'), ('minzoom', 0), ('maxzoom', 14), ('description', NULL)",
+            [],
+        )
+        .unwrap();
+
+        let metadata = parse_mbtiles(temp_file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(metadata.region.top_left, (10.1, 40.4));
+        assert!(metadata
+            .tags
+            .iter()
+            .any(|(key, value)| key == "MinZoom" && value == "0"));
+        assert!(metadata
+            .tags
+            .iter()
+            .any(|(key, value)| key == "MaxZoom" && value == "14"));
+    }
+
     #[test]
     fn test_parse_mbtiles_empty() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -134,15 +299,130 @@ mod tests {
         let conn = Connection::open(temp_file_path).unwrap();
         conn.execute("CREATE TABLE metadata (name TEXT, value TEXT);", [])
             .unwrap();
+        conn.execute(
+            "CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);",
+            [],
+        )
+        .unwrap();
 
         let result = parse_mbtiles(temp_file_path);
+        assert!(matches!(result, Err(MBTilesErrorState::NotEnoughGeoData)));
+    }
+
+    fn create_tiles_only_mbtiles(scheme: Option<&str>) -> NamedTempFile {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        conn.execute("CREATE TABLE metadata (name TEXT, value TEXT);", [])
+            .unwrap();
+        conn.execute(
+            "INSERT INTO metadata (name, value) VALUES ('name', 'No Bounds'), ('format', 'png'), ('minzoom', '0'), ('maxzoom', '1')",
+            [],
+        )
+        .unwrap();
+        if let Some(scheme) = scheme {
+            conn.execute(
+                "INSERT INTO metadata (name, value) VALUES ('scheme', ?1)",
+                params![scheme],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);",
+            [],
+        )
+        .unwrap();
+        // A single world tile at zoom 0, and the north west quarter at zoom 1 (row 1 in TMS, row 0 in XYZ).
+        let row = if scheme == Some("xyz") { 0 } else { 1 };
+        conn.execute(
+            "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (0, 0, 0, x''), (1, 0, ?1, x'')",
+            params![row],
+        )
+        .unwrap();
+        temp_file
+    }
+
+    #[test]
+    fn test_parse_mbtiles_bounds_from_tiles() {
+        let temp_file = create_tiles_only_mbtiles(None);
+        let metadata = parse_mbtiles(temp_file.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(metadata.region.top_left.0, -180.0);
+        assert!((metadata.region.top_left.1 - 85.0511287798).abs() < 1e-9);
+        assert_eq!(metadata.region.bottom_right, (0.0, 0.0));
+        assert!(metadata
+            .tags
+            .contains(&("BoundsSource".to_string(), "tiles".to_string())));
+    }
+
+    #[test]
+    fn test_parse_mbtiles_bounds_from_xyz_tiles() {
+        let temp_file = create_tiles_only_mbtiles(Some("xyz"));
+        let metadata = parse_mbtiles(temp_file.path().to_str().unwrap()).unwrap();
 
-        // If the function is expected to return an Ok response with default or empty data when no bounds are found
-        if let Ok(metadata) = result {
-            assert_eq!(metadata.region.top_left, (0.0, 0.0));
-            assert_eq!(metadata.region.bottom_right, (0.0, 0.0));
-        } else {
-            panic!("Expected Ok response with default or empty data, got Err.");
+        assert_eq!(metadata.region.top_left.0, -180.0);
+        assert!((metadata.region.top_left.1 - 85.0511287798).abs() < 1e-9);
+        assert_eq!(metadata.region.bottom_right, (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_mbtiles_zoom_out_of_range() {
+        let temp_file = create_tiles_only_mbtiles(None);
+        let conn = Connection::open(temp_file.path()).unwrap();
+        conn.execute(
+            "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (64, 0, 0, x'')",
+            [],
+        )
+        .unwrap();
+
+        let result = parse_mbtiles(temp_file.path().to_str().unwrap());
+        assert!(matches!(
+            result,
+            Err(MBTilesErrorState::UnexpectedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_mbtiles_metadata_tags() {
+        let temp_file = create_tiles_only_mbtiles(None);
+        let metadata = parse_mbtiles(temp_file.path().to_str().unwrap()).unwrap();
+
+        for tag in [
+            ("Name", "No Bounds"),
+            ("Format", "png"),
+            ("MinZoom", "0"),
+            ("MaxZoom", "1"),
+        ] {
+            assert!(metadata
+                .tags
+                .contains(&(tag.0.to_string(), tag.1.to_string())));
         }
     }
+
+    #[test]
+    fn test_parse_mbtiles_malformed_bounds() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        conn.execute("CREATE TABLE metadata (name TEXT, value TEXT);", [])
+            .unwrap();
+        conn.execute(
+            "INSERT INTO metadata (name, value) VALUES ('bounds', '10.1,abc,30.3')",
+            [],
+        )
+        .unwrap();
+
+        let result = parse_mbtiles(temp_file.path().to_str().unwrap());
+        assert!(matches!(
+            result,
+            Err(MBTilesErrorState::UnexpectedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_mbtiles_not_a_database() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"definitely not sqlite").unwrap();
+
+        let result = parse_mbtiles(temp_file.path().to_str().unwrap());
+        assert!(matches!(result, Err(MBTilesErrorState::QueryError(_))));
+    }
 }
@@ -1,36 +1,60 @@
 use crate::index::Node;
+use crate::parsing::asc::parse_asc;
 use crate::parsing::dted::parse_dted;
 use crate::parsing::geojson::parse_geojson;
 use crate::parsing::gpkg::parse_gpkg;
+use crate::parsing::gpx::parse_gpx;
+use crate::parsing::hgt::parse_hgt;
 use crate::parsing::kml::parse_kml;
+use crate::parsing::kmz::parse_kmz;
 use crate::parsing::mbtiles::parse_mbtiles;
+use crate::parsing::nitf::parse_nitf;
 use crate::MapType;
 use geotiff::parse_tiff;
 use std::error::Error;
+use std::ffi::OsStr;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::{span, Level};
+use tracing::{event, span, Level};
 
 use crate::parsing::shapefile::parse_shapefile;
 
+pub mod asc;
+pub mod crs;
 pub mod dted;
 pub mod geojson;
+pub mod gpx;
+pub mod hgt;
 pub mod kml;
+pub mod kmz;
 
 pub mod mbtiles;
+pub mod nitf;
+pub mod reproject;
 
 pub mod conversions;
 pub mod error;
+pub(crate) mod fields;
 pub(crate) mod gpkg;
 pub(crate) mod shapefile;
 
-pub fn parse(map: Arc<MapType>) -> Result<Option<Node>, Box<dyn Error>> {
+// Optional parsing, slower or producing larger metadata, enabled in config.txt.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ParseOptions {
+    // Read every elevation in DTED, HGT and ASC files for their statistics, also verifying DTED checksums.
+    pub elevation_scan: bool,
+    // Keep the envelope of every GeoJSON feature in the index, for searching within files.
+    pub feature_envelopes: bool,
+}
+
+pub fn parse(map: Arc<MapType>, options: ParseOptions) -> Result<Vec<Node>, Box<dyn Error>> {
     let span = span!(Level::INFO, "Parsing");
     let _guard = span.enter();
 
     match map.as_ref() {
-        MapType::GEOTIFF(tiff) => Ok(Some(Node {
+        MapType::GEOTIFF(tiff) => Ok(vec![Node {
             metadata: parse_tiff(
                 &mut BufReader::new(File::open(&tiff.tiff)?),
                 tiff.tfw
@@ -39,43 +63,158 @@ pub fn parse(map: Arc<MapType>) -> Result<Option<Node>, Box<dyn Error>> {
                     .transpose()?
                     .map(BufReader::new)
                     .as_mut(),
+                tiff.prj
+                    .clone()
+                    .map(File::open)
+                    .transpose()?
+                    .map(BufReader::new)
+                    .as_mut(),
+                crs::registry(),
             )?
-            .into(),
-            map,
-        })),
-        MapType::DTED(dted) => Ok(Some(Node {
-            metadata: parse_dted(&mut BufReader::new(File::open(&dted.path)?))?.into(),
+            .try_into()?,
             map,
-        })),
-        MapType::KML(kml) => Ok(Some(Node {
+        }]),
+        MapType::DTED(dted) => {
+            let mut metadata = parse_dted(
+                &mut BufReader::new(File::open(&dted.path)?),
+                options.elevation_scan,
+            )?;
+            // Levels are detected from the file itself, flag those whose extension disagrees.
+            let extension = dted
+                .path
+                .extension()
+                .and_then(OsStr::to_str)
+                .map(|e| e.to_ascii_uppercase().replace("DT", "DTED"));
+            let level = metadata
+                .tags
+                .iter()
+                .find(|(k, _)| k == "Level")
+                .map(|(_, v)| v.clone());
+            if let (Some(extension), Some(level)) = (extension, level) {
+                if extension != level {
+                    event!(
+                        Level::WARN,
+                        "{:?} is {level}, but has the extension of {extension}",
+                        dted.path
+                    );
+                    metadata.tags.push(("LevelMismatch".to_string(), extension));
+                }
+            }
+            Ok(vec![Node {
+                metadata: metadata.into(),
+                map,
+            }])
+        }
+        MapType::HGT(hgt) => {
+            let name = hgt
+                .path
+                .file_stem()
+                .and_then(OsStr::to_str)
+                .unwrap_or_default();
+            Ok(vec![Node {
+                metadata: parse_hgt(
+                    name,
+                    &mut BufReader::new(File::open(&hgt.path)?),
+                    options.elevation_scan,
+                )?
+                .into(),
+                map,
+            }])
+        }
+        MapType::ASC(asc) => {
+            let mut prj_reader = asc
+                .prj
+                .clone()
+                .map(File::open)
+                .transpose()?
+                .map(BufReader::new);
+            Ok(vec![Node {
+                metadata: parse_asc(
+                    &mut BufReader::new(File::open(&asc.path)?),
+                    prj_reader.as_mut(),
+                    options.elevation_scan,
+                )?
+                .into(),
+                map,
+            }])
+        }
+        MapType::KML(kml) => Ok(vec![Node {
             metadata: parse_kml(&mut BufReader::new(File::open(&kml.path)?))?.into(),
             map,
-        })),
-        MapType::GEOJSON(geojson) => Ok(Some(Node {
-            metadata: parse_geojson(&mut BufReader::new(File::open(&geojson.path)?))?.into(),
+        }]),
+        MapType::KMZ(kmz) => Ok(vec![Node {
+            metadata: parse_kmz(BufReader::new(File::open(&kmz.path)?))?.into(),
+            map,
+        }]),
+        MapType::GEOJSON(geojson) => Ok(vec![Node {
+            metadata: parse_geojson(
+                &mut BufReader::new(File::open(&geojson.path)?),
+                options.feature_envelopes,
+            )?
+            .into(),
             map,
-        })),
-        MapType::MBTILES(mbtiles) => Ok(Some(Node {
+        }]),
+        MapType::GPX(gpx) => Ok(vec![Node {
+            metadata: parse_gpx(&mut BufReader::new(File::open(&gpx.path)?))?.into(),
+            map,
+        }]),
+        MapType::MBTILES(mbtiles) => Ok(vec![Node {
             metadata: parse_mbtiles(&mbtiles.path.to_str().unwrap())?.into(),
             map,
-        })),
-        MapType::GPKG(gpkg) => Ok(Some(Node {
+        }]),
+        MapType::GPKG(gpkg) => Ok(vec![Node {
             metadata: parse_gpkg(&gpkg.path.to_str().unwrap())?.into(),
             map,
-        })),
+        }]),
+        // A file can hold several images, each indexed separately.
+        MapType::NITF(nitf) => Ok(parse_nitf(&mut BufReader::new(File::open(&nitf.path)?))?
+            .into_iter()
+            .map(|image| Node {
+                metadata: image.into(),
+                map: map.clone(),
+            })
+            .collect()),
         MapType::SHAPEFILE(shapefile) => {
+            let open = |path: &Option<PathBuf>| -> Result<Option<BufReader<File>>, std::io::Error> {
+                Ok(path
+                    .as_ref()
+                    .map(File::open)
+                    .transpose()?
+                    .map(BufReader::new))
+            };
             let mut shp_reader = BufReader::new(File::open(&shapefile.shp)?);
-            let mut prj_reader = shapefile
-                .prj
-                .clone()
-                .map(File::open)
-                .transpose()?
-                .map(BufReader::new);
+            let mut prj_reader = open(&shapefile.prj)?;
+            let mut shx_reader = open(&shapefile.shx)?;
+            let mut dbf_reader = open(&shapefile.dbf)?;
+            let mut cpg_reader = open(&shapefile.cpg)?;
 
-            return Ok(Some(Node {
-                metadata: parse_shapefile(&mut shp_reader, prj_reader.as_mut())?.into(),
+            return Ok(vec![Node {
+                metadata: parse_shapefile(
+                    &mut shp_reader,
+                    prj_reader.as_mut(),
+                    shx_reader.as_mut(),
+                    dbf_reader.as_mut(),
+                    cpg_reader.as_mut(),
+                )?
+                .into(),
                 map,
-            }));
+            }]);
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    // First value of a tag, for the parsers' tests.
+    pub(crate) fn tag<'a>(tags: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        tags.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    // Every value of a repeated tag, in order.
+    pub(crate) fn tags<'a>(tags: &'a [(String, String)], key: &str) -> Vec<&'a str> {
+        tags.iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+}
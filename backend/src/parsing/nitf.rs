@@ -0,0 +1,751 @@
+use crate::parsing::crs;
+use crate::parsing::fields::{numeric_field, text_field};
+use crate::parsing::reproject::to_wgs84;
+use crate::spatial::{Coordinate, Region};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use tracing::{event, Level};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NITFMap {
+    pub(crate) path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum NITFErrorState {
+    UnexpectedFormat(String),
+    FileHeaderError(FileHeaderErrorState),
+    // Problems with an image subheader, by the index of its image segment.
+    ImageSubheaderError(usize, ImageSubheaderErrorState),
+    NoGeolocatedImages,
+}
+
+#[derive(Debug)]
+pub enum FileHeaderErrorState {
+    InvalidLength(usize),
+    InvalidSentinel([u8; 4]),
+    UnsupportedVersion(String),
+    InvalidField(&'static str),
+}
+
+#[derive(Debug)]
+pub enum ImageSubheaderErrorState {
+    InvalidLength(usize),
+    InvalidSentinel([u8; 2]),
+    InvalidIGEOLO(String),
+}
+
+impl Display for NITFErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NITFErrorState::UnexpectedFormat(s) => write!(f, "UnexpectedFormatError: {s}"),
+            NITFErrorState::FileHeaderError(e) => write!(f, "File header: {e}"),
+            NITFErrorState::ImageSubheaderError(image, e) => {
+                write!(f, "Image subheader {image}: {e}")
+            }
+            NITFErrorState::NoGeolocatedImages => {
+                write!(f, "No image within the file has corner coordinates!")
+            }
+        }
+    }
+}
+
+impl Error for NITFErrorState {}
+
+impl Display for FileHeaderErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileHeaderErrorState::InvalidLength(length) => write!(f, "Invalid length {length}"),
+            FileHeaderErrorState::InvalidSentinel(found) => {
+                write!(f, "Invalid sentinel {:?}", String::from_utf8_lossy(found))
+            }
+            FileHeaderErrorState::UnsupportedVersion(version) => {
+                write!(f, "Unsupported version {version}")
+            }
+            FileHeaderErrorState::InvalidField(field) => write!(f, "Invalid {field} field"),
+        }
+    }
+}
+
+impl Display for ImageSubheaderErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageSubheaderErrorState::InvalidLength(length) => {
+                write!(f, "Invalid length {length}")
+            }
+            ImageSubheaderErrorState::InvalidSentinel(found) => {
+                write!(f, "Invalid sentinel {:?}", String::from_utf8_lossy(found))
+            }
+            ImageSubheaderErrorState::InvalidIGEOLO(reason) => {
+                write!(f, "Invalid IGEOLO: {reason}")
+            }
+        }
+    }
+}
+
+// Fixed length start of the NITF 2.1 file header, up to and including NUMI.
+const FILE_HEADER_LENGTH: usize = 363;
+// LISH and LI, the lengths of each image subheader and its image.
+const IMAGE_INFO_LENGTH: usize = 16;
+// Image subheader up to and including IGEOLO.
+const IMAGE_SUBHEADER_LENGTH: usize = 432;
+// NSIF 1.0 shares the NITF 2.1 layout.
+const VERSIONS: [&[u8]; 2] = [b"NITF02.10", b"NSIF01.00"];
+
+// Latitude bands of MGRS, 8 degrees each from 80S, and its 100km square letters.
+const LATITUDE_BANDS: &[u8] = b"CDEFGHJKLMNPQRSTUVWX";
+const MGRS_COLUMNS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+const MGRS_ROWS: &[u8] = b"ABCDEFGHJKLMNPQRSTUV";
+// Metres of northing per degree of latitude along a UTM central meridian, near enough to pick
+// which 2000km cycle of MGRS rows a square is in.
+const METRES_PER_DEGREE: f64 = 111_132.0 * 0.9996;
+
+// CCYYMMDDhhmmss as ISO 8601. Unknown parts may be filled with -, so those are kept as written.
+fn date_field(data: &[u8]) -> Option<String> {
+    let date = text_field(data)?;
+    if date.len() != 14 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return Some(date);
+    }
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &date[0..4],
+        &date[4..6],
+        &date[6..8],
+        &date[8..10],
+        &date[10..12],
+        &date[12..14]
+    ))
+}
+
+fn classification(code: u8) -> Option<&'static str> {
+    Some(match code {
+        b'U' => "Unclassified",
+        b'R' => "Restricted",
+        b'C' => "Confidential",
+        b'S' => "Secret",
+        b'T' => "Top Secret",
+        _ => return None,
+    })
+}
+
+#[derive(Debug)]
+pub struct NITFMetaData {
+    pub region: Region,
+    pub tags: Vec<(String, String)>,
+    pub footprint: Option<Vec<Coordinate>>,
+}
+
+#[derive(Debug)]
+pub struct FileHeader {
+    version: String,
+    title: Option<String>,
+    date: Option<String>,
+    station: Option<String>,
+    classification: Option<&'static str>,
+    header_length: u64,
+    // Subheader and data lengths of each image segment.
+    images: Vec<(u64, u64)>,
+}
+
+impl FileHeader {
+    // The fixed fields, after which come the NUMI image segment lengths.
+    pub fn from_bytes(buffer: &[u8]) -> Result<(FileHeader, usize), NITFErrorState> {
+        let error = NITFErrorState::FileHeaderError;
+        if buffer.len() != FILE_HEADER_LENGTH {
+            return Err(error(FileHeaderErrorState::InvalidLength(buffer.len())));
+        }
+        if &buffer[0..4] != b"NITF" && &buffer[0..4] != b"NSIF" {
+            return Err(error(FileHeaderErrorState::InvalidSentinel([
+                buffer[0], buffer[1], buffer[2], buffer[3],
+            ])));
+        }
+        if !VERSIONS.contains(&&buffer[0..9]) {
+            return Err(error(FileHeaderErrorState::UnsupportedVersion(
+                String::from_utf8_lossy(&buffer[0..9]).to_string(),
+            )));
+        }
+        let header_length = numeric_field(&buffer[354..360])
+            .ok_or(error(FileHeaderErrorState::InvalidField("HL")))?;
+        let image_count = numeric_field(&buffer[360..363])
+            .ok_or(error(FileHeaderErrorState::InvalidField("NUMI")))?;
+        Ok((
+            FileHeader {
+                version: String::from_utf8_lossy(&buffer[4..9]).to_string(),
+                title: text_field(&buffer[39..119]),
+                date: date_field(&buffer[25..39]),
+                station: text_field(&buffer[15..25]),
+                classification: classification(buffer[119]),
+                header_length,
+                images: Vec::with_capacity(image_count),
+            },
+            image_count,
+        ))
+    }
+
+    // Reads LISHn and LIn for every image segment.
+    pub fn read_images(&mut self, buffer: &[u8]) -> Result<(), NITFErrorState> {
+        for entry in buffer.chunks_exact(IMAGE_INFO_LENGTH) {
+            let invalid =
+                || NITFErrorState::FileHeaderError(FileHeaderErrorState::InvalidField("LI"));
+            let subheader_length = numeric_field(&entry[0..6]).ok_or_else(invalid)?;
+            let data_length = numeric_field(&entry[6..16]).ok_or_else(invalid)?;
+            self.images.push((subheader_length, data_length));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageSubheader {
+    id: Option<String>,
+    title: Option<String>,
+    date: Option<String>,
+    target: Option<String>,
+    classification: Option<&'static str>,
+    source: Option<String>,
+    rows: Option<u64>,
+    columns: Option<u64>,
+    representation: Option<String>,
+    category: Option<String>,
+    // ICORDS, and the corners of the first row and column, first row last column, last row and
+    // column, and last row first column. Absent for images without geolocation.
+    coordinates: Option<(char, [Coordinate; 4])>,
+}
+
+impl ImageSubheader {
+    pub fn from_bytes(buffer: &[u8]) -> Result<ImageSubheader, ImageSubheaderErrorState> {
+        // IGEOLO follows ICORDS only when ICORDS is not blank, so subheaders read may be shorter.
+        if buffer.len() < IMAGE_SUBHEADER_LENGTH - 60 {
+            return Err(ImageSubheaderErrorState::InvalidLength(buffer.len()));
+        }
+        if &buffer[0..2] != b"IM" {
+            return Err(ImageSubheaderErrorState::InvalidSentinel([
+                buffer[0], buffer[1],
+            ]));
+        }
+        let coordinates = match buffer[371] {
+            b' ' => None,
+            icords => {
+                let igeolo = buffer
+                    .get(372..IMAGE_SUBHEADER_LENGTH)
+                    .ok_or(ImageSubheaderErrorState::InvalidLength(buffer.len()))?;
+                Some((icords as char, parse_igeolo(icords, igeolo)?))
+            }
+        };
+        Ok(ImageSubheader {
+            id: text_field(&buffer[2..12]),
+            date: date_field(&buffer[12..26]),
+            target: text_field(&buffer[26..43]),
+            title: text_field(&buffer[43..123]),
+            classification: classification(buffer[123]),
+            source: text_field(&buffer[291..333]),
+            rows: numeric_field(&buffer[333..341]),
+            columns: numeric_field(&buffer[341..349]),
+            representation: text_field(&buffer[352..360]),
+            category: text_field(&buffer[360..368]),
+            coordinates,
+        })
+    }
+}
+
+fn invalid_igeolo(reason: String) -> ImageSubheaderErrorState {
+    ImageSubheaderErrorState::InvalidIGEOLO(reason)
+}
+
+fn ascii(data: &[u8]) -> Result<&str, ImageSubheaderErrorState> {
+    std::str::from_utf8(data).map_err(|_| invalid_igeolo(format!("Non ASCII IGEOLO {data:?}")))
+}
+
+fn number(data: &[u8]) -> Result<f64, ImageSubheaderErrorState> {
+    let text = ascii(data)?;
+    match text.trim().parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        _ => Err(invalid_igeolo(format!("Expected a number, got {text:?}"))),
+    }
+}
+
+// Degrees, minutes and seconds followed by a hemisphere, such as 513030N or 0001545W.
+fn parse_dms(data: &[u8], positive: u8, negative: u8) -> Result<f64, ImageSubheaderErrorState> {
+    let degrees = data.len() - 5;
+    let value = number(&data[..degrees])?
+        + number(&data[degrees..degrees + 2])? / 60.0
+        + number(&data[degrees + 2..degrees + 4])? / 3600.0;
+    match data[data.len() - 1] {
+        h if h == positive => Ok(value),
+        h if h == negative => Ok(-value),
+        h => Err(invalid_igeolo(format!(
+            "Invalid hemisphere {:?}",
+            h as char
+        ))),
+    }
+}
+
+// Easting and northing in a WGS84 UTM zone, to longitude and latitude.
+fn utm_to_wgs84(
+    zone: u32,
+    south: bool,
+    points: &[(f64, f64)],
+) -> Result<Vec<Coordinate>, ImageSubheaderErrorState> {
+    if !(1..=60).contains(&zone) {
+        return Err(invalid_igeolo(format!("Invalid UTM zone {zone}")));
+    }
+    let code = match south {
+        true => 32700 + zone,
+        false => 32600 + zone,
+    };
+    let proj = crs::registry()
+        .projection(code)
+        .map_err(|e| invalid_igeolo(e.to_string()))?;
+    to_wgs84(points, &proj).map_err(|e| invalid_igeolo(e.to_string()))
+}
+
+// A zzBJKeeeeennnnn MGRS reference, zone, latitude band, 100km square and 1m easting and northing,
+// to UTM. Returns the easting, northing and whether it is south of the equator.
+fn mgrs_to_utm(data: &[u8]) -> Result<(u32, bool, (f64, f64)), ImageSubheaderErrorState> {
+    let zone = number(&data[0..2])? as u32;
+    let position = |letters: &[u8], letter: u8| {
+        letters
+            .iter()
+            .position(|&l| l == letter.to_ascii_uppercase())
+            .ok_or_else(|| invalid_igeolo(format!("Invalid MGRS letter {:?}", letter as char)))
+    };
+    let band = position(LATITUDE_BANDS, data[2])?;
+    let column = position(MGRS_COLUMNS, data[3])?;
+    let row = position(MGRS_ROWS, data[4])?;
+    if !(1..=60).contains(&zone) {
+        return Err(invalid_igeolo(format!("Invalid UTM zone {zone}")));
+    }
+
+    // Column letters cycle through three sets of eight across zones.
+    let set = ((zone - 1) % 3) as usize * 8;
+    if !(set..set + 8).contains(&column) {
+        return Err(invalid_igeolo(format!(
+            "MGRS column {:?} isn't used in zone {zone}",
+            data[3] as char
+        )));
+    }
+    let easting = ((column - set + 1) * 100_000) as f64 + number(&data[5..10])?;
+
+    // Row letters repeat every 2000km, and are offset by five in even zones. The latitude band
+    // picks the repetition, the first whose northing is not well south of the band.
+    let row = (row + 20 - if zone.is_multiple_of(2) { 5 } else { 0 }) % 20;
+    let mut northing = (row * 100_000) as f64 + number(&data[10..15])?;
+    let south = band < 10;
+    let band_latitude = -80.0 + 8.0 * band as f64;
+    let mut band_northing = band_latitude * METRES_PER_DEGREE;
+    if south {
+        band_northing += 10_000_000.0;
+    }
+    while northing < band_northing - 500_000.0 {
+        northing += 2_000_000.0;
+    }
+    Ok((zone, south, (easting, northing)))
+}
+
+// The four corners of IGEOLO, each 15 characters in the form given by ICORDS.
+fn parse_igeolo(icords: u8, igeolo: &[u8]) -> Result<[Coordinate; 4], ImageSubheaderErrorState> {
+    let corners: Vec<&[u8]> = igeolo.chunks_exact(15).collect();
+    let corners: Vec<Coordinate> = match icords {
+        // ddmmssXdddmmssY
+        b'G' => corners
+            .iter()
+            .map(|c| {
+                Ok((
+                    parse_dms(&c[7..15], b'E', b'W')?,
+                    parse_dms(&c[0..7], b'N', b'S')?,
+                ))
+            })
+            .collect::<Result<_, _>>()?,
+        // ±dd.ddd±ddd.ddd
+        b'D' => corners
+            .iter()
+            .map(|c| Ok((number(&c[7..15])?, number(&c[0..7])?)))
+            .collect::<Result<_, _>>()?,
+        // zzeeeeeennnnnnn, in the northern or southern hemisphere.
+        b'N' | b'S' => {
+            let mut points = Vec::with_capacity(4);
+            for c in corners.iter() {
+                let zone = number(&c[0..2])? as u32;
+                let point = (number(&c[2..8])?, number(&c[8..15])?);
+                points.extend(utm_to_wgs84(zone, icords == b'S', &[point])?);
+            }
+            points
+        }
+        b'U' => {
+            let mut points = Vec::with_capacity(4);
+            for c in corners.iter() {
+                let (zone, south, point) = mgrs_to_utm(c)?;
+                points.extend(utm_to_wgs84(zone, south, &[point])?);
+            }
+            points
+        }
+        icords => {
+            return Err(invalid_igeolo(format!(
+                "Unsupported ICORDS {:?}",
+                icords as char
+            )))
+        }
+    };
+    for &(x, y) in corners.iter() {
+        if !(-180.0..=180.0).contains(&x) || !(-90.0..=90.0).contains(&y) {
+            return Err(invalid_igeolo(format!("Corner ({x}, {y}) is out of range")));
+        }
+    }
+    corners
+        .try_into()
+        .map_err(|c| invalid_igeolo(format!("Expected 4 corners, got {c:?}")))
+}
+
+fn read_exact(reader: &mut BufReader<File>, length: usize) -> Result<Vec<u8>, NITFErrorState> {
+    let mut buffer = vec![0u8; length];
+    reader
+        .read_exact(&mut buffer)
+        .map_err(|e| NITFErrorState::UnexpectedFormat(format!("Failed to read bytes: {e:?}")))?;
+    Ok(buffer)
+}
+
+// Parses the file header and every image subheader. Each image with corner coordinates is
+// indexed on its own, so a file of several scenes gives several entries.
+pub fn parse_nitf(reader: &mut BufReader<File>) -> Result<Vec<NITFMetaData>, NITFErrorState> {
+    let (mut header, image_count) =
+        FileHeader::from_bytes(&read_exact(reader, FILE_HEADER_LENGTH)?)?;
+    header.read_images(&read_exact(reader, image_count * IMAGE_INFO_LENGTH)?)?;
+
+    let mut file_tags = vec![
+        ("Filetype".to_string(), "NITF".to_string()),
+        ("NITFVersion".to_string(), header.version.clone()),
+        ("ImageCount".to_string(), image_count.to_string()),
+    ];
+    let optional_tags = [
+        ("FileTitle", header.title.clone()),
+        ("FileDateTime", header.date.clone()),
+        ("OriginatingStation", header.station.clone()),
+        (
+            "FileClassification",
+            header.classification.map(str::to_string),
+        ),
+    ];
+    for (key, value) in optional_tags {
+        if let Some(value) = value {
+            file_tags.push((key.to_string(), value));
+        }
+    }
+
+    let mut images = Vec::new();
+    let mut offset = header.header_length;
+    for (index, &(subheader_length, data_length)) in header.images.iter().enumerate() {
+        reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| NITFErrorState::UnexpectedFormat(format!("Failed to seek: {e:?}")))?;
+        offset += subheader_length + data_length;
+        let length = (subheader_length as usize).min(IMAGE_SUBHEADER_LENGTH);
+        // Unreadable coordinates lose only that image, other errors mean the file is malformed.
+        let subheader = match ImageSubheader::from_bytes(&read_exact(reader, length)?) {
+            Ok(subheader) => subheader,
+            Err(e @ ImageSubheaderErrorState::InvalidIGEOLO(_)) => {
+                event!(Level::WARN, "Skipping NITF image {}: {e}", index + 1);
+                continue;
+            }
+            Err(e) => return Err(NITFErrorState::ImageSubheaderError(index + 1, e)),
+        };
+        let Some((icords, corners)) = subheader.coordinates else {
+            event!(
+                Level::WARN,
+                "NITF image {} has no corner coordinates, skipping.",
+                index + 1
+            );
+            continue;
+        };
+
+        let mut tags = file_tags.clone();
+        tags.push(("ImageSegment".to_string(), (index + 1).to_string()));
+        let coordinate_system = match icords {
+            'G' | 'D' => "Geographic",
+            'U' => "MGRS",
+            'N' => "UTM North",
+            _ => "UTM South",
+        };
+        tags.push((
+            "CoordinateSystem".to_string(),
+            coordinate_system.to_string(),
+        ));
+        let image_tags = [
+            ("ImageID", subheader.id),
+            ("ImageTitle", subheader.title),
+            ("AcquisitionDate", subheader.date),
+            ("TargetID", subheader.target),
+            (
+                "Classification",
+                subheader.classification.map(str::to_string),
+            ),
+            ("ImageSource", subheader.source),
+            ("Rows", subheader.rows.map(|r| r.to_string())),
+            ("Columns", subheader.columns.map(|c| c.to_string())),
+            ("ImageRepresentation", subheader.representation),
+            ("ImageCategory", subheader.category),
+        ];
+        for (key, value) in image_tags {
+            if let Some(value) = value {
+                tags.push((key.to_string(), value));
+            }
+        }
+
+        let mut region = Region::envelope(&corners);
+        // Corners more than half the globe apart are taken as straddling the antimeridian.
+        let footprint = match region.bottom_right.0 - region.top_left.0 > 180.0 {
+            true => {
+                tags.push(("CrossesAntimeridian".to_string(), "true".to_string()));
+                (region.top_left.0, region.bottom_right.0) = (-180.0, 180.0);
+                None
+            }
+            false => Some(corners.to_vec()),
+        };
+        images.push(NITFMetaData {
+            region,
+            tags,
+            footprint,
+        });
+    }
+
+    if images.is_empty() {
+        return Err(NITFErrorState::NoGeolocatedImages);
+    }
+    Ok(images)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::tests::tag;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn field(buffer: &mut [u8], offset: usize, value: &str) {
+        buffer[offset..offset + value.len()].copy_from_slice(value.as_bytes());
+    }
+
+    // An image subheader, with IGEOLO when icords isn't blank.
+    fn mock_image(icords: char, igeolo: &str) -> Vec<u8> {
+        let length = match icords {
+            ' ' => IMAGE_SUBHEADER_LENGTH - 60,
+            _ => IMAGE_SUBHEADER_LENGTH,
+        };
+        let mut subheader = vec![b' '; length];
+        field(&mut subheader, 0, "IM");
+        field(&mut subheader, 2, "SCENE1");
+        field(&mut subheader, 12, "20240501083000");
+        field(&mut subheader, 43, "Harbour");
+        field(&mut subheader, 123, "U");
+        field(&mut subheader, 291, "Satellite");
+        field(&mut subheader, 333, "00001024");
+        field(&mut subheader, 341, "00002048");
+        field(&mut subheader, 352, "MONO");
+        field(&mut subheader, 360, "VIS");
+        subheader[371] = icords as u8;
+        if icords != ' ' {
+            field(&mut subheader, 372, igeolo);
+        }
+        subheader
+    }
+
+    // A NITF 2.1 file of the given image subheaders, each followed by 4 bytes of image data.
+    fn mock_nitf(images: &[Vec<u8>]) -> Vec<u8> {
+        let header_length = FILE_HEADER_LENGTH + images.len() * IMAGE_INFO_LENGTH;
+        let mut header = vec![b' '; FILE_HEADER_LENGTH];
+        field(&mut header, 0, "NITF02.10");
+        field(&mut header, 9, "03");
+        field(&mut header, 11, "BF01");
+        field(&mut header, 15, "STATION");
+        field(&mut header, 25, "20240502120000");
+        field(&mut header, 39, "Survey imagery");
+        field(&mut header, 119, "R");
+        field(&mut header, 354, &format!("{header_length:06}"));
+        field(&mut header, 360, &format!("{:03}", images.len()));
+        for image in images {
+            header.extend_from_slice(format!("{:06}{:010}", image.len(), 4).as_bytes());
+        }
+        for image in images {
+            header.extend_from_slice(image);
+            header.extend_from_slice(&[0, 1, 2, 3]);
+        }
+        header
+    }
+
+    fn parse(data: &[u8]) -> Result<Vec<NITFMetaData>, NITFErrorState> {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(data).unwrap();
+        parse_nitf(&mut BufReader::new(file.reopen().unwrap()))
+    }
+
+    fn assert_near((x, y): Coordinate, (expected_x, expected_y): Coordinate) {
+        assert!(
+            (x - expected_x).abs() < 1e-4 && (y - expected_y).abs() < 1e-4,
+            "({x}, {y}) isn't near ({expected_x}, {expected_y})"
+        );
+    }
+
+    #[test]
+    fn test_parse_nitf_geographic() {
+        let igeolo = "513000N0003000W513000N0001500E510000N0001500E510000N0003000W";
+        let images = parse(&mock_nitf(&[mock_image('G', igeolo)])).unwrap();
+        assert_eq!(images.len(), 1);
+        let image = &images[0];
+        assert_near(image.region.top_left, (-0.5, 51.5));
+        assert_near(image.region.bottom_right, (0.25, 51.0));
+        assert_eq!(image.footprint.as_ref().unwrap().len(), 4);
+        assert_eq!(tag(&image.tags, "Filetype"), Some("NITF"));
+        assert_eq!(tag(&image.tags, "NITFVersion"), Some("02.10"));
+        assert_eq!(tag(&image.tags, "FileTitle"), Some("Survey imagery"));
+        assert_eq!(tag(&image.tags, "FileClassification"), Some("Restricted"));
+        assert_eq!(tag(&image.tags, "OriginatingStation"), Some("STATION"));
+        assert_eq!(tag(&image.tags, "CoordinateSystem"), Some("Geographic"));
+        assert_eq!(tag(&image.tags, "ImageID"), Some("SCENE1"));
+        assert_eq!(tag(&image.tags, "ImageTitle"), Some("Harbour"));
+        assert_eq!(tag(&image.tags, "Classification"), Some("Unclassified"));
+        assert_eq!(
+            tag(&image.tags, "AcquisitionDate"),
+            Some("2024-05-01T08:30:00Z")
+        );
+        assert_eq!(tag(&image.tags, "ImageSource"), Some("Satellite"));
+        assert_eq!(tag(&image.tags, "Rows"), Some("1024"));
+        assert_eq!(tag(&image.tags, "Columns"), Some("2048"));
+    }
+
+    #[test]
+    fn test_parse_nitf_decimal_degrees() {
+        let igeolo = "-33.500+151.000-33.500+151.500-34.000+151.500-34.000+151.000";
+        let images = parse(&mock_nitf(&[mock_image('D', igeolo)])).unwrap();
+        assert_near(images[0].region.top_left, (151.0, -33.5));
+        assert_near(images[0].region.bottom_right, (151.5, -34.0));
+    }
+
+    #[test]
+    fn test_parse_nitf_mgrs() {
+        // The Eiffel Tower, 48.8583N 2.2945E.
+        let corner = "31UDQ4825111932";
+        let images = parse(&mock_nitf(&[mock_image('U', &corner.repeat(4))])).unwrap();
+        assert!((images[0].region.top_left.0 - 2.2945).abs() < 1e-3);
+        assert!((images[0].region.top_left.1 - 48.8583).abs() < 1e-3);
+        assert_eq!(tag(&images[0].tags, "CoordinateSystem"), Some("MGRS"));
+    }
+
+    #[test]
+    fn test_mgrs_matches_utm() {
+        // An even zone in the southern hemisphere, whose row letters are offset.
+        assert_eq!(
+            mgrs_to_utm(b"56HLH3490652428").unwrap(),
+            (56, true, (334906.0, 6252428.0))
+        );
+        assert_eq!(
+            mgrs_to_utm(b"31UDQ4825111932").unwrap(),
+            (31, false, (448251.0, 5411932.0))
+        );
+        assert!(mgrs_to_utm(b"31UJQ4825111932").is_err());
+        assert!(mgrs_to_utm(b"31IDQ4825111932").is_err());
+
+        let utm = parse_igeolo(b'S', "563349066252428".repeat(4).as_bytes()).unwrap();
+        let mgrs = parse_igeolo(b'U', "56HLH3490652428".repeat(4).as_bytes()).unwrap();
+        assert_near(utm[0], mgrs[0]);
+        assert!((151.0..151.5).contains(&utm[0].0) && (-34.0..-33.5).contains(&utm[0].1));
+    }
+
+    #[test]
+    fn test_parse_nitf_utm_north() {
+        // The central meridian of zone 31 is 3E.
+        let corner = "315000005500000";
+        let images = parse(&mock_nitf(&[mock_image('N', &corner.repeat(4))])).unwrap();
+        assert!((images[0].region.top_left.0 - 3.0).abs() < 1e-9);
+        assert!((49.0..50.0).contains(&images[0].region.top_left.1));
+        assert_eq!(tag(&images[0].tags, "CoordinateSystem"), Some("UTM North"));
+    }
+
+    #[test]
+    fn test_parse_nitf_multiple_images() {
+        let images = parse(&mock_nitf(&[
+            mock_image('D', &"+10.000+020.000".repeat(4)),
+            mock_image(' ', ""),
+            mock_image('D', &"-10.000-020.000".repeat(4)),
+        ]))
+        .unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(tag(&images[0].tags, "ImageSegment"), Some("1"));
+        assert_eq!(tag(&images[1].tags, "ImageSegment"), Some("3"));
+        assert_eq!(tag(&images[1].tags, "ImageCount"), Some("3"));
+        assert_near(images[1].region.top_left, (-20.0, -10.0));
+    }
+
+    #[test]
+    fn test_parse_nitf_skips_invalid_coordinates() {
+        // A polar MGRS band, then a good image, then an unsupported ICORDS.
+        let images = parse(&mock_nitf(&[
+            mock_image('U', &"31ZDQ0000000000".repeat(4)),
+            mock_image('D', &"+10.000+020.000".repeat(4)),
+            mock_image('X', &" ".repeat(60)),
+        ]))
+        .unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(tag(&images[0].tags, "ImageSegment"), Some("2"));
+
+        // A malformed subheader still fails the file.
+        let mut image = mock_image('D', &"+10.000+020.000".repeat(4));
+        image[0..2].copy_from_slice(b"XX");
+        assert!(matches!(
+            parse(&mock_nitf(&[image])),
+            Err(NITFErrorState::ImageSubheaderError(
+                1,
+                ImageSubheaderErrorState::InvalidSentinel(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_parse_nitf_across_antimeridian() {
+        let igeolo = "-16.000+179.500-16.000-179.500-17.000-179.500-17.000+179.500";
+        let images = parse(&mock_nitf(&[mock_image('D', igeolo)])).unwrap();
+        assert_eq!(tag(&images[0].tags, "CrossesAntimeridian"), Some("true"));
+        assert_eq!(images[0].region.top_left.0, -180.0);
+        assert!(images[0].footprint.is_none());
+    }
+
+    #[test]
+    fn test_parse_nitf_invalid() {
+        let valid = mock_nitf(&[mock_image('D', &"+10.000+020.000".repeat(4))]);
+
+        let mut data = valid.clone();
+        data[0..4].copy_from_slice(b"NTIF");
+        assert!(matches!(
+            parse(&data),
+            Err(NITFErrorState::FileHeaderError(
+                FileHeaderErrorState::InvalidSentinel(_)
+            ))
+        ));
+        let mut data = valid.clone();
+        data[4..9].copy_from_slice(b"02.00");
+        assert!(matches!(
+            parse(&data),
+            Err(NITFErrorState::FileHeaderError(
+                FileHeaderErrorState::UnsupportedVersion(_)
+            ))
+        ));
+        assert!(matches!(
+            parse(&valid[..200]),
+            Err(NITFErrorState::UnexpectedFormat(_))
+        ));
+        assert!(matches!(
+            parse(&mock_nitf(&[mock_image('D', &"+99.000+020.000".repeat(4))])),
+            Err(NITFErrorState::NoGeolocatedImages)
+        ));
+        assert!(matches!(
+            parse(&mock_nitf(&[mock_image('G', &"51300XN0003000W".repeat(4))])),
+            Err(NITFErrorState::NoGeolocatedImages)
+        ));
+        assert!(matches!(
+            parse(&mock_nitf(&[mock_image(' ', "")])),
+            Err(NITFErrorState::NoGeolocatedImages)
+        ));
+    }
+}
@@ -0,0 +1,293 @@
+use proj4rs::proj::ProjType;
+use proj4rs::Proj;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::iter::once;
+use tracing::{event, Level};
+
+#[derive(Debug)]
+pub enum ReprojectErrorState {
+    ProjectionError(String),
+    // No point of the outline could be transformed.
+    NotEnoughGeoData,
+}
+
+impl Display for ReprojectErrorState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReprojectErrorState::ProjectionError(s) => write!(f, "ProjectionError: {s}"),
+            ReprojectErrorState::NotEnoughGeoData => {
+                write!(
+                    f,
+                    "NotEnoughGeoData: No point could be transformed into EPSG:4326"
+                )
+            }
+        }
+    }
+}
+
+impl Error for ReprojectErrorState {}
+
+// Points sampled along each edge, so edges which curve once reprojected are still covered.
+pub const EDGE_SAMPLES: usize = 32;
+// Fewer are needed for footprints, which are only a simplified outline.
+pub const FOOTPRINT_SAMPLES: usize = 8;
+
+// Extent in EPSG:4326 degrees. Across the antimeridian east is beyond 180, so west < east always holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeographicExtent {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+    pub crosses_antimeridian: bool,
+}
+
+impl GeographicExtent {
+    // Wraps around the antimeridian or a pole, so the outline can't be drawn as a single polygon.
+    pub fn wraps(&self) -> bool {
+        self.crosses_antimeridian || self.east - self.west >= 360.0
+    }
+
+    // Corners for the index, which can't wrap, so an extent across the antimeridian covers every longitude.
+    pub fn top_left(&self) -> (f64, f64) {
+        match self.crosses_antimeridian {
+            true => (-180.0, self.north),
+            false => (self.west, self.north),
+        }
+    }
+
+    pub fn bottom_right(&self) -> (f64, f64) {
+        match self.crosses_antimeridian {
+            true => (180.0, self.south),
+            false => (self.east, self.south),
+        }
+    }
+}
+
+// Adds evenly spaced points along every edge of a ring, including the closing edge back to the start.
+pub fn densify(ring: &[(f64, f64)], samples: usize) -> Vec<(f64, f64)> {
+    let samples = samples.max(1);
+    let mut points = Vec::with_capacity(ring.len() * samples);
+    for (index, start) in ring.iter().enumerate() {
+        let end = ring[(index + 1) % ring.len()];
+        for step in 0..samples {
+            let t = step as f64 / samples as f64;
+            points.push((
+                start.0 + (end.0 - start.0) * t,
+                start.1 + (end.1 - start.1) * t,
+            ));
+        }
+    }
+    points
+}
+
+// Transforms points in the source CRS into EPSG:4326 degrees. Geographic sources are also in degrees.
+// Points which fail to transform are NaN, erroring only if none succeed.
+pub fn to_wgs84(
+    points: &[(f64, f64)],
+    from_proj: &Proj,
+) -> Result<Vec<(f64, f64)>, ReprojectErrorState> {
+    let to_proj = Proj::from_proj_string(crs_definitions::EPSG_4326.proj4)
+        .expect("FAILED TO BUILD DEFAULT PROJ!");
+
+    let latlong = match from_proj.projection_type() {
+        ProjType::Latlong => true,
+        ProjType::Other => false,
+        ProjType::Geocentric => {
+            return Err(ReprojectErrorState::ProjectionError("Unsupported projection! From GEOCENTRIC! Please contact developer, and send file content for implementation.".to_string()));
+        }
+    };
+
+    // Points outside the projection's domain become NaN, so one bad point doesn't lose the rest.
+    let transformed: Vec<(f64, f64)> = points
+        .iter()
+        .map(|point| {
+            let mut p = match latlong {
+                true => (point.0.to_radians(), point.1.to_radians()),
+                false => *point,
+            };
+            match proj4rs::transform::transform(from_proj, &to_proj, &mut p) {
+                Ok(()) => (p.0.to_degrees(), p.1.to_degrees()),
+                Err(e) => {
+                    event!(
+                        Level::DEBUG,
+                        "Failed to transform {point:?} from {from_proj:?}, dropping it. Reason: {e:?}"
+                    );
+                    (f64::NAN, f64::NAN)
+                }
+            }
+        })
+        .collect();
+    if !points.is_empty()
+        && !transformed
+            .iter()
+            .any(|(x, y)| x.is_finite() && y.is_finite())
+    {
+        return Err(ReprojectErrorState::NotEnoughGeoData);
+    }
+    Ok(transformed)
+}
+
+// Extent in EPSG:4326 of a ring in the source CRS, such as the corners of an image.
+// Edges are densified before transforming, as straight lines in the source CRS needn't be in EPSG:4326.
+pub fn reproject_extent(
+    ring: &[(f64, f64)],
+    from_proj: &Proj,
+) -> Result<GeographicExtent, ReprojectErrorState> {
+    let points: Vec<(f64, f64)> = to_wgs84(&densify(ring, EDGE_SAMPLES), from_proj)?
+        .into_iter()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .collect();
+    let Some(&(first, _)) = points.first() else {
+        return Err(ReprojectErrorState::NotEnoughGeoData);
+    };
+
+    // Longitudes are unwrapped as the ring is walked, so a jump of more than 180 degrees between
+    // neighbouring points is taken as crossing the antimeridian rather than spanning the globe.
+    let (mut west, mut east) = (first, first);
+    let (mut south, mut north) = (f64::INFINITY, f64::NEG_INFINITY);
+    let (mut previous, mut unwrapped) = (first, first);
+    for &(x, y) in points.iter().chain(once(&points[0])) {
+        unwrapped += (x - previous + 180.0).rem_euclid(360.0) - 180.0;
+        previous = x;
+        west = west.min(unwrapped);
+        east = east.max(unwrapped);
+        south = south.min(y);
+        north = north.max(y);
+    }
+
+    // A ring which winds all the way around surrounds a pole, so reaches it at every longitude.
+    if (unwrapped - first).abs() > 180.0 {
+        if north + south > 0.0 {
+            north = 90.0;
+        } else {
+            south = -90.0;
+        }
+        return Ok(GeographicExtent {
+            west: -180.0,
+            south,
+            east: 180.0,
+            north,
+            crosses_antimeridian: false,
+        });
+    }
+
+    if east - west >= 360.0 {
+        (west, east) = (-180.0, 180.0);
+    } else {
+        let shift = ((west + 180.0) / 360.0).floor() * 360.0;
+        west -= shift;
+        east -= shift;
+    }
+    Ok(GeographicExtent {
+        west,
+        south,
+        east,
+        north,
+        crosses_antimeridian: east > 180.0,
+    })
+}
+
+// As reproject_extent, for a (min_x, min_y, max_x, max_y) rectangle.
+pub fn reproject_bounds(
+    (min_x, min_y, max_x, max_y): (f64, f64, f64, f64),
+    from_proj: &Proj,
+) -> Result<GeographicExtent, ReprojectErrorState> {
+    reproject_extent(
+        &[
+            (min_x, max_y),
+            (max_x, max_y),
+            (max_x, min_y),
+            (min_x, min_y),
+        ],
+        from_proj,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proj(code: u16) -> Proj {
+        Proj::from_proj_string(crs_definitions::from_code(code).unwrap().proj4).unwrap()
+    }
+
+    #[test]
+    fn test_densify() {
+        let points = densify(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)], 4);
+        assert_eq!(points.len(), 12);
+        assert_eq!(points[1], (1.0, 0.0));
+        assert_eq!(points[5], (4.0, 1.0));
+        // Closing edge back to the start.
+        assert_eq!(points[11], (1.0, 1.0));
+    }
+
+    #[test]
+    fn test_geographic_is_unchanged() {
+        let extent = reproject_bounds((-3.0, 50.0, 2.0, 55.0), &proj(4326)).unwrap();
+        assert!((extent.west + 3.0).abs() < 1e-9);
+        assert!((extent.east - 2.0).abs() < 1e-9);
+        assert!((extent.south - 50.0).abs() < 1e-9);
+        assert!((extent.north - 55.0).abs() < 1e-9);
+        assert!(!extent.crosses_antimeridian && !extent.wraps());
+    }
+
+    #[test]
+    fn test_utm_edges_bulge() {
+        // A wide UTM zone 33N box, whose northern edge curves north of its corners.
+        let (min_x, min_y, max_x, max_y) = (200_000.0, 6_600_000.0, 800_000.0, 7_400_000.0);
+        let from = proj(32633);
+        let corners = to_wgs84(&[(min_x, max_y), (max_x, max_y)], &from).unwrap();
+        let extent = reproject_bounds((min_x, min_y, max_x, max_y), &from).unwrap();
+        assert!(extent.north > corners[0].1.max(corners[1].1) + 0.1);
+    }
+
+    #[test]
+    fn test_polar_stereographic_covers_pole() {
+        // Arctic polar stereographic, a box centred on the north pole.
+        let extent = reproject_bounds(
+            (-1_000_000.0, -1_000_000.0, 1_000_000.0, 1_000_000.0),
+            &proj(3995),
+        )
+        .unwrap();
+        assert_eq!(extent.north, 90.0);
+        assert_eq!((extent.west, extent.east), (-180.0, 180.0));
+        assert!(extent.wraps());
+        assert!(
+            (75.0..85.0).contains(&extent.south),
+            "south: {}",
+            extent.south
+        );
+    }
+
+    #[test]
+    fn test_antimeridian() {
+        let extent = reproject_bounds((170.0, -20.0, 190.0, -10.0), &proj(4326)).unwrap();
+        assert!(extent.crosses_antimeridian && extent.wraps());
+        assert!((extent.west - 170.0).abs() < 1e-9);
+        assert!((extent.east - 190.0).abs() < 1e-9);
+        assert_eq!(extent.top_left(), (-180.0, extent.north));
+        assert_eq!(extent.bottom_right(), (180.0, extent.south));
+    }
+
+    #[test]
+    fn test_failed_points_are_dropped() {
+        // Points far beyond the projection's domain fail to transform.
+        let from = proj(32633);
+        let points = to_wgs84(&[(500_000.0, 6_600_000.0), (1e20, 1e20)], &from).unwrap();
+        assert!(points[0].0.is_finite() && points[0].1.is_finite());
+        assert!(points[1].0.is_nan() && points[1].1.is_nan());
+        assert!(matches!(
+            to_wgs84(&[(1e20, 1e20)], &from),
+            Err(ReprojectErrorState::NotEnoughGeoData)
+        ));
+    }
+
+    #[test]
+    fn test_whole_world() {
+        let extent = reproject_bounds((-180.0, -90.0, 180.0, 90.0), &proj(4326)).unwrap();
+        assert_eq!((extent.west, extent.east), (-180.0, 180.0));
+        assert!(!extent.crosses_antimeridian);
+    }
+}
@@ -1,7 +1,8 @@
-use crate::spatial::Region;
+use crate::parsing::crs;
+use crate::parsing::reproject::{densify, reproject_extent, to_wgs84, FOOTPRINT_SAMPLES};
+use crate::spatial::{Coordinate, Region};
 use proj4rs::proj::ProjType;
 use proj4rs::Proj;
-use proj4wkt::wkt_to_projstring;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
@@ -10,14 +11,14 @@ use std::io::{BufReader, ErrorKind, Read};
 use std::path::PathBuf;
 use tracing::{event, Level};
 
-pub trait FromBytes {
-    fn from_bytes(bytes: &[u8]) -> Self;
+pub trait FromBytes: Sized {
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
 }
 
 impl FromBytes for f64 {
-    fn from_bytes(bytes: &[u8]) -> Self {
-        let bytes: [u8; 8] = bytes.try_into().unwrap();
-        return f64::from_le_bytes(bytes);
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; 8] = bytes.try_into().ok()?;
+        Some(f64::from_le_bytes(bytes))
     }
 }
 
@@ -26,127 +27,374 @@ pub struct ShapeFileMap {
     pub shp: PathBuf,
     pub prj: Option<PathBuf>,
     pub tfw: Option<PathBuf>,
+    pub shx: Option<PathBuf>,
+    pub dbf: Option<PathBuf>,
+    pub cpg: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShapeFileMetaData {
     pub region: Region,
     pub tags: Vec<(String, String)>,
+    pub footprint: Option<Vec<Coordinate>>,
 }
 
 #[derive(Debug)]
 pub enum ShapeFileErrorKind {
     UnexpectedMagicNumber([u8; 4]),
+    ShortHeader(usize),
+    NonFiniteBounds([f64; 4]),
+    InvalidWKT(String),
+    GeocentricCRS(String),
+    InvalidIndex(String),
+    InvalidDBF(String),
 }
 
 impl Display for ShapeFileErrorKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            ShapeFileErrorKind::UnexpectedMagicNumber(magic) => {
+                write!(f, "Unexpected magic number {magic:?}!")
+            }
+            ShapeFileErrorKind::ShortHeader(length) => {
+                write!(f, "Header is {length} bytes, expected 100!")
+            }
+            ShapeFileErrorKind::NonFiniteBounds(bounds) => {
+                write!(f, "Bounding box {bounds:?} isn't finite!")
+            }
+            ShapeFileErrorKind::InvalidWKT(e) => write!(f, "InvalidWKT: {e}"),
+            ShapeFileErrorKind::GeocentricCRS(crs) => {
+                write!(f, "Geocentric CRS {crs} is not supported!")
+            }
+            ShapeFileErrorKind::InvalidIndex(e) => write!(f, "InvalidIndex: {e}"),
+            ShapeFileErrorKind::InvalidDBF(e) => write!(f, "InvalidDBF: {e}"),
+        }
     }
 }
 impl Error for ShapeFileErrorKind {}
 
 #[derive(Debug)]
 pub struct ShapeFileHeader {
+    file_length: u64,
+    shape_type: i32,
     x_min: f64,
     y_min: f64,
     x_max: f64,
     y_max: f64,
 }
 
-pub fn parse_header(buffer: &[u8]) -> Result<ShapeFileHeader, Box<dyn Error>> {
-    assert_eq!(buffer.len(), 100); // Assert valid slice! Runtime check!
+pub fn parse_header(buffer: &[u8]) -> Result<ShapeFileHeader, ShapeFileErrorKind> {
+    if buffer.len() != 100 {
+        return Err(ShapeFileErrorKind::ShortHeader(buffer.len()));
+    }
     if buffer[0..4] != [0, 0, 39, 10] {
         return Err(ShapeFileErrorKind::UnexpectedMagicNumber([
             buffer[0], buffer[1], buffer[2], buffer[3],
-        ])
-        .into());
+        ]));
+    }
+    // File length is big endian and in 16 bit words, the rest of the header is little endian.
+    let file_length = u64::from(u32::from_be_bytes([
+        buffer[24], buffer[25], buffer[26], buffer[27],
+    ])) * 2;
+    let shape_type = i32::from_le_bytes([buffer[32], buffer[33], buffer[34], buffer[35]]);
+    let bound = |offset: usize| {
+        f64::from_bytes(&buffer[offset..offset + 8])
+            .ok_or(ShapeFileErrorKind::ShortHeader(buffer.len()))
+    };
+    let (x_min, y_min, x_max, y_max) = (bound(36)?, bound(44)?, bound(52)?, bound(60)?);
+    if ![x_min, y_min, x_max, y_max].iter().all(|b| b.is_finite()) {
+        return Err(ShapeFileErrorKind::NonFiniteBounds([
+            x_min, y_min, x_max, y_max,
+        ]));
     }
-    let x_min = f64::from_bytes(&buffer[36..44]);
-    let y_min = f64::from_bytes(&buffer[44..52]);
-    let x_max = f64::from_bytes(&buffer[52..60]);
-    let y_max = f64::from_bytes(&buffer[60..68]);
 
-    return Ok(ShapeFileHeader {
+    Ok(ShapeFileHeader {
+        file_length,
+        shape_type,
         x_min,
         y_min,
         x_max,
         y_max,
-    });
+    })
+}
+
+pub fn shape_type_name(shape_type: i32) -> Option<&'static str> {
+    Some(match shape_type {
+        0 => "Null",
+        1 => "Point",
+        3 => "PolyLine",
+        5 => "Polygon",
+        8 => "MultiPoint",
+        11 => "PointZ",
+        13 => "PolyLineZ",
+        15 => "PolygonZ",
+        18 => "MultiPointZ",
+        21 => "PointM",
+        23 => "PolyLineM",
+        25 => "PolygonM",
+        28 => "MultiPointM",
+        31 => "MultiPatch",
+        _ => return None,
+    })
+}
+
+// Reads the 100 byte header, a shorter file being reported as a short header.
+fn read_header(reader: &mut BufReader<File>) -> Result<ShapeFileHeader, Box<dyn Error>> {
+    let mut header_buf = Vec::with_capacity(100);
+    reader.take(100).read_to_end(&mut header_buf)?;
+    Ok(parse_header(&header_buf)?)
+}
+
+// The .prj must describe positions on the surface, not geocentric x, y and z.
+fn prj_projection(registry: &crs::CRSRegistry, wkt: &str) -> Result<Proj, ShapeFileErrorKind> {
+    let geocentric = || ShapeFileErrorKind::GeocentricCRS(crs::wkt_label(wkt));
+    if wkt.trim_start().to_ascii_uppercase().starts_with("GEOCCS") {
+        return Err(geocentric());
+    }
+    let proj = registry
+        .projection_from_wkt(wkt)
+        .map_err(|e| ShapeFileErrorKind::InvalidWKT(e.to_string()))?;
+    // Degrees and projected units are both converted by to_wgs84, geocentric x, y and z can't be.
+    match proj.projection_type() {
+        ProjType::Latlong | ProjType::Other => Ok(proj),
+        ProjType::Geocentric => Err(geocentric()),
+    }
+}
+
+// The .shx has the same header as the .shp, followed by an 8 byte offset and length per record.
+pub fn parse_shx(shx_reader: &mut BufReader<File>) -> Result<u64, Box<dyn Error>> {
+    let header = read_header(shx_reader)?;
+    if header.file_length < 100 || (header.file_length - 100) % 8 != 0 {
+        return Err(ShapeFileErrorKind::InvalidIndex(format!(
+            "File length of {} bytes isn't a whole number of records!",
+            header.file_length
+        ))
+        .into());
+    }
+    Ok((header.file_length - 100) / 8)
+}
+
+#[derive(Debug)]
+pub struct DBFField {
+    name: String,
+    kind: char,
+    length: u8,
+    decimals: u8,
+}
+
+impl Display for DBFField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            'C' => "Character",
+            'N' => "Numeric",
+            'F' => "Float",
+            'L' => "Logical",
+            'D' => "Date",
+            'M' => "Memo",
+            _ => return write!(f, "{}({})", self.kind, self.length),
+        };
+        match self.decimals {
+            0 => write!(f, "{kind}({})", self.length),
+            decimals => write!(f, "{kind}({},{decimals})", self.length),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DBFHeader {
+    record_count: u32,
+    fields: Vec<DBFField>,
+}
+
+// The dBase header, 32 bytes followed by a 32 byte descriptor per field and a 0x0D terminator.
+pub fn parse_dbf(dbf_reader: &mut BufReader<File>) -> Result<DBFHeader, Box<dyn Error>> {
+    let mut header_buf = [0u8; 32];
+    dbf_reader.read_exact(&mut header_buf)?;
+    let record_count = u32::from_le_bytes(header_buf[4..8].try_into()?);
+    let header_length = u16::from_le_bytes(header_buf[8..10].try_into()?) as usize;
+    if header_length < 33 {
+        return Err(ShapeFileErrorKind::InvalidDBF(format!(
+            "Header length of {header_length} bytes is too short!"
+        ))
+        .into());
+    }
+
+    let mut descriptors = vec![0u8; header_length - 32];
+    dbf_reader.read_exact(&mut descriptors)?;
+    let mut fields = Vec::new();
+    for descriptor in descriptors.chunks_exact(32) {
+        if descriptor[0] == 0x0D {
+            break;
+        }
+        let name = &descriptor[..11];
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(11)];
+        fields.push(DBFField {
+            name: String::from_utf8_lossy(name).trim().to_string(),
+            kind: descriptor[11] as char,
+            length: descriptor[16],
+            decimals: descriptor[17],
+        });
+    }
+    Ok(DBFHeader {
+        record_count,
+        fields,
+    })
+}
+
+// Projected systems commonly used without a .prj, by the eastings and northings they span.
+// UTM includes the false northing of southern zones, so covers every zone.
+const PROJECTED_RANGES: [(&str, [f64; 4]); 3] = [
+    ("UTM", [100_000.0, 0.0, 900_000.0, 10_000_000.0]),
+    ("EPSG:27700", [0.0, 0.0, 700_000.0, 1_300_000.0]),
+    (
+        "EPSG:3857",
+        [-20_037_508.34, -20_048_966.1, 20_037_508.34, 20_048_966.1],
+    ),
+];
+
+// Without a .prj, bounds within longitude and latitude are assumed to be EPSG:4326.
+// This is only a range check, small projected extents near their origin pass for degrees.
+// Bounds beyond it are left unprojected, with the projected systems they fit tagged as CRSGuess.
+fn assume_crs(header: &ShapeFileHeader, tags: &mut Vec<(String, String)>) {
+    let within = |[x_min, y_min, x_max, y_max]: [f64; 4]| {
+        (x_min..=x_max).contains(&header.x_min)
+            && (x_min..=x_max).contains(&header.x_max)
+            && (y_min..=y_max).contains(&header.y_min)
+            && (y_min..=y_max).contains(&header.y_max)
+    };
+    if within([-180.0, -90.0, 180.0, 90.0]) {
+        event!(
+            Level::WARN,
+            "Shapefile without accompanying projection found, assuming EPSG:4326 from its bounds!"
+        );
+        tags.push(("CRS".to_string(), "EPSG:4326".to_string()));
+    } else {
+        event!(
+            Level::WARN,
+            "Shapefile without accompanying projection has bounds beyond longitude and latitude!"
+        );
+        tags.push(("CoordinatesOutOfRange".to_string(), "true".to_string()));
+        for (name, _) in PROJECTED_RANGES.iter().filter(|(_, range)| within(*range)) {
+            tags.push(("CRSGuess".to_string(), name.to_string()));
+        }
+    }
+    tags.push(("CRSUnverified".to_string(), "true".to_string()));
 }
 
 pub fn parse_shapefile(
     shp_reader: &mut BufReader<File>,
     prj_reader: Option<&mut BufReader<File>>,
+    shx_reader: Option<&mut BufReader<File>>,
+    dbf_reader: Option<&mut BufReader<File>>,
+    cpg_reader: Option<&mut BufReader<File>>,
 ) -> Result<ShapeFileMetaData, Box<dyn Error>> {
-    let to_proj = Proj::from_proj_string(crs_definitions::EPSG_4326.proj4)
-        .expect("FAILED TO BUILD DEFAULT PROJ!");
-    let tags = vec![("Filetype".to_string(), "SHAPEFILE".to_string())];
-    let mut header_buf = [0u8; 100];
-    shp_reader.read_exact(&mut header_buf)?;
-    let header = parse_header(&header_buf)?;
+    let mut tags = vec![("Filetype".to_string(), "SHAPEFILE".to_string())];
+    let header = read_header(shp_reader)?;
+    tags.push((
+        "ShapeType".to_string(),
+        shape_type_name(header.shape_type)
+            .map_or_else(|| header.shape_type.to_string(), str::to_string),
+    ));
+
+    // Companion files only add tags, so a broken one is reported rather than failing the shapefile.
+    let shx_count = match shx_reader.map(parse_shx).transpose() {
+        Ok(count) => count,
+        Err(e) => {
+            event!(Level::WARN, "Failed to read shapefile index: {e}");
+            None
+        }
+    };
+    let dbf = match dbf_reader.map(parse_dbf).transpose() {
+        Ok(dbf) => dbf,
+        Err(e) => {
+            event!(Level::WARN, "Failed to read shapefile attributes: {e}");
+            None
+        }
+    };
+    match (
+        shx_count,
+        dbf.as_ref().map(|dbf| u64::from(dbf.record_count)),
+    ) {
+        (Some(shx_count), Some(dbf_count)) if shx_count != dbf_count => {
+            event!(
+                Level::WARN,
+                "Shapefile index has {shx_count} records, but its attributes have {dbf_count}!"
+            );
+            tags.push(("RecordCount".to_string(), shx_count.to_string()));
+            tags.push(("DBFRecordCount".to_string(), dbf_count.to_string()));
+        }
+        (Some(count), _) | (None, Some(count)) => {
+            tags.push(("RecordCount".to_string(), count.to_string()))
+        }
+        (None, None) => {}
+    }
+    if let Some(dbf) = dbf {
+        let names: Vec<&str> = dbf.fields.iter().map(|f| f.name.as_str()).collect();
+        tags.push(("Fields".to_string(), names.join(",")));
+        for field in dbf.fields.iter() {
+            tags.push((format!("Field.{}", field.name), field.to_string()));
+        }
+    }
+    if let Some(cpg_reader) = cpg_reader {
+        let mut encoding = String::new();
+        match cpg_reader.take(256).read_to_string(&mut encoding) {
+            Ok(_) if !encoding.trim().is_empty() => {
+                tags.push(("Encoding".to_string(), encoding.trim().to_string()))
+            }
+            Ok(_) => {}
+            Err(e) => event!(Level::WARN, "Failed to read shapefile encoding: {e}"),
+        }
+    }
 
     if let Some(prj_reader) = prj_reader {
         let mut prj_content = String::new();
         prj_reader.read_to_string(&mut prj_content)?;
-        let proj = Proj::from_proj_string(wkt_to_projstring(prj_content.as_str())?.as_str())?;
-        let (mut top_left, mut bottom_right) = match proj.projection_type() {
-            ProjType::Latlong => (
-                (header.x_min.to_radians(), header.y_max.to_radians()),
-                (header.x_max.to_radians(), header.y_min.to_radians()),
-            ),
-            ProjType::Other => ((header.x_min, header.y_max), (header.x_max, header.y_min)),
-            ProjType::Geocentric => {
-                event!(Level::ERROR, "Unsupported projection! From GEOCENTRIC!");
-                event!(
-                    Level::ERROR,
-                    "Please contact developer, and send file content for implementation."
-                );
-                panic!();
-            }
-        };
-
+        let proj = prj_projection(crs::registry(), &prj_content)?;
+        tags.push(("CRS".to_string(), crs::wkt_label(&prj_content)));
         event!(
             Level::INFO,
-            "Applying Projection to {top_left:?} and {bottom_right:?}"
+            "Applying Projection to ({}, {}), ({}, {})",
+            header.x_min,
+            header.y_max,
+            header.x_max,
+            header.y_min
         );
-        proj4rs::transform::transform(&proj, &to_proj, &mut top_left)?;
-        proj4rs::transform::transform(&proj, &to_proj, &mut bottom_right)?;
+        let corners = [
+            (header.x_min, header.y_max),
+            (header.x_max, header.y_max),
+            (header.x_max, header.y_min),
+            (header.x_min, header.y_min),
+        ];
+        let extent = reproject_extent(&corners, &proj)?;
+        if extent.crosses_antimeridian {
+            tags.push(("CrossesAntimeridian".to_string(), "true".to_string()));
+        }
+        let footprint = match extent.wraps() {
+            true => None,
+            false => Some(to_wgs84(&densify(&corners, FOOTPRINT_SAMPLES), &proj)?),
+        };
         event!(Level::INFO, "Parsed shapefile and applied projection!");
 
         return Ok(ShapeFileMetaData {
             region: Region {
-                top_left: (top_left.0.to_degrees(), top_left.1.to_degrees()),
-                bottom_right: (bottom_right.0.to_degrees(), bottom_right.1.to_degrees()),
+                top_left: extent.top_left(),
+                bottom_right: extent.bottom_right(),
             },
             tags,
+            footprint,
         });
     } else {
-        event!(
-            Level::WARN,
-            "Shapefile without accompanying projection found!"
-        );
-        event!(
-            Level::WARN,
-            "This is a forseen error, and we will assume that the CRS is EPSG:4326!"
-        );
-        event!(
-            Level::WARN,
-            "However, this might be incorrect! If you encounter inaccuracies in shapefile"
-        );
-        event!(
-            Level::WARN,
-            "Please contact the developers, and attach the unhandled file!"
-        );
         // TODO: Add a config option to disable this behaviour!
-        let mut top_left = (header.x_min, header.y_max);
-        let mut bottom_right = (header.x_max, header.y_min);
+        assume_crs(&header, &mut tags);
+        let top_left = (header.x_min, header.y_max);
+        let bottom_right = (header.x_max, header.y_min);
         return Ok(ShapeFileMetaData {
             region: Region {
                 top_left,
                 bottom_right,
             },
             tags,
+            footprint: None,
         });
     }
 }
@@ -154,6 +402,7 @@ pub fn parse_shapefile(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parsing::tests::{tag, tags};
     use std::io;
     use std::io::{Seek, Write};
     use tempfile::NamedTempFile;
@@ -161,8 +410,9 @@ mod tests {
     #[test]
     fn test_from_bytes_f64() {
         let bytes = 42.42f64.to_le_bytes();
-        let result = f64::from_bytes(&bytes);
+        let result = f64::from_bytes(&bytes).unwrap();
         assert!((result - 42.42).abs() < f64::EPSILON);
+        assert!(f64::from_bytes(&bytes[..7]).is_none());
     }
     #[test]
     fn test_parse_header_valid() {
@@ -216,7 +466,7 @@ mod tests {
         shp_reader.get_mut().sync_all().unwrap();
         shp_reader.get_mut().seek(io::SeekFrom::Start(0)).unwrap();
 
-        let result = parse_shapefile(&mut shp_reader, None);
+        let result = parse_shapefile(&mut shp_reader, None, None, None, None);
         assert!(result.is_err());
     }
     #[test]
@@ -226,7 +476,7 @@ mod tests {
         let (mut shp_reader, mut prj_reader) =
             create_temp_shapefile(&header_bytes, Some(prj_content));
 
-        let result = parse_shapefile(&mut shp_reader, prj_reader.as_mut());
+        let result = parse_shapefile(&mut shp_reader, prj_reader.as_mut(), None, None, None);
         assert!(result.is_err());
     }
     #[test]
@@ -234,7 +484,7 @@ mod tests {
         let header_bytes = []; // Empty header size
         let (mut shp_reader, mut prj_reader) = create_temp_shapefile(&header_bytes, None);
 
-        let result = parse_shapefile(&mut shp_reader, prj_reader.as_mut());
+        let result = parse_shapefile(&mut shp_reader, prj_reader.as_mut(), None, None, None);
         assert!(result.is_err());
     }
     #[test]
@@ -251,16 +501,231 @@ mod tests {
 
         let (mut shp_reader, mut prj_reader) = create_temp_shapefile(&header_bytes, None);
 
-        let result = parse_shapefile(&mut shp_reader, prj_reader.as_mut());
+        let result = parse_shapefile(&mut shp_reader, prj_reader.as_mut(), None, None, None);
         assert!(result.is_ok(), "Should handle large coordinates gracefully");
     }
 
+    #[test]
+    fn test_parse_shapefile_across_antimeridian() {
+        let mut header_bytes = vec![0; 100];
+        header_bytes[2] = 39;
+        header_bytes[3] = 10;
+        for (offset, value) in [(36, 170.0f64), (44, -20.0), (52, 190.0), (60, -10.0)] {
+            header_bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+        let (mut shp_reader, mut prj_reader) =
+            create_temp_shapefile(&header_bytes, Some(crs_definitions::EPSG_4326.wkt));
+
+        let metadata =
+            parse_shapefile(&mut shp_reader, prj_reader.as_mut(), None, None, None).unwrap();
+        assert_eq!(metadata.region.top_left.0, -180.0);
+        assert_eq!(metadata.region.bottom_right.0, 180.0);
+        assert!(metadata
+            .tags
+            .contains(&("CrossesAntimeridian".to_string(), "true".to_string())));
+        assert!(metadata
+            .tags
+            .contains(&("CRS".to_string(), "EPSG:4326".to_string())));
+    }
+
     #[test]
     fn test_parse_shapefile_with_incorrect_header_size() {
         let header_bytes = [0; 90]; // Incorrect header size
         let (mut shp_reader, mut prj_reader) = create_temp_shapefile(&header_bytes, None);
 
-        let result = parse_shapefile(&mut shp_reader, prj_reader.as_mut());
-        assert!(result.is_err());
+        let result = parse_shapefile(&mut shp_reader, prj_reader.as_mut(), None, None, None);
+        assert!(matches!(
+            error_kind(result),
+            Some(ShapeFileErrorKind::ShortHeader(90))
+        ));
+        assert!(matches!(
+            parse_header(&[0; 10]),
+            Err(ShapeFileErrorKind::ShortHeader(10))
+        ));
+    }
+
+    fn error_kind(result: Result<ShapeFileMetaData, Box<dyn Error>>) -> Option<ShapeFileErrorKind> {
+        result
+            .err()?
+            .downcast::<ShapeFileErrorKind>()
+            .ok()
+            .map(|e| *e)
+    }
+
+    #[test]
+    fn test_parse_shapefile_non_finite_bounds() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut shp = reader(&mock_header(5, 100, [0.0, 0.0, value, 1.0]));
+            let result = parse_shapefile(&mut shp, None, None, None, None);
+            assert!(matches!(
+                error_kind(result),
+                Some(ShapeFileErrorKind::NonFiniteBounds(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_shapefile_malformed_wkt() {
+        let mut shp = reader(&mock_header(5, 100, [0.0, 0.0, 1.0, 1.0]));
+        let mut prj = reader(b"PROJCS[\"Broken\",GEOGCS[");
+        let result = parse_shapefile(&mut shp, Some(&mut prj), None, None, None);
+        assert!(matches!(
+            error_kind(result),
+            Some(ShapeFileErrorKind::InvalidWKT(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_shapefile_geocentric_crs() {
+        let wkt = r#"GEOCCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563]],PRIMEM["Greenwich",0],UNIT["metre",1],AUTHORITY["EPSG","4978"]]"#;
+        let mut shp = reader(&mock_header(5, 100, [0.0, 0.0, 1.0, 1.0]));
+        let mut prj = reader(wkt.as_bytes());
+        let result = parse_shapefile(&mut shp, Some(&mut prj), None, None, None);
+        assert!(matches!(
+            error_kind(result),
+            Some(ShapeFileErrorKind::GeocentricCRS(_))
+        ));
+
+        // Also when the code is defined as geocentric, whatever the WKT says.
+        let registry =
+            crs::CRSRegistry::from_reader("EPSG:4978 +proj=geocent +datum=WGS84".as_bytes())
+                .unwrap();
+        let wkt = r#"GEOGCS["Mislabelled",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563]],PRIMEM["Greenwich",0],UNIT["degree",0.0174532925199433],AUTHORITY["EPSG","4978"]]"#;
+        assert!(matches!(
+            prj_projection(&registry, wkt),
+            Err(ShapeFileErrorKind::GeocentricCRS(_))
+        ));
+    }
+
+    fn mock_header(shape_type: i32, file_length: u32, bounds: [f64; 4]) -> Vec<u8> {
+        let mut header = vec![0u8; 100];
+        header[2] = 39;
+        header[3] = 10;
+        header[24..28].copy_from_slice(&(file_length / 2).to_be_bytes());
+        header[32..36].copy_from_slice(&shape_type.to_le_bytes());
+        for (index, value) in bounds.iter().enumerate() {
+            header[36 + index * 8..44 + index * 8].copy_from_slice(&value.to_le_bytes());
+        }
+        header
+    }
+
+    fn mock_dbf(record_count: u32, fields: &[(&str, u8, u8, u8)]) -> Vec<u8> {
+        let mut dbf = vec![0u8; 32];
+        dbf[0] = 3;
+        dbf[4..8].copy_from_slice(&record_count.to_le_bytes());
+        dbf[8..10].copy_from_slice(&(33 + 32 * fields.len() as u16).to_le_bytes());
+        for (name, kind, length, decimals) in fields {
+            let mut descriptor = [0u8; 32];
+            descriptor[..name.len()].copy_from_slice(name.as_bytes());
+            descriptor[11] = *kind;
+            descriptor[16] = *length;
+            descriptor[17] = *decimals;
+            dbf.extend_from_slice(&descriptor);
+        }
+        dbf.push(0x0D);
+        dbf
+    }
+
+    fn reader(content: &[u8]) -> BufReader<File> {
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(content).unwrap();
+        BufReader::new(temp.reopen().unwrap())
+    }
+
+    #[test]
+    fn test_parse_shapefile_companions() {
+        let bounds = [-3.0, 50.0, 2.0, 55.0];
+        let mut shp = reader(&mock_header(15, 100, bounds));
+        let mut shx = reader(&mock_header(15, 100 + 3 * 8, bounds));
+        let mut dbf = reader(&mock_dbf(
+            3,
+            &[
+                ("NAME", b'C', 20, 0),
+                ("AREA", b'N', 12, 3),
+                ("BUILT", b'D', 8, 0),
+            ],
+        ));
+        let mut cpg = reader(b"UTF-8\r\n");
+        let metadata = parse_shapefile(
+            &mut shp,
+            None,
+            Some(&mut shx),
+            Some(&mut dbf),
+            Some(&mut cpg),
+        )
+        .unwrap();
+        assert_eq!(tag(&metadata.tags, "ShapeType"), Some("PolygonZ"));
+        assert_eq!(tag(&metadata.tags, "RecordCount"), Some("3"));
+        assert!(tag(&metadata.tags, "DBFRecordCount").is_none());
+        assert_eq!(tag(&metadata.tags, "Fields"), Some("NAME,AREA,BUILT"));
+        assert_eq!(tag(&metadata.tags, "Field.NAME"), Some("Character(20)"));
+        assert_eq!(tag(&metadata.tags, "Field.AREA"), Some("Numeric(12,3)"));
+        assert_eq!(tag(&metadata.tags, "Field.BUILT"), Some("Date(8)"));
+        assert_eq!(tag(&metadata.tags, "Encoding"), Some("UTF-8"));
+    }
+
+    #[test]
+    fn test_parse_shapefile_record_count_mismatch() {
+        let bounds = [0.0, 0.0, 1.0, 1.0];
+        let mut shp = reader(&mock_header(1, 100, bounds));
+        let mut shx = reader(&mock_header(1, 100 + 2 * 8, bounds));
+        let mut dbf = reader(&mock_dbf(5, &[]));
+        let metadata =
+            parse_shapefile(&mut shp, None, Some(&mut shx), Some(&mut dbf), None).unwrap();
+        assert_eq!(tag(&metadata.tags, "ShapeType"), Some("Point"));
+        assert_eq!(tag(&metadata.tags, "RecordCount"), Some("2"));
+        assert_eq!(tag(&metadata.tags, "DBFRecordCount"), Some("5"));
+    }
+
+    #[test]
+    fn test_parse_shapefile_broken_companions() {
+        // A truncated index and attribute table are reported, but the shapefile is still indexed.
+        let bounds = [0.0, 0.0, 1.0, 1.0];
+        let mut shp = reader(&mock_header(99, 100, bounds));
+        let mut shx = reader(&mock_header(1, 100 + 5, bounds));
+        let mut dbf = reader(&[3, 0, 0]);
+        let metadata =
+            parse_shapefile(&mut shp, None, Some(&mut shx), Some(&mut dbf), None).unwrap();
+        assert_eq!(tag(&metadata.tags, "ShapeType"), Some("99"));
+        assert!(tag(&metadata.tags, "RecordCount").is_none());
+        assert!(tag(&metadata.tags, "Fields").is_none());
+    }
+
+    #[test]
+    fn test_parse_shapefile_assumed_crs() {
+        let mut shp = reader(&mock_header(5, 100, [-3.0, 50.0, 2.0, 55.0]));
+        let metadata = parse_shapefile(&mut shp, None, None, None, None).unwrap();
+        assert_eq!(tag(&metadata.tags, "CRS"), Some("EPSG:4326"));
+        assert_eq!(tag(&metadata.tags, "CRSUnverified"), Some("true"));
+        assert!(tag(&metadata.tags, "CoordinatesOutOfRange").is_none());
+
+        let mut shp = reader(&mock_header(
+            5,
+            100,
+            [529000.0, 179000.0, 531000.0, 181000.0],
+        ));
+        let metadata = parse_shapefile(&mut shp, None, None, None, None).unwrap();
+        assert!(tag(&metadata.tags, "CRS").is_none());
+        assert_eq!(tag(&metadata.tags, "CRSUnverified"), Some("true"));
+        assert_eq!(tag(&metadata.tags, "CoordinatesOutOfRange"), Some("true"));
+        assert_eq!(
+            tags(&metadata.tags, "CRSGuess"),
+            vec!["UTM", "EPSG:27700", "EPSG:3857"]
+        );
+
+        // Beyond a UTM zone or the British National Grid, only Web Mercator fits.
+        let mut shp = reader(&mock_header(
+            5,
+            100,
+            [-1_000_000.0, 6_000_000.0, -900_000.0, 6_100_000.0],
+        ));
+        let metadata = parse_shapefile(&mut shp, None, None, None, None).unwrap();
+        assert_eq!(tags(&metadata.tags, "CRSGuess"), vec!["EPSG:3857"]);
+
+        // A .prj is taken at its word.
+        let mut shp = reader(&mock_header(5, 100, [-3.0, 50.0, 2.0, 55.0]));
+        let mut prj = reader(crs_definitions::EPSG_4326.wkt.as_bytes());
+        let metadata = parse_shapefile(&mut shp, Some(&mut prj), None, None, None).unwrap();
+        assert!(tag(&metadata.tags, "CRSUnverified").is_none());
     }
 }
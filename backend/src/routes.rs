@@ -1,7 +1,9 @@
+use crate::elevation::{candidate_tiles, densify_polyline, haversine, ElevationSampler};
 use crate::index::Node;
 use crate::io::{
-    Page, PaginatedQueryResponse, Pagination, QueryRegion, ResultQuery, SearchQueryResponse,
-    PER_PAGE,
+    CRSUsage, CRSUsageResponse, ElevationProfileQuery, ElevationProfileResponse, ElevationQuery,
+    ElevationResponse, Page, PaginatedQueryResponse, Pagination, ProfileSample, QueryRegion,
+    ResultQuery, SearchQueryResponse, PER_PAGE,
 };
 use crate::worker::QueryState::Waiting;
 use crate::worker::QueryTask;
@@ -9,6 +11,8 @@ use crate::State;
 use axum::extract::Query;
 use axum::http::StatusCode;
 use axum::{Extension, Json};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{event, span, Level};
@@ -102,3 +106,138 @@ pub async fn results(
         None => return Err((StatusCode::NOT_FOUND, "Task not found".to_string())),
     }
 }
+
+// Lists the CRSes used by indexed maps, with how many maps use each.
+pub async fn crs(Extension(state): Extension<Arc<State>>) -> Json<CRSUsageResponse> {
+    let crs_span = span!(Level::INFO, "/crs handler");
+    let _g = crs_span.enter();
+    event!(Level::INFO, "Received CRS usage request!");
+
+    let index = state.i.read().await;
+    let mut usage: BTreeMap<String, usize> = BTreeMap::new();
+    for node in index.iter() {
+        let labels: Vec<&String> = node
+            .metadata
+            .tags
+            .iter()
+            .filter(|(k, _)| k == "CRS")
+            .map(|(_, v)| v)
+            .collect();
+        // Maps without a declared CRS are in WGS84.
+        if labels.is_empty() {
+            *usage.entry("EPSG:4326".to_string()).or_default() += 1;
+        }
+        for label in labels {
+            *usage.entry(label.clone()).or_default() += 1;
+        }
+    }
+
+    Json(CRSUsageResponse {
+        crs: usage
+            .into_iter()
+            .map(|(crs, count)| CRSUsage { crs, count })
+            .collect(),
+    })
+}
+
+// Most points sampled by a single profile request.
+const MAX_PROFILE_POINTS: usize = 10_000;
+
+// Failure of a blocking sampling task, such as a panic while reading tiles.
+fn sampling_failed(e: tokio::task::JoinError) -> (StatusCode, String) {
+    event!(Level::ERROR, "Elevation sampling failed: {e:?}");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to sample elevations!".to_string(),
+    )
+}
+
+pub async fn elevation(
+    Extension(state): Extension<Arc<State>>,
+    Query(query): Query<ElevationQuery>,
+) -> Result<Json<ElevationResponse>, (StatusCode, String)> {
+    let elevation_span = span!(Level::INFO, "/elevation handler");
+    let _g = elevation_span.enter();
+    event!(Level::INFO, "Received elevation request: {query:?}");
+
+    let point = (query.lon, query.lat);
+    let candidates = candidate_tiles(&*state.i.read().await, point);
+    // Tiles are read without holding the index, and off the async runtime.
+    let elevation = tokio::task::spawn_blocking(move || {
+        ElevationSampler::default().elevation(point, &candidates)
+    })
+    .await
+    .map_err(sampling_failed)?;
+    Ok(Json(ElevationResponse {
+        lon: query.lon,
+        lat: query.lat,
+        elevation,
+    }))
+}
+
+pub async fn elevation_profile(
+    Extension(state): Extension<Arc<State>>,
+    Json(query): Json<ElevationProfileQuery>,
+) -> Result<Json<ElevationProfileResponse>, (StatusCode, String)> {
+    let profile_span = span!(Level::INFO, "/elevation/profile handler");
+    let _g = profile_span.enter();
+    event!(
+        Level::INFO,
+        "Received elevation profile request of {} points",
+        query.points.len()
+    );
+
+    if query.points.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "No points given!".to_string()));
+    }
+    // Checked before densifying, so a huge sample count can't exhaust memory.
+    let segments = query.points.len() - 1;
+    let count = match query.samples_per_segment {
+        Some(samples) => segments
+            .saturating_mul(samples.saturating_add(1))
+            .saturating_add(1),
+        None => query.points.len(),
+    };
+    if count > MAX_PROFILE_POINTS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Too many points, at most {MAX_PROFILE_POINTS} can be sampled!"),
+        ));
+    }
+    let points = match query.samples_per_segment {
+        Some(samples) => densify_polyline(&query.points, samples),
+        None => query.points,
+    };
+
+    let candidates: Vec<Vec<PathBuf>> = {
+        let index = state.i.read().await;
+        points
+            .iter()
+            .map(|point| candidate_tiles(&index, *point))
+            .collect()
+    };
+    // Tiles are read without holding the index, and off the async runtime.
+    let profile = tokio::task::spawn_blocking(move || {
+        let mut sampler = ElevationSampler::default();
+        let mut distance = 0.0;
+        points
+            .iter()
+            .zip(&candidates)
+            .enumerate()
+            .map(|(i, (point, candidates))| {
+                if i > 0 {
+                    distance += haversine(points[i - 1], *point);
+                }
+                ProfileSample {
+                    lon: point.0,
+                    lat: point.1,
+                    distance,
+                    elevation: sampler.elevation(*point, candidates),
+                }
+            })
+            .collect()
+    })
+    .await
+    .map_err(sampling_failed)?;
+    Ok(Json(ElevationProfileResponse { profile }))
+}
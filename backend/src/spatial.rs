@@ -4,7 +4,6 @@ use crate::parsing::geojson::GeoJSONRegion;
 use crate::parsing::gpkg::GPKGRegion;
 use crate::parsing::kml::KMLRegion;
 use crate::parsing::mbtiles::MBTilesRegion;
-use geotiff::GeoTiffRegion;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
@@ -35,17 +34,177 @@ impl Region {
     pub fn top_right(&self) -> Coordinate {
         (self.bottom_right.0, self.top_left.1)
     }
-}
 
-impl From<GeoTiffRegion> for Region {
-    fn from(t: GeoTiffRegion) -> Region {
+    // Smallest region containing all the given points.
+    pub fn envelope(points: &[Coordinate]) -> Region {
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in points {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
         Region {
-            top_left: t.top_left,
-            bottom_right: t.bottom_right,
+            top_left: (min_x, max_y),
+            bottom_right: (max_x, min_y),
+        }
+    }
+
+    // (min_x, min_y, max_x, max_y), whichever way round the corners were given.
+    fn bounds(&self) -> (f64, f64, f64, f64) {
+        let (a, b) = (self.top_left, self.bottom_right);
+        (a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1))
+    }
+
+    pub fn contains(&self, (x, y): Coordinate) -> bool {
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y)
+    }
+
+    pub fn intersects(&self, other: &Region) -> bool {
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        let (other_min_x, other_min_y, other_max_x, other_max_y) = other.bounds();
+        min_x <= other_max_x && other_min_x <= max_x && min_y <= other_max_y && other_min_y <= max_y
+    }
+
+    // Exact test of whether a polygon, given as its ring of vertices, overlaps the region.
+    pub fn intersects_polygon(&self, polygon: &[Coordinate]) -> bool {
+        if polygon.iter().any(|p| self.contains(*p)) {
+            return true;
+        }
+        let corners = [
+            self.top_left(),
+            self.top_right(),
+            self.bottom_right(),
+            self.bottom_left(),
+        ];
+        if corners.iter().any(|c| polygon_contains(polygon, *c)) {
+            return true;
+        }
+        // Neither holds a vertex of the other, so they only overlap if their edges cross.
+        edges(polygon)
+            .any(|(p1, p2)| edges(&corners).any(|(q1, q2)| segments_intersect(p1, p2, q1, q2)))
+    }
+}
+
+// Coordinates kept for a footprint before being reduced to their convex hull.
+const FOOTPRINT_BUFFER: usize = 4096;
+
+// A footprint gathered point by point, reduced to its convex hull once over the buffer.
+// The hull itself may be larger than the buffer, so the next reduction waits until the points
+// have doubled, keeping the cost of reducing amortised over the points added.
+#[derive(Debug, Default)]
+pub struct FootprintBuffer {
+    pub points: Vec<Coordinate>,
+    limit: usize,
+}
+
+impl FootprintBuffer {
+    pub fn push(&mut self, point: Coordinate) {
+        self.points.push(point);
+        if self.points.len() > self.limit.max(FOOTPRINT_BUFFER) {
+            self.points = convex_hull(&self.points);
+            self.limit = self.points.len() * 2;
+        }
+    }
+
+    pub fn extend(&mut self, points: impl IntoIterator<Item = Coordinate>) {
+        for point in points {
+            self.push(point);
         }
     }
 }
 
+// The index can't wrap, so geometry across the antimeridian is tagged and widened to every longitude,
+// without a footprint. Otherwise the points are the footprint, if there are any.
+pub fn antimeridian_footprint(
+    points: Vec<Coordinate>,
+    wraps: bool,
+    (west, east): (&mut f64, &mut f64),
+    tags: &mut Vec<(String, String)>,
+) -> Option<Vec<Coordinate>> {
+    if wraps {
+        tags.push(("CrossesAntimeridian".to_string(), "true".to_string()));
+        (*west, *east) = (-180.0, 180.0);
+        return None;
+    }
+    (!points.is_empty()).then_some(points)
+}
+
+// Convex hull of a set of points, anticlockwise, without repeating the first point.
+// Used to simplify footprints, as it always covers the original geometry.
+pub fn convex_hull(points: &[Coordinate]) -> Vec<Coordinate> {
+    let mut points: Vec<Coordinate> = points
+        .iter()
+        .copied()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    // Andrew's monotone chain; lower hull, then upper hull back to the start.
+    let mut hull: Vec<Coordinate> = Vec::with_capacity(points.len() + 1);
+    for &point in points.iter() {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(point);
+    }
+    let lower = hull.len() + 1;
+    for &point in points.iter().rev().skip(1) {
+        while hull.len() >= lower && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0
+        {
+            hull.pop();
+        }
+        hull.push(point);
+    }
+    // The upper hull ends with the first point again.
+    hull.pop();
+    hull
+}
+
+// Z component of (b - a) x (c - a); positive when a, b, c turn anticlockwise.
+fn cross(a: Coordinate, b: Coordinate, c: Coordinate) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+fn edges(ring: &[Coordinate]) -> impl Iterator<Item = (Coordinate, Coordinate)> + '_ {
+    (0..ring.len()).map(move |i| (ring[i], ring[(i + 1) % ring.len()]))
+}
+
+// Ray casting, points exactly on an edge may fall either way.
+fn polygon_contains(polygon: &[Coordinate], (x, y): Coordinate) -> bool {
+    let mut inside = false;
+    for ((x1, y1), (x2, y2)) in edges(polygon) {
+        if (y1 > y) != (y2 > y) && x < (x2 - x1) * (y - y1) / (y2 - y1) + x1 {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn segments_intersect(p1: Coordinate, p2: Coordinate, q1: Coordinate, q2: Coordinate) -> bool {
+    let on_segment = |a: Coordinate, b: Coordinate, c: Coordinate| {
+        c.0 >= a.0.min(b.0) && c.0 <= a.0.max(b.0) && c.1 >= a.1.min(b.1) && c.1 <= a.1.max(b.1)
+    };
+    let (d1, d2) = (cross(q1, q2, p1), cross(q1, q2, p2));
+    let (d3, d4) = (cross(p1, p2, q1), cross(p1, p2, q2));
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+    // Touching or collinear.
+    (d1 == 0.0 && on_segment(q1, q2, p1))
+        || (d2 == 0.0 && on_segment(q1, q2, p2))
+        || (d3 == 0.0 && on_segment(p1, p2, q1))
+        || (d4 == 0.0 && on_segment(p1, p2, q2))
+}
+
 impl From<KMLRegion> for Region {
     fn from(t: KMLRegion) -> Region {
         Region {
@@ -65,11 +224,9 @@ impl From<GeoJSONRegion> for Region {
 }
 
 impl From<DT2Region> for Region {
+    // Corners needn't be axis aligned, so bound all four.
     fn from(t: DT2Region) -> Region {
-        Region {
-            top_left: t.top_left,
-            bottom_right: t.bottom_right,
-        }
+        Region::envelope(&[t.top_left, t.top_right, t.bottom_right, t.bottom_left])
     }
 }
 
@@ -103,16 +260,52 @@ impl From<GPKGRegion> for Region {
 mod tests {
     use super::*;
 
-    // Test conversion from GeoTiffRegion to Region
     #[test]
-    fn test_convert_from_geotiff_region() {
-        let geo_tiff_region = GeoTiffRegion {
-            top_left: (1.0, 2.0),
-            bottom_right: (3.0, 4.0),
+    fn test_footprint_buffer() {
+        let mut buffer = FootprintBuffer::default();
+        buffer.extend((0..=FOOTPRINT_BUFFER).map(|i| ((i % 2) as f64, i as f64)));
+        assert_eq!(buffer.points.len(), 4);
+    }
+
+    #[test]
+    fn test_footprint_buffer_large_hull() {
+        // Every point of a ring is on its hull, which can't be reduced below the buffer.
+        let ring = |n: usize| {
+            (0..n).map(move |i| {
+                let angle = i as f64 / n as f64 * std::f64::consts::TAU;
+                (angle.cos(), angle.sin())
+            })
         };
-        let region: Region = geo_tiff_region.into();
-        assert_eq!(region.top_left, (1.0, 2.0));
-        assert_eq!(region.bottom_right, (3.0, 4.0));
+        let mut buffer = FootprintBuffer::default();
+        buffer.extend(ring(FOOTPRINT_BUFFER + 1));
+        assert_eq!(buffer.points.len(), FOOTPRINT_BUFFER + 1);
+        // So the next points are kept until the buffer has doubled, rather than each re-reducing it.
+        buffer.extend([(0.0, 0.0), (0.1, 0.1)]);
+        assert_eq!(buffer.points.len(), FOOTPRINT_BUFFER + 3);
+        // Until the points double, when those inside the ring are dropped.
+        buffer.extend(ring(FOOTPRINT_BUFFER).map(|(x, y)| (x * 0.5, y * 0.5)));
+        assert_eq!(buffer.points.len(), FOOTPRINT_BUFFER + 1);
+    }
+
+    #[test]
+    fn test_antimeridian_footprint() {
+        let mut tags = vec![];
+        let (mut west, mut east) = (170.0, 190.0);
+        let footprint =
+            antimeridian_footprint(vec![(170.0, 0.0)], true, (&mut west, &mut east), &mut tags);
+        assert!(footprint.is_none());
+        assert_eq!((west, east), (-180.0, 180.0));
+        assert_eq!(
+            tags,
+            vec![("CrossesAntimeridian".to_string(), "true".to_string())]
+        );
+
+        let mut tags = vec![];
+        let footprint =
+            antimeridian_footprint(vec![(1.0, 2.0)], false, (&mut west, &mut east), &mut tags);
+        assert_eq!(footprint, Some(vec![(1.0, 2.0)]));
+        assert!(tags.is_empty());
+        assert!(antimeridian_footprint(vec![], false, (&mut west, &mut east), &mut tags).is_none());
     }
 
     // Test conversion from KMLRegion to Region
@@ -149,8 +342,21 @@ mod tests {
             bottom_right: (3.0, 4.0),
         };
         let region: Region = dt2_region.into();
-        assert_eq!(region.top_left, (1.0, 2.0));
-        assert_eq!(region.bottom_right, (3.0, 4.0));
+        assert_eq!(region.top_left, (1.0, 4.0));
+        assert_eq!(region.bottom_right, (3.0, 2.0));
+    }
+
+    #[test]
+    fn test_convert_from_skewed_dt2_region() {
+        let dt2_region = DT2Region {
+            top_left: (0.5, 2.0),
+            top_right: (2.0, 2.5),
+            bottom_left: (0.0, 0.0),
+            bottom_right: (1.5, 0.5),
+        };
+        let region: Region = dt2_region.into();
+        assert_eq!(region.top_left, (0.0, 2.5));
+        assert_eq!(region.bottom_right, (2.0, 0.0));
     }
     #[test]
     fn test_convert_from_mbtiles_region() {
@@ -194,4 +400,75 @@ mod tests {
         );
         assert_eq!(region.top_right(), (3.0, 4.0), "top_right method failed");
     }
+
+    #[test]
+    fn test_convex_hull() {
+        let points = [
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (1.0, 1.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (1.0, 0.0),
+            (0.0, 0.0),
+        ];
+        assert_eq!(
+            convex_hull(&points),
+            vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]
+        );
+        assert_eq!(convex_hull(&[(1.0, 1.0)]), vec![(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_intersects_rotated_polygon() {
+        // A diamond, whose envelope is (0, 0) to (2, 2).
+        let diamond = [(1.0, 0.0), (2.0, 1.0), (1.0, 2.0), (0.0, 1.0)];
+        let corner = Region {
+            top_left: (0.0, 0.4),
+            bottom_right: (0.4, 0.0),
+        };
+        assert!(!corner.intersects_polygon(&diamond));
+
+        let centre = Region {
+            top_left: (0.9, 1.1),
+            bottom_right: (1.1, 0.9),
+        };
+        assert!(centre.intersects_polygon(&diamond));
+
+        let covering = Region {
+            top_left: (-1.0, 3.0),
+            bottom_right: (3.0, -1.0),
+        };
+        assert!(covering.intersects_polygon(&diamond));
+    }
+
+    #[test]
+    fn test_intersects_polygon_by_edges_only() {
+        // A thin cross, neither holds a vertex of the other.
+        let bar = [(-1.0, 0.9), (3.0, 0.9), (3.0, 1.1), (-1.0, 1.1)];
+        let column = Region {
+            top_left: (0.9, 3.0),
+            bottom_right: (1.1, -1.0),
+        };
+        assert!(column.intersects_polygon(&bar));
+    }
+
+    #[test]
+    fn test_intersects_region() {
+        let region = Region {
+            top_left: (0.0, 2.0),
+            bottom_right: (2.0, 0.0),
+        };
+        let touching = Region {
+            top_left: (2.0, 1.0),
+            bottom_right: (3.0, -1.0),
+        };
+        assert!(region.intersects(&touching));
+        let apart = Region {
+            top_left: (2.5, 1.0),
+            bottom_right: (3.0, -1.0),
+        };
+        assert!(!region.intersects(&apart));
+        assert!(!apart.intersects(&region));
+    }
 }
@@ -59,14 +59,20 @@ pub async fn worker(state: Arc<State>) {
             task.read().await.region.top_left,
             task.read().await.region.bottom_right,
         );
+        let region = task.read().await.region.clone();
         event!(Level::DEBUG, "Awaiting READ lock on index");
         for v in state
             .i
             .read()
             .await
             .locate_in_envelope_intersecting(&envelope)
+            .filter(|v| v.metadata.intersects(&region))
         {
-            let n = v.clone();
+            let mut n = v.clone();
+            // Only report the features within the query region.
+            if let Some(features) = &mut n.metadata.features {
+                features.retain(|f| f.region.intersects(&region));
+            }
             event!(Level::DEBUG, "Got result: {v:?}");
             event!(Level::DEBUG, "Awaiting WRITE lock on task to add result!");
             task.write().await.results.push(n);
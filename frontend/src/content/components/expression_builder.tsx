@@ -19,7 +19,7 @@ interface AttributeOption {
 const attributeOptions: Record<string, AttributeOption> = {
   FileType: {
     operators: ['=', '!='],
-    values: ['DTED', 'GEOJSON', 'GPKG', 'KML', 'MBTILES', 'SHAPEFILE', 'TIFF'],
+    values: ['ASC', 'DTED', 'GEOJSON', 'GPKG', 'GPX', 'HGT', 'KML', 'KMZ', 'MBTILES', 'NITF', 'SHAPEFILE', 'TIFF'],
   },
   // Resolution: {
   //   operators: ['=', '!=', '>', '<', '>=', '<='],